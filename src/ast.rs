@@ -0,0 +1,630 @@
+// ast.rs
+//
+// Typed syntax tree for Bicep files.
+
+use std::fmt;
+
+/// A point in the source; line and column are 1-based, offset is in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Half-open source range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+
+    /// Source text covered by this span
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.start.offset..self.end.offset).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+/// Identifier with its location
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+/// `// ...`, `/* ... */` or a `#...` pragma, text excludes the delimiters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub kind: CommentKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,
+    Block,
+    /// Linter pragma such as `#disable-next-line no-unused-params`
+    Pragma,
+}
+
+/// Parsed Bicep file
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    /// Comments and pragmas, for inline suppressions
+    pub comments: Vec<Comment>,
+    pub errors: Vec<crate::parser::ParseError>,
+}
+
+impl Program {
+    pub fn params(&self) -> impl Iterator<Item = &ParamDecl> {
+        self.statements.iter().filter_map(|s| match s {
+            Statement::Param(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = &VarDecl> {
+        self.statements.iter().filter_map(|s| match s {
+            Statement::Var(v) => Some(v),
+            _ => None,
+        })
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = &ResourceDecl> {
+        self.statements.iter().filter_map(|s| match s {
+            Statement::Resource(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &ModuleDecl> {
+        self.statements.iter().filter_map(|s| match s {
+            Statement::Module(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &OutputDecl> {
+        self.statements.iter().filter_map(|s| match s {
+            Statement::Output(o) => Some(o),
+            _ => None,
+        })
+    }
+
+    /// Every declared symbol with its kind, including child resources nested in bodies
+    pub fn symbols(&self) -> Vec<(&'static str, &Ident)> {
+        fn nested<'a>(res: &'a ResourceDecl, out: &mut Vec<(&'static str, &'a Ident)>) {
+            if let Some(obj) = res.body_object() {
+                for child in &obj.resources {
+                    out.push(("Resource", &child.name));
+                    nested(child, out);
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        for stmt in &self.statements {
+            let kind = match stmt {
+                Statement::Param(_) => "Parameter",
+                Statement::Var(_) => "Variable",
+                Statement::Resource(_) => "Resource",
+                Statement::Module(_) => "Module",
+                Statement::Output(_) => "Output",
+                _ => continue,
+            };
+            if let Some(name) = stmt.name() {
+                out.push((kind, name));
+            }
+            if let Statement::Resource(r) = stmt {
+                nested(r, &mut out);
+            }
+        }
+        out
+    }
+
+    /// Visit every expression in every statement, including nested resources
+    pub fn walk_exprs(&self, visit: &mut dyn FnMut(&Expr)) {
+        for stmt in &self.statements {
+            stmt.walk_exprs(visit);
+        }
+    }
+}
+
+/// Top-level declarations
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Param(ParamDecl),
+    Var(VarDecl),
+    Resource(ResourceDecl),
+    Module(ModuleDecl),
+    Output(OutputDecl),
+    TargetScope(TargetScopeDecl),
+    Metadata(MetadataDecl),
+    Type(TypeDecl),
+    /// import / using / extension / func and anything else kept as raw text
+    Other(OtherDecl),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Param(d) => d.span,
+            Statement::Var(d) => d.span,
+            Statement::Resource(d) => d.span,
+            Statement::Module(d) => d.span,
+            Statement::Output(d) => d.span,
+            Statement::TargetScope(d) => d.span,
+            Statement::Metadata(d) => d.span,
+            Statement::Type(d) => d.span,
+            Statement::Other(d) => d.span,
+        }
+    }
+
+    /// Declared symbol, if the statement introduces one
+    pub fn name(&self) -> Option<&Ident> {
+        match self {
+            Statement::Param(d) => Some(&d.name),
+            Statement::Var(d) => Some(&d.name),
+            Statement::Resource(d) => Some(&d.name),
+            Statement::Module(d) => Some(&d.name),
+            Statement::Output(d) => Some(&d.name),
+            Statement::Metadata(d) => Some(&d.name),
+            Statement::Type(d) => Some(&d.name),
+            Statement::TargetScope(_) | Statement::Other(_) => None,
+        }
+    }
+
+    pub fn decorators(&self) -> &[Decorator] {
+        match self {
+            Statement::Param(d) => &d.decorators,
+            Statement::Var(d) => &d.decorators,
+            Statement::Resource(d) => &d.decorators,
+            Statement::Module(d) => &d.decorators,
+            Statement::Output(d) => &d.decorators,
+            Statement::Type(d) => &d.decorators,
+            _ => &[],
+        }
+    }
+
+    pub fn walk_exprs(&self, visit: &mut dyn FnMut(&Expr)) {
+        for dec in self.decorators() {
+            for arg in &dec.args {
+                arg.walk(visit);
+            }
+        }
+        match self {
+            Statement::Param(d) => {
+                if let Some(v) = &d.default {
+                    v.walk(visit);
+                }
+            }
+            Statement::Var(d) => d.value.walk(visit),
+            Statement::Resource(d) => d.body.walk(visit),
+            Statement::Module(d) => d.body.walk(visit),
+            Statement::Output(d) => d.value.walk(visit),
+            Statement::TargetScope(d) => d.value.walk(visit),
+            Statement::Metadata(d) => d.value.walk(visit),
+            Statement::Type(_) | Statement::Other(_) => {}
+        }
+    }
+}
+
+/// `@name(args)` or `@ns.name(args)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decorator {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub args: Vec<Expr>,
+    pub span: Span,
+}
+
+/// Look up a decorator by name, ignoring the `sys.` namespace
+pub fn find_decorator<'a>(decorators: &'a [Decorator], name: &str) -> Option<&'a Decorator> {
+    decorators.iter().find(|d| d.name == name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub type_expr: TypeExpr,
+    pub default: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub type_expr: Option<TypeExpr>,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    /// Full type string, e.g. `Microsoft.Storage/storageAccounts@2022-09-01`
+    pub type_string: String,
+    pub type_span: Span,
+    pub existing: bool,
+    /// Object, `if (...) {...}` or `[for ...: {...}]`
+    pub body: Expr,
+    pub span: Span,
+}
+
+impl ResourceDecl {
+    /// `Microsoft.Storage/storageAccounts`
+    pub fn resource_type(&self) -> &str {
+        self.type_string.split('@').next().unwrap_or("")
+    }
+
+    /// The object literal behind any `if`/`for` wrapper
+    pub fn body_object(&self) -> Option<&ObjectExpr> {
+        self.body.innermost_object()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub path: String,
+    pub path_span: Span,
+    pub body: Expr,
+    pub span: Span,
+}
+
+impl ModuleDecl {
+    pub fn body_object(&self) -> Option<&ObjectExpr> {
+        self.body.innermost_object()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub type_expr: TypeExpr,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetScopeDecl {
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataDecl {
+    pub name: Ident,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDecl {
+    pub decorators: Vec<Decorator>,
+    pub name: Ident,
+    pub value: TypeExpr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtherDecl {
+    pub keyword: String,
+    pub text: String,
+    pub span: Span,
+}
+
+/// Type annotations on params, outputs, vars and type declarations
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    /// `string`, `sys.int`, user-defined type names
+    Named(String, Span),
+    /// `resource 'Microsoft.Storage/storageAccounts@2022-09-01'`
+    Resource(String, Span),
+    /// Literal types such as `'Standard_LRS'` or `3`
+    Literal(Box<Expr>),
+    Array(Box<TypeExpr>, Span),
+    Nullable(Box<TypeExpr>, Span),
+    Union(Vec<TypeExpr>, Span),
+    Object(Vec<(Ident, TypeExpr)>, Span),
+    Tuple(Vec<TypeExpr>, Span),
+}
+
+impl TypeExpr {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeExpr::Literal(e) => e.span,
+            TypeExpr::Named(_, s)
+            | TypeExpr::Resource(_, s)
+            | TypeExpr::Array(_, s)
+            | TypeExpr::Nullable(_, s)
+            | TypeExpr::Union(_, s)
+            | TypeExpr::Object(_, s)
+            | TypeExpr::Tuple(_, s) => *s,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Int(i64),
+    Bool(bool),
+    Null,
+    /// Single-quoted string, possibly interpolated
+    String(Vec<StringPart>),
+    /// `'''...'''`, never interpolated
+    MultilineString(String),
+    Ident(String),
+    Object(ObjectExpr),
+    Array(Vec<Expr>),
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `a.b`, `a.?b` and `a::b`
+    Member {
+        object: Box<Expr>,
+        property: Ident,
+        safe: bool,
+        nested: bool,
+    },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        safe: bool,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Ternary {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    /// `if (cond) body` on resources and modules
+    If {
+        condition: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// `...items` inside an array literal
+    Spread(Box<Expr>),
+    /// `[for item in items: body]` / `[for (item, i) in items: body]`
+    For {
+        item: Ident,
+        index: Option<Ident>,
+        iterable: Box<Expr>,
+        body: Box<Expr>,
+    },
+    Lambda {
+        params: Vec<Ident>,
+        body: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Interpolation(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ObjectExpr {
+    pub properties: Vec<ObjectProperty>,
+    /// Child resources declared inline in a resource body
+    pub resources: Vec<ResourceDecl>,
+}
+
+impl ObjectExpr {
+    pub fn get(&self, key: &str) -> Option<&ObjectProperty> {
+        self.properties
+            .iter()
+            .find(|p| matches!(&p.key, PropertyKey::Name(name) if name == key))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectProperty {
+    pub decorators: Vec<Decorator>,
+    pub key: PropertyKey,
+    pub key_span: Span,
+    /// Property value, or the spread object for `...expr`
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyKey {
+    /// Identifier or plain string key
+    Name(String),
+    /// Interpolated string key such as `'${prefix}-owner'`
+    Interpolated(Expr),
+    /// `...expr` merging another object into this one
+    Spread,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Coalesce,
+    Equals,
+    NotEquals,
+    EqualsInsensitive,
+    NotEqualsInsensitive,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+
+    /// Pre-order traversal over this expression and all children
+    pub fn walk(&self, visit: &mut dyn FnMut(&Expr)) {
+        visit(self);
+        match &self.kind {
+            ExprKind::Int(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Null
+            | ExprKind::MultilineString(_)
+            | ExprKind::Ident(_) => {}
+            ExprKind::String(parts) => {
+                for part in parts {
+                    if let StringPart::Interpolation(e) = part {
+                        e.walk(visit);
+                    }
+                }
+            }
+            ExprKind::Object(obj) => {
+                for prop in &obj.properties {
+                    for dec in &prop.decorators {
+                        for arg in &dec.args {
+                            arg.walk(visit);
+                        }
+                    }
+                    if let PropertyKey::Interpolated(key) = &prop.key {
+                        key.walk(visit);
+                    }
+                    prop.value.walk(visit);
+                }
+                for res in &obj.resources {
+                    res.body.walk(visit);
+                }
+            }
+            ExprKind::Array(items) => {
+                for item in items {
+                    item.walk(visit);
+                }
+            }
+            ExprKind::Call { callee, args } => {
+                callee.walk(visit);
+                for arg in args {
+                    arg.walk(visit);
+                }
+            }
+            ExprKind::Member { object, .. } => object.walk(visit),
+            ExprKind::Index { object, index, .. } => {
+                object.walk(visit);
+                index.walk(visit);
+            }
+            ExprKind::Unary { operand, .. } | ExprKind::Spread(operand) => operand.walk(visit),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                lhs.walk(visit);
+                rhs.walk(visit);
+            }
+            ExprKind::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                condition.walk(visit);
+                then.walk(visit);
+                otherwise.walk(visit);
+            }
+            ExprKind::If { condition, body } => {
+                condition.walk(visit);
+                body.walk(visit);
+            }
+            ExprKind::For { iterable, body, .. } => {
+                iterable.walk(visit);
+                body.walk(visit);
+            }
+            ExprKind::Lambda { body, .. } => body.walk(visit),
+        }
+    }
+
+    /// Unwrap `if`/`for` wrappers down to an object literal
+    pub fn innermost_object(&self) -> Option<&ObjectExpr> {
+        match &self.kind {
+            ExprKind::Object(o) => Some(o),
+            ExprKind::If { body, .. } | ExprKind::For { body, .. } => body.innermost_object(),
+            _ => None,
+        }
+    }
+
+    /// Literal string value when there is no interpolation
+    pub fn as_plain_string(&self) -> Option<String> {
+        match &self.kind {
+            ExprKind::MultilineString(s) => Some(s.clone()),
+            ExprKind::String(parts) => {
+                let mut out = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Text(t) => out.push_str(t),
+                        StringPart::Interpolation(_) => return None,
+                    }
+                }
+                Some(out)
+            }
+            _ => None,
+        }
+    }
+
+    /// Function name for calls to plain or namespaced functions
+    pub fn call_name(&self) -> Option<&str> {
+        match &self.kind {
+            ExprKind::Call { callee, .. } => match &callee.kind {
+                ExprKind::Ident(n) => Some(n),
+                ExprKind::Member { property, .. } => Some(&property.name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,334 @@
+// azure_devops.rs
+//
+// Azure DevOps REST client for pull request reviews: repositories, pull
+// requests, iterations, items and threads. The organization can be given
+// as a name (dev.azure.com), an organization URL, or the collection URL
+// of an Azure DevOps Server, which also lets the client talk to a mock.
+
+use crate::http;
+use crate::models::{
+    AzureDevOpsArgs, ExistingThread, PullRequestFile, PullRequestStatus, Thread, ThreadStatus,
+};
+use crate::services::is_debug_enabled;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+const CLOUD_URL: &str = "https://dev.azure.com";
+
+#[derive(Debug, Deserialize)]
+struct PullRequestIteration {
+    id: i32,
+}
+
+/// `{ "value": [...] }` wrapper of list responses
+#[derive(Debug, Deserialize)]
+struct ListResponse<T> {
+    value: Vec<T>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PullRequestIterationChanges {
+    #[serde(rename = "changeEntries")]
+    change_entries: Vec<IterationChangeEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct IterationChangeEntry {
+    #[serde(rename = "changeTrackingId")]
+    change_tracking_id: i32,
+    #[serde(rename = "changeId")]
+    change_id: i32,
+    item: Option<IterationItem>,
+    #[serde(rename = "changeType")]
+    change_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct IterationItem {
+    #[serde(rename = "objectId")]
+    object_id: Option<String>,
+    #[serde(rename = "originalObjectId")]
+    original_object_id: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequestInfo {
+    #[serde(rename = "sourceRefName")]
+    pub source_ref_name: String,
+}
+
+/// Client for the Git APIs of one project
+pub struct AzureDevOpsClient {
+    client: Client,
+    /// Organization or collection URL without a trailing slash
+    base_url: String,
+    project: String,
+    /// `Authorization` header value
+    auth: String,
+    api_version: String,
+}
+
+impl AzureDevOpsClient {
+    pub fn new(args: &AzureDevOpsArgs) -> Self {
+        AzureDevOpsClient {
+            client: http::client().clone(),
+            base_url: base_url(&args.organization),
+            project: args.project.clone(),
+            auth: format!("Basic {}", BASE64.encode(format!(":{}", args.pat))),
+            api_version: args.api_version.clone(),
+        }
+    }
+
+    /// URL under `_apis/git/repositories/{repo}` with the API version appended
+    fn repo_url(&self, repo: &str, path: &str) -> String {
+        let url = format!(
+            "{}/{}/_apis/git/repositories/{}{}",
+            self.base_url,
+            urlencoding::encode(&self.project),
+            repo,
+            path
+        );
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!("{}{}api-version={}", url, separator, self.api_version)
+    }
+
+    fn pr_url(&self, repo_id: &str, pr_id: i32, path: &str) -> String {
+        self.repo_url(repo_id, &format!("/pullRequests/{}{}", pr_id, path))
+    }
+
+    async fn send(&self, what: &str, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        let debug_enabled = is_debug_enabled();
+        if debug_enabled {
+            if let Some(r) = request.try_clone().and_then(|r| r.build().ok()) {
+                println!("(DEBUG) azure devops {} => {} {}", what, r.method(), r.url());
+            }
+        }
+        let resp = http::send(request.header("Authorization", &self.auth)).await?;
+        if !resp.status().is_success() {
+            let st = resp.status();
+            let body = resp.text().await?;
+            if debug_enabled {
+                eprintln!("(DEBUG) azure devops {} => error: {} => {}", what, st, body);
+            }
+            return Err(format!("Azure DevOps {} API error: status={}, body={}", what, st, body).into());
+        }
+        Ok(resp)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, what: &str, url: &str) -> Result<T, Box<dyn Error>> {
+        Ok(self.send(what, self.client.get(url)).await?.json().await?)
+    }
+
+    async fn get_text(&self, what: &str, url: &str) -> Result<String, Box<dyn Error>> {
+        let request = self.client.get(url).header("Accept", "text/plain");
+        let content = self.send(what, request).await?.text().await?;
+        if is_debug_enabled() {
+            println!("(DEBUG) azure devops {} => received {} bytes", what, content.len());
+        }
+        Ok(content)
+    }
+
+    /// GUID of a repository from its name
+    pub async fn repository_id(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let url = self.repo_url(&urlencoding::encode(name), "");
+        let repo: Repository = self.get_json("repository", &url).await?;
+        Ok(repo.id)
+    }
+
+    /// PR details including the source branch
+    pub async fn pull_request(&self, repo_id: &str, pr_id: i32) -> Result<PullRequestInfo, Box<dyn Error>> {
+        self.get_json("pull request", &self.pr_url(repo_id, pr_id, ""))
+            .await
+    }
+
+    /// Id of the PR's latest iteration; `None` before the first push
+    pub async fn latest_iteration(&self, repo_id: &str, pr_id: i32) -> Result<Option<i32>, Box<dyn Error>> {
+        let url = self.pr_url(repo_id, pr_id, "/iterations");
+        let list: ListResponse<PullRequestIteration> = self.get_json("iterations", &url).await?;
+        Ok(list.value.iter().map(|x| x.id).max())
+    }
+
+    /// Changed Bicep files of a PR iteration
+    pub async fn iteration_bicep_files(
+        &self,
+        repo_id: &str,
+        pr_id: i32,
+        iteration: i32,
+    ) -> Result<Vec<PullRequestFile>, Box<dyn Error>> {
+        let debug = is_debug_enabled();
+        let url = self.pr_url(repo_id, pr_id, &format!("/iterations/{}/changes", iteration));
+        let changes: PullRequestIterationChanges = self.get_json("iteration changes", &url).await?;
+        if debug {
+            println!("(DEBUG) Raw changes response: {}", serde_json::to_string_pretty(&changes)?);
+        }
+
+        let mut results = Vec::new();
+        for entry in changes.change_entries {
+            let item = match entry.item {
+                Some(item) => item,
+                None => continue,
+            };
+            let path = match item.path {
+                Some(path) if path.ends_with(".bicep") => path,
+                _ => continue,
+            };
+            if debug {
+                println!(
+                    "(DEBUG) Found changed file: {} (change {}, objectId: {:?}, originalObjectId: {:?})",
+                    path, entry.change_id, item.object_id, item.original_object_id
+                );
+            }
+            results.push(PullRequestFile {
+                path,
+                change_type: entry.change_type.unwrap_or_else(|| "edit".to_string()),
+                object_id: item.object_id.unwrap_or_default(),
+                original_object_id: item.original_object_id,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Changed Bicep files from the latest iteration
+    pub async fn modified_bicep_files(&self, repo_id: &str, pr_id: i32) -> Result<Vec<PullRequestFile>, Box<dyn Error>> {
+        let files = match self.latest_iteration(repo_id, pr_id).await? {
+            Some(iteration) => self.iteration_bicep_files(repo_id, pr_id, iteration).await?,
+            None => Vec::new(),
+        };
+        if is_debug_enabled() {
+            println!("(DEBUG) Found {} changed .bicep files", files.len());
+            for file in &files {
+                println!("(DEBUG) - {} ({} / {})", file.path, file.change_type, file.object_id);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Content of a file at the tip of a branch
+    pub async fn item_content(&self, repo_id: &str, path: &str, branch: &str) -> Result<String, Box<dyn Error>> {
+        let url = self.repo_url(
+            repo_id,
+            &format!(
+                "/items?path={}&includeContent=true&versionDescriptor.version={}",
+                urlencoding::encode(path),
+                urlencoding::encode(branch)
+            ),
+        );
+        self.get_text("item", &url).await
+    }
+
+    /// File version by its blob object ID, e.g. the PR base version
+    pub async fn blob_content(&self, repo_id: &str, object_id: &str) -> Result<String, Box<dyn Error>> {
+        let url = self.repo_url(repo_id, &format!("/blobs/{}?$format=text", object_id));
+        self.get_text("blob", &url).await
+    }
+
+    pub async fn threads(&self, repo_id: &str, pr_id: i32) -> Result<Vec<ExistingThread>, Box<dyn Error>> {
+        let url = self.pr_url(repo_id, pr_id, "/threads");
+        let list: ListResponse<ExistingThread> = self.get_json("list threads", &url).await?;
+        Ok(list.value)
+    }
+
+    pub async fn create_thread(&self, repo_id: &str, pr_id: i32, thread: &Thread) -> Result<(), Box<dyn Error>> {
+        let url = self.pr_url(repo_id, pr_id, "/threads");
+        self.send("create thread", self.client.post(&url).json(thread))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the text of a thread comment
+    pub async fn update_comment(
+        &self,
+        repo_id: &str,
+        pr_id: i32,
+        thread_id: i32,
+        comment_id: i32,
+        content: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = self.pr_url(
+            repo_id,
+            pr_id,
+            &format!("/threads/{}/comments/{}", thread_id, comment_id),
+        );
+        let body = serde_json::json!({ "content": content });
+        self.send("update comment", self.client.patch(&url).json(&body))
+            .await?;
+        Ok(())
+    }
+
+    /// Set the status of a thread, e.g. to fixed when its finding is gone
+    pub async fn update_thread_status(
+        &self,
+        repo_id: &str,
+        pr_id: i32,
+        thread_id: i32,
+        status: ThreadStatus,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = self.pr_url(repo_id, pr_id, &format!("/threads/{}", thread_id));
+        let body = serde_json::json!({ "status": status });
+        self.send("update thread", self.client.patch(&url).json(&body))
+            .await?;
+        Ok(())
+    }
+
+    /// Post a PR status, e.g. the review result for branch policies
+    pub async fn create_pull_request_status(
+        &self,
+        repo_id: &str,
+        pr_id: i32,
+        status: &PullRequestStatus,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = self.pr_url(repo_id, pr_id, "/statuses");
+        self.send("PR status", self.client.post(&url).json(status))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Organization or collection URL: URLs are used as given, e.g.
+/// `https://dev.azure.com/org` or `https://server/tfs/DefaultCollection`;
+/// a bare name is an organization on dev.azure.com
+fn base_url(organization: &str) -> String {
+    if organization.starts_with("https://") || organization.starts_with("http://") {
+        organization.trim_end_matches('/').to_string()
+    } else {
+        format!("{}/{}", CLOUD_URL, organization.trim_matches('/'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_name_is_a_cloud_organization() {
+        assert_eq!(base_url("contoso"), "https://dev.azure.com/contoso");
+        assert_eq!(base_url("/contoso/"), "https://dev.azure.com/contoso");
+    }
+
+    #[test]
+    fn urls_are_used_as_given() {
+        assert_eq!(base_url("https://dev.azure.com/contoso"), "https://dev.azure.com/contoso");
+        assert_eq!(
+            base_url("http://localhost:8080/tfs/DefaultCollection"),
+            "http://localhost:8080/tfs/DefaultCollection"
+        );
+    }
+
+    #[test]
+    fn trailing_slash_is_dropped() {
+        assert_eq!(base_url("https://dev.azure.com/contoso/"), "https://dev.azure.com/contoso");
+        assert_eq!(
+            base_url("https://server/tfs/DefaultCollection//"),
+            "https://server/tfs/DefaultCollection"
+        );
+    }
+}
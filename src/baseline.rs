@@ -0,0 +1,242 @@
+// baseline.rs
+//
+// Baseline of known findings for adopting the tool on an existing code
+// base. `--write-baseline` stores a fingerprint per finding; later runs
+// with `--baseline <file>` hide findings whose fingerprint is listed, so
+// only newly introduced problems are reported and fail the run.
+//
+// A fingerprint hashes the file, the rule id (or category for LLM
+// findings), the finding text with numbers and punctuation removed, and
+// the symbol of the declaration the finding points into. Line numbers are
+// left out so that edits elsewhere in the file do not invalidate it.
+
+use crate::ast::Program;
+use crate::models::ValidationResult;
+use crate::policy::usage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+
+pub const DEFAULT_BASELINE_FILE: &str = ".bicep-reviewer-baseline.json";
+const BASELINE_VERSION: u32 = 1;
+
+/// One known finding; everything but the fingerprint is for human review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub fingerprint: String,
+    pub file: String,
+    pub rule: String,
+    pub symbol: String,
+    pub finding: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineFile {
+    version: u32,
+    generated_at: String,
+    findings: Vec<BaselineEntry>,
+}
+
+/// Fingerprints loaded from a baseline file
+pub struct Baseline {
+    known: HashSet<String>,
+}
+
+impl Baseline {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| usage(format!("Cannot read baseline {}: {}", path, e)))?;
+        let file: BaselineFile = serde_json::from_str(&text)
+            .map_err(|e| usage(format!("Invalid baseline {}: {}", path, e)))?;
+        if file.version != BASELINE_VERSION {
+            return Err(usage(format!(
+                "Baseline {} has version {}, expected {}; re-create it with --write-baseline",
+                path, file.version, BASELINE_VERSION
+            )));
+        }
+        Ok(Baseline {
+            known: file.findings.into_iter().map(|e| e.fingerprint).collect(),
+        })
+    }
+
+    /// Split a file's findings into new and already known ones
+    pub fn partition(
+        &self,
+        findings: Vec<ValidationResult>,
+        file: &str,
+        program: Option<&Program>,
+    ) -> (Vec<ValidationResult>, Vec<ValidationResult>) {
+        findings
+            .into_iter()
+            .partition(|f| !self.known.contains(&entry(f, file, program).fingerprint))
+    }
+}
+
+/// Baseline entry for a finding in `file` (a path relative to the project root)
+pub fn entry(finding: &ValidationResult, file: &str, program: Option<&Program>) -> BaselineEntry {
+    let rule = finding
+        .rule_id
+        .clone()
+        .unwrap_or_else(|| format!("llm/{}", finding.category.to_lowercase()));
+    let symbol = symbol_of(finding, program).unwrap_or_default();
+    let text = normalize(&finding.finding);
+    let fingerprint = fingerprint(file, &rule, &text, &symbol);
+    BaselineEntry {
+        fingerprint,
+        file: file.to_string(),
+        rule,
+        symbol,
+        finding: finding.finding.clone(),
+    }
+}
+
+/// SHA-256 of the identifying parts as a compact JSON array. Baseline files
+/// store it, so the input format must not change within a BASELINE_VERSION.
+fn fingerprint(file: &str, rule: &str, text: &str, symbol: &str) -> String {
+    let json = serde_json::json!([file, rule, text, symbol]).to_string();
+    format!("{:x}", Sha256::digest(json.as_bytes()))
+}
+
+/// Write entries sorted by file, rule and fingerprint so diffs stay small
+pub fn write(path: &str, mut entries: Vec<BaselineEntry>) -> Result<(), Box<dyn Error>> {
+    entries.sort_by(|a, b| {
+        (&a.file, &a.rule, &a.fingerprint).cmp(&(&b.file, &b.rule, &b.fingerprint))
+    });
+    entries.dedup_by(|a, b| a.fingerprint == b.fingerprint);
+    let file = BaselineFile {
+        version: BASELINE_VERSION,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        findings: entries,
+    };
+    fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+    Ok(())
+}
+
+/// Name of the top-level declaration the finding starts in, else the first
+/// symbol quoted in its text
+fn symbol_of(finding: &ValidationResult, program: Option<&Program>) -> Option<String> {
+    let line = finding
+        .location
+        .as_ref()
+        .filter(|l| !l.is_file_level())
+        .map(|l| l.start_line);
+    let declared = match (line, program) {
+        (Some(line), Some(program)) => program
+            .statements
+            .iter()
+            .find(|s| {
+                let span = s.span();
+                span.start.line <= line && line <= span.end.line
+            })
+            .and_then(|s| s.name())
+            .map(|ident| ident.name.clone()),
+        _ => None,
+    };
+    declared.or_else(|| {
+        let mut parts = finding.finding.split('`');
+        parts.next();
+        parts.next().map(|s| s.to_string())
+    })
+}
+
+/// Lowercase words without digits or punctuation
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Location;
+
+    fn finding(rule_id: Option<&str>, text: &str, line: usize) -> ValidationResult {
+        ValidationResult {
+            category: "Naming".to_string(),
+            finding: text.to_string(),
+            severity: 3,
+            impact: "i".to_string(),
+            rule_id: rule_id.map(str::to_string),
+            location: Some(Location {
+                start_line: line,
+                end_line: line,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn fingerprint_format_is_stable() {
+        assert_eq!(
+            fingerprint("main.bicep", "naming/x", "a b", "sym"),
+            format!("{:x}", Sha256::digest(br#"["main.bicep","naming/x","a b","sym"]"#))
+        );
+    }
+
+    #[test]
+    fn fingerprint_survives_shifted_lines_and_numbers() {
+        let before = crate::parser::parse("param a string\nvar storageName = 'st1'\n").unwrap();
+        let after = crate::parser::parse("param a string\nparam b string\n\nvar storageName = 'st1'\n").unwrap();
+        let old = entry(&finding(None, "Name has 2 issues.", 2), "main.bicep", Some(&before));
+        let new = entry(&finding(None, "Name has 3 issues!", 4), "main.bicep", Some(&after));
+        assert_eq!(old.symbol, "storageName");
+        assert_eq!(old.rule, "llm/naming");
+        assert_eq!(old.fingerprint, new.fingerprint);
+
+        // Another file, rule or symbol is another finding
+        let other_file = entry(&finding(None, "Name has 2 issues.", 2), "other.bicep", Some(&before));
+        let other_symbol = entry(&finding(None, "Name has 2 issues.", 1), "main.bicep", Some(&before));
+        let other_rule = entry(&finding(Some("naming/x"), "Name has 2 issues.", 2), "main.bicep", Some(&before));
+        assert_ne!(old.fingerprint, other_file.fingerprint);
+        assert_ne!(old.fingerprint, other_symbol.fingerprint);
+        assert_ne!(old.fingerprint, other_rule.fingerprint);
+    }
+
+    #[test]
+    fn symbol_comes_from_the_declaration_or_the_text() {
+        let program = crate::parser::parse("param a string\n\nresource st 'Microsoft.Storage/storageAccounts@2023-01-01' = {\n  name: a\n}\n").unwrap();
+        assert_eq!(symbol_of(&finding(None, "x", 4), Some(&program)), Some("st".to_string()));
+        // Between declarations, file-level or without a parse: the first quoted symbol
+        assert_eq!(symbol_of(&finding(None, "Use `b` here", 2), Some(&program)), Some("b".to_string()));
+        let mut file_level = finding(None, "Use `c`", 0);
+        file_level.location = None;
+        assert_eq!(symbol_of(&file_level, Some(&program)), Some("c".to_string()));
+        assert_eq!(symbol_of(&finding(None, "Use `d`", 4), None), Some("d".to_string()));
+        assert_eq!(symbol_of(&finding(None, "nothing quoted", 2), Some(&program)), None);
+    }
+
+    #[test]
+    fn write_sorts_and_dedups() {
+        let path = std::env::temp_dir().join(format!("bicep-reviewer-baseline-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let e = |file: &str, rule: &str, text: &str| entry(&finding(Some(rule), text, 1), file, None);
+        write(
+            &path,
+            vec![
+                e("b.bicep", "naming/x", "one"),
+                e("a.bicep", "outputs/y", "two"),
+                e("a.bicep", "naming/x", "three"),
+                e("b.bicep", "naming/x", "one"),
+            ],
+        )
+        .unwrap();
+
+        let file: BaselineFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let order: Vec<_> = file.findings.iter().map(|f| (f.file.as_str(), f.rule.as_str(), f.finding.as_str())).collect();
+        assert_eq!(
+            order,
+            vec![("a.bicep", "naming/x", "three"), ("a.bicep", "outputs/y", "two"), ("b.bicep", "naming/x", "one")]
+        );
+        assert_eq!(file.version, BASELINE_VERSION);
+
+        let baseline = Baseline::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let (new, old) = baseline.partition(vec![finding(Some("naming/x"), "one", 5)], "b.bicep", None);
+        assert_eq!((new.len(), old.len()), (0, 1));
+    }
+}
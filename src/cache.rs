@@ -0,0 +1,326 @@
+// cache.rs
+//
+// Content-addressed on-disk cache of LLM responses. The key is a hash of
+// the provider, model, prompt messages, temperature and response format,
+// so re-running on unchanged input costs nothing. Entries expire after a
+// TTL and the oldest ones are evicted once the cache exceeds its size limit.
+// A cache that cannot be read or written never fails the run.
+
+use crate::fixtures::request_hash;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_TTL_HOURS: u64 = 24 * 7;
+const DEFAULT_MAX_MB: u64 = 100;
+const ENTRY_EXTENSION: &str = "json";
+
+/// Where and how long responses are kept; read from BICEP_CACHE_* env vars
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub ttl: Duration,
+    pub max_bytes: u64,
+}
+
+impl CacheConfig {
+    pub fn from_env() -> Self {
+        let enabled = !matches!(
+            std::env::var("BICEP_CACHE").as_deref(),
+            Ok("off") | Ok("false") | Ok("0")
+        );
+        let dir = std::env::var("BICEP_CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| default_dir());
+        let ttl_hours = env_u64("BICEP_CACHE_TTL_HOURS").unwrap_or(DEFAULT_TTL_HOURS);
+        let max_mb = env_u64("BICEP_CACHE_MAX_MB").unwrap_or(DEFAULT_MAX_MB);
+        CacheConfig {
+            enabled,
+            dir,
+            ttl: Duration::from_secs(ttl_hours * 3600),
+            max_bytes: max_mb * 1024 * 1024,
+        }
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+/// `$XDG_CACHE_HOME/bicep-reviewer`, else `~/.cache/bicep-reviewer`
+fn default_dir() -> PathBuf {
+    let base = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|h| Path::new(&h).join(".cache")))
+        .unwrap_or_else(|_| std::env::temp_dir());
+    base.join("bicep-reviewer")
+}
+
+/// Cache key for a request sent to a given provider
+pub fn cache_key(provider: &str, request: &impl Serialize) -> Result<String, Box<dyn Error>> {
+    request_hash(&(provider, request))
+}
+
+fn entry_path(cfg: &CacheConfig, key: &str) -> PathBuf {
+    cfg.dir.join(format!("{}.{}", key, ENTRY_EXTENSION))
+}
+
+/// Cached response for the key, if present and not expired
+pub fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let cfg = CacheConfig::from_env();
+    if !cfg.enabled {
+        return None;
+    }
+    let path = entry_path(&cfg, key);
+    let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age > cfg.ttl {
+        return None;
+    }
+    let entry: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    serde_json::from_value(entry["response"].clone()).ok()
+}
+
+/// Store a response, then evict the oldest entries beyond the size limit
+pub fn put(key: &str, model: &str, response: &impl Serialize) {
+    let cfg = CacheConfig::from_env();
+    if !cfg.enabled {
+        return;
+    }
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&cfg.dir)?;
+        let entry = serde_json::json!({
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "model": model,
+            "response": response,
+        });
+        fs::write(entry_path(&cfg, key), serde_json::to_string(&entry)?)?;
+        enforce_size_limit(&cfg)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        eprintln!("Warning: could not write LLM cache in {}: {}", cfg.dir.display(), e);
+    }
+}
+
+// ------------------------------------------------------------
+// Inspection and pruning
+
+/// One cached response on disk
+pub struct CacheEntry {
+    pub key: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub age: Duration,
+}
+
+/// All entries, oldest first
+pub fn entries(cfg: &CacheConfig) -> Result<Vec<CacheEntry>, Box<dyn Error>> {
+    if !cfg.dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut out = Vec::new();
+    for item in fs::read_dir(&cfg.dir)? {
+        let path = item?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(ENTRY_EXTENSION) {
+            continue;
+        }
+        let meta = fs::metadata(&path)?;
+        let age = meta.modified()?.elapsed().unwrap_or_default();
+        out.push(CacheEntry {
+            key: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path,
+            size: meta.len(),
+            age,
+        });
+    }
+    out.sort_by_key(|e| std::cmp::Reverse(e.age));
+    Ok(out)
+}
+
+/// Model recorded in an entry, for listings
+pub fn entry_model(entry: &CacheEntry) -> Option<String> {
+    let text = fs::read_to_string(&entry.path).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&text).ok()?;
+    value["model"].as_str().map(|s| s.to_string())
+}
+
+/// Result of a prune run
+pub struct PruneSummary {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+/// Remove expired entries (or all of them), then enforce the size limit
+pub fn prune(cfg: &CacheConfig, all: bool) -> Result<PruneSummary, Box<dyn Error>> {
+    let mut summary = PruneSummary {
+        removed: 0,
+        freed_bytes: 0,
+    };
+    for entry in entries(cfg)? {
+        if all || entry.age > cfg.ttl {
+            fs::remove_file(&entry.path)?;
+            summary.removed += 1;
+            summary.freed_bytes += entry.size;
+        }
+    }
+    let evicted = enforce_size_limit(cfg)?;
+    summary.removed += evicted.removed;
+    summary.freed_bytes += evicted.freed_bytes;
+    Ok(summary)
+}
+
+fn enforce_size_limit(cfg: &CacheConfig) -> Result<PruneSummary, Box<dyn Error>> {
+    let all = entries(cfg)?;
+    let mut total: u64 = all.iter().map(|e| e.size).sum();
+    let mut summary = PruneSummary {
+        removed: 0,
+        freed_bytes: 0,
+    };
+    for entry in all {
+        if total <= cfg.max_bytes {
+            break;
+        }
+        fs::remove_file(&entry.path)?;
+        total -= entry.size;
+        summary.removed += 1;
+        summary.freed_bytes += entry.size;
+    }
+    Ok(summary)
+}
+
+/// Compact age for listings: `42s`, `5m`, `3h`, `2d`
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    /// Tests that set BICEP_CACHE_* run one at a time
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bicep-reviewer-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(dir: &Path, max_bytes: u64) -> CacheConfig {
+        CacheConfig {
+            enabled: true,
+            dir: dir.to_path_buf(),
+            ttl: 24 * HOUR,
+            max_bytes,
+        }
+    }
+
+    /// Write an entry of `size` bytes last modified `age` ago
+    fn entry(dir: &Path, key: &str, size: usize, age: Duration) {
+        let path = dir.join(format!("{}.{}", key, ENTRY_EXTENSION));
+        fs::write(&path, "x".repeat(size)).unwrap();
+        age_file(&path, age);
+    }
+
+    fn age_file(path: &Path, age: Duration) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn keys(cfg: &CacheConfig) -> Vec<String> {
+        entries(cfg).unwrap().into_iter().map(|e| e.key).collect()
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let _env = ENV.lock().unwrap();
+        let dir = cache_dir("ttl");
+        std::env::set_var("BICEP_CACHE_DIR", &dir);
+        std::env::set_var("BICEP_CACHE_TTL_HOURS", "2");
+        std::env::remove_var("BICEP_CACHE");
+        std::env::remove_var("BICEP_CACHE_MAX_MB");
+
+        put("key", "gpt-test", &serde_json::json!({ "answer": 42 }));
+        let cached: Option<serde_json::Value> = get("key");
+        assert_eq!(cached, Some(serde_json::json!({ "answer": 42 })));
+        let entry = &entries(&CacheConfig::from_env()).unwrap()[0];
+        assert_eq!(entry_model(entry).as_deref(), Some("gpt-test"));
+
+        std::env::set_var("BICEP_CACHE", "off");
+        assert_eq!(get::<serde_json::Value>("key"), None);
+        std::env::remove_var("BICEP_CACHE");
+
+        age_file(&dir.join("key.json"), 3 * HOUR);
+        assert_eq!(get::<serde_json::Value>("key"), None);
+        assert_eq!(get::<serde_json::Value>("missing"), None);
+
+        std::env::remove_var("BICEP_CACHE_DIR");
+        std::env::remove_var("BICEP_CACHE_TTL_HOURS");
+    }
+
+    #[test]
+    fn size_limit_evicts_the_oldest_entries_first() {
+        let dir = cache_dir("evict");
+        entry(&dir, "newest", 10, HOUR);
+        entry(&dir, "oldest", 10, 3 * HOUR);
+        entry(&dir, "middle", 10, 2 * HOUR);
+        fs::write(dir.join("notes.txt"), "x".repeat(100)).unwrap();
+        let cfg = config(&dir, 15);
+        assert_eq!(keys(&cfg), vec!["oldest", "middle", "newest"]);
+
+        let summary = enforce_size_limit(&cfg).unwrap();
+        assert_eq!((summary.removed, summary.freed_bytes), (2, 20));
+        assert_eq!(keys(&cfg), vec!["newest"]);
+        assert!(dir.join("notes.txt").exists());
+
+        let summary = enforce_size_limit(&cfg).unwrap();
+        assert_eq!(summary.removed, 0);
+    }
+
+    #[test]
+    fn prune_removes_expired_entries_or_all_of_them() {
+        let dir = cache_dir("prune");
+        entry(&dir, "fresh", 10, HOUR);
+        entry(&dir, "stale", 7, 25 * HOUR);
+        let cfg = config(&dir, 1024);
+
+        let summary = prune(&cfg, false).unwrap();
+        assert_eq!((summary.removed, summary.freed_bytes), (1, 7));
+        assert_eq!(keys(&cfg), vec!["fresh"]);
+
+        let summary = prune(&cfg, true).unwrap();
+        assert_eq!((summary.removed, summary.freed_bytes), (1, 10));
+        assert!(keys(&cfg).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(keys(&cfg).is_empty());
+        assert_eq!(prune(&cfg, true).unwrap().removed, 0);
+    }
+
+    #[test]
+    fn formats_ages_in_the_largest_whole_unit() {
+        let ages: Vec<String> = [0, 59, 60, 3599, 3600, 86399, 86400, 10 * 86400]
+            .into_iter()
+            .map(|secs| format_age(Duration::from_secs(secs)))
+            .collect();
+        assert_eq!(ages, vec!["0s", "59s", "1m", "59m", "1h", "23h", "1d", "10d"]);
+    }
+}
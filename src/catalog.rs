@@ -0,0 +1,366 @@
+// catalog.rs
+//
+// Structured view of the best-practices document. `## ` headings become
+// categories, bullet items become rules, fenced code blocks attach to the
+// bullet above them and markdown links are kept as references. This
+// replaces asking the LLM to extract practices from the raw markdown.
+// Structured rule packs (see rulepack.rs) load into the same catalog.
+
+use crate::models::DEFAULT_CATEGORIES;
+use crate::rulepack::{self, Matcher};
+use std::error::Error;
+
+/// Fenced code block from the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeExample {
+    pub language: String,
+    pub code: String,
+    /// "Good" or "Bad" for rule-pack examples
+    pub label: Option<String>,
+}
+
+/// One best practice
+#[derive(Debug, Clone)]
+pub struct PracticeRule {
+    /// `<category-slug>/<first words of the rule>`, unique within the catalog
+    pub id: String,
+    pub text: String,
+    pub links: Vec<String>,
+    pub examples: Vec<CodeExample>,
+    /// Rule-pack fields; markdown rules leave them empty
+    pub severity: Option<u8>,
+    pub rationale: Option<String>,
+    pub matcher: Option<Matcher>,
+}
+
+/// A `## ` section of the document
+#[derive(Debug, Clone)]
+pub struct CategorySection {
+    pub heading: String,
+    pub rules: Vec<PracticeRule>,
+    /// Links and examples outside any bullet, e.g. "For more details, see ..."
+    pub links: Vec<String>,
+    pub examples: Vec<CodeExample>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RuleCatalog {
+    pub sections: Vec<CategorySection>,
+    /// Categories declared by a rule pack; empty for markdown documents
+    pub categories: Vec<String>,
+    /// Document text for prompts that take the whole best-practices file
+    pub document: String,
+}
+
+/// Words of a rule text used for its id
+const ID_WORDS: usize = 5;
+
+impl RuleCatalog {
+    /// Load a best-practices file: a YAML/TOML/JSON rule pack or markdown
+    pub fn load(path: &str, text: &str) -> Result<Self, Box<dyn Error>> {
+        if rulepack::is_pack_path(path) {
+            rulepack::parse_pack(path, text)
+        } else {
+            Ok(Self::from_markdown(text))
+        }
+    }
+
+    /// Parse a markdown document into sections and rules
+    pub fn from_markdown(markdown: &str) -> Self {
+        let mut catalog = RuleCatalog {
+            document: markdown.to_string(),
+            ..Default::default()
+        };
+        let mut in_code: Option<CodeExample> = None;
+
+        for line in markdown.lines() {
+            let trimmed = line.trim();
+
+            // Fenced code blocks
+            if let Some(fence) = trimmed.strip_prefix("```") {
+                match in_code.take() {
+                    Some(example) => catalog.attach_example(example),
+                    None => {
+                        in_code = Some(CodeExample {
+                            language: fence.trim().to_string(),
+                            code: String::new(),
+                            label: None,
+                        })
+                    }
+                }
+                continue;
+            }
+            if let Some(example) = in_code.as_mut() {
+                if !example.code.is_empty() {
+                    example.code.push('\n');
+                }
+                example.code.push_str(line);
+                continue;
+            }
+
+            if let Some(heading) = line.strip_prefix("## ") {
+                catalog.sections.push(CategorySection {
+                    heading: heading.trim().to_string(),
+                    rules: Vec::new(),
+                    links: Vec::new(),
+                    examples: Vec::new(),
+                });
+                continue;
+            }
+            let section = match catalog.sections.last_mut() {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let indented = line.starts_with("  ") || line.starts_with('\t');
+            match bullet_text(trimmed) {
+                // Nested bullets add detail to their parent rule
+                Some(text) if indented && !section.rules.is_empty() => {
+                    let rule = section.rules.last_mut().unwrap();
+                    rule.text.push_str("; ");
+                    rule.text.push_str(text);
+                    rule.links.extend(links_in(text));
+                }
+                Some(text) => section.rules.push(PracticeRule {
+                    id: String::new(),
+                    text: text.to_string(),
+                    links: links_in(text),
+                    examples: Vec::new(),
+                    severity: None,
+                    rationale: None,
+                    matcher: None,
+                }),
+                // Wrapped continuation of the previous bullet
+                None if indented && !trimmed.is_empty() && !section.rules.is_empty() => {
+                    let rule = section.rules.last_mut().unwrap();
+                    rule.text.push(' ');
+                    rule.text.push_str(trimmed);
+                    rule.links.extend(links_in(trimmed));
+                }
+                None => section.links.extend(links_in(trimmed)),
+            }
+        }
+        if let Some(example) = in_code {
+            catalog.attach_example(example);
+        }
+
+        catalog.assign_ids();
+        catalog
+    }
+
+    /// Attach a code block to the last rule of the current section, or the section itself
+    fn attach_example(&mut self, example: CodeExample) {
+        if let Some(section) = self.sections.last_mut() {
+            match section.rules.last_mut() {
+                Some(rule) => rule.examples.push(example),
+                None => section.examples.push(example),
+            }
+        }
+    }
+
+    fn assign_ids(&mut self) {
+        let mut seen = std::collections::HashSet::new();
+        for section in &mut self.sections {
+            let prefix = slug(&section.heading);
+            for rule in &mut section.rules {
+                let plain = strip_markdown(&rule.text);
+                let words: Vec<&str> = plain
+                    .split_whitespace()
+                    .take(ID_WORDS)
+                    .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+                    .filter(|w| !w.is_empty())
+                    .collect();
+                let base = format!("{}/{}", prefix, slug(&words.join(" ")));
+                let mut id = base.clone();
+                let mut n = 2;
+                while !seen.insert(id.clone()) {
+                    id = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                rule.id = id;
+            }
+        }
+    }
+
+    /// Categories to review: the pack's own, else the defaults
+    pub fn categories(&self) -> Vec<String> {
+        if self.categories.is_empty() {
+            DEFAULT_CATEGORIES.iter().map(|&s| s.to_string()).collect()
+        } else {
+            self.categories.clone()
+        }
+    }
+
+    /// Rule by id
+    pub fn rule(&self, id: &str) -> Option<&PracticeRule> {
+        self.sections
+            .iter()
+            .flat_map(|s| s.rules.iter())
+            .find(|r| r.id == id)
+    }
+
+    /// Sections that list practices under a heading naming the category as a
+    /// whole word, singular or plural: "Resources" matches "Resource
+    /// Definitions" and "Child Resources". Prose-only sections such as
+    /// "Training Resources" are not categories.
+    pub fn sections_for(&self, category: &str) -> Vec<&CategorySection> {
+        let wanted = category.trim().to_lowercase();
+        let stem = wanted.trim_end_matches('s');
+        self.sections
+            .iter()
+            .filter(|s| !s.rules.is_empty() || !s.examples.is_empty())
+            .filter(|s| {
+                !stem.is_empty()
+                    && s.heading
+                        .to_lowercase()
+                        .split(|c: char| !c.is_alphanumeric())
+                        .any(|word| word.trim_end_matches('s') == stem)
+            })
+            .collect()
+    }
+
+    /// Rules for a category; empty when the document has no bullets for it
+    pub fn rules_for(&self, category: &str) -> Vec<&PracticeRule> {
+        self.sections_for(category)
+            .into_iter()
+            .flat_map(|s| s.rules.iter())
+            .collect()
+    }
+
+    /// Prompt lines for a category: one per rule, with its examples
+    pub fn practices_for(&self, category: &str) -> Vec<String> {
+        self.rules_for(category)
+            .into_iter()
+            .map(|rule| {
+                let mut line = format!("[{}] {}", rule.id, rule.text);
+                if let Some(severity) = rule.severity {
+                    line.push_str(&format!(" (default severity {})", severity));
+                }
+                if let Some(rationale) = &rule.rationale {
+                    line.push_str(&format!("\n  Rationale: {}", rationale));
+                }
+                for ex in &rule.examples {
+                    if let Some(label) = &ex.label {
+                        line.push_str(&format!("\n  {}:", label));
+                    }
+                    line.push_str(&format!("\n```{}\n{}\n```", ex.language, ex.code));
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// First reference link for a category, from its rules or section text
+    pub fn first_link(&self, category: &str) -> Option<String> {
+        self.sections_for(category).into_iter().find_map(|s| {
+            s.links
+                .first()
+                .or_else(|| s.rules.iter().flat_map(|r| r.links.iter()).next())
+                .cloned()
+        })
+    }
+}
+
+fn bullet_text(line: &str) -> Option<&str> {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return Some(rest.trim());
+        }
+    }
+    // Numbered lists: "1. text"
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(rest) = line[digits..].strip_prefix(". ") {
+            return Some(rest.trim());
+        }
+    }
+    None
+}
+
+/// Targets of `[text](url)` links
+fn links_in(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        let after = &rest[start + 2..];
+        match after.find(')') {
+            Some(end) => {
+                out.push(after[..end].to_string());
+                rest = &after[end..];
+            }
+            None => break,
+        }
+    }
+    out
+}
+
+/// Drop link targets and inline code markers, keeping the visible text
+fn strip_markdown(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find(')') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+    out.replace(['[', '`', '\''], "")
+}
+
+/// Lowercase ASCII words joined by dashes
+pub fn slug(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped() -> RuleCatalog {
+        RuleCatalog::load("bicep-best-practices.md", include_str!("../bicep-best-practices.md")).unwrap()
+    }
+
+    fn headings<'a>(catalog: &'a RuleCatalog, category: &str) -> Vec<&'a str> {
+        catalog.sections_for(category).iter().map(|s| s.heading.as_str()).collect()
+    }
+
+    #[test]
+    fn matches_categories_on_whole_words() {
+        let catalog = shipped();
+        assert_eq!(headings(&catalog, "Resources"), vec!["Resource Definitions", "Child Resources"]);
+        assert_eq!(headings(&catalog, "Naming"), vec!["Naming Conventions"]);
+        assert_eq!(headings(&catalog, "parameters"), vec!["Parameters"]);
+        assert_eq!(headings(&catalog, "Output"), vec!["Outputs"]);
+        assert!(headings(&catalog, "Training").is_empty());
+        assert!(headings(&catalog, "Name").is_empty());
+        assert!(headings(&catalog, "").is_empty());
+    }
+
+    #[test]
+    fn help_links_come_from_the_category_sections() {
+        let catalog = shipped();
+        assert_eq!(catalog.first_link("Resources"), None);
+        assert_eq!(
+            catalog.first_link("Parameters").as_deref(),
+            Some("https://learn.microsoft.com/en-us/azure/azure-resource-manager/bicep/parameters")
+        );
+        assert_eq!(
+            catalog.rules_for("Resources").len(),
+            9,
+            "rules of Resource Definitions and Child Resources"
+        );
+    }
+}
@@ -0,0 +1,531 @@
+// config.rs
+//
+// Project configuration from `.bicep-reviewer.toml`. The file is looked up
+// in the working directory and its parents (up to the repository root),
+// or given with `--config`. Settings resolve in this order, first wins:
+// command-line flags, environment variables, the config file, defaults.
+
+use crate::catalog::RuleCatalog;
+use crate::diff::DEFAULT_DIFF_CONTEXT;
+use crate::models::{
+    OutputFormat, ReviewOptions, DEFAULT_CONCURRENCY, DEFAULT_FAIL_ON_SEVERITY,
+    DEFAULT_MIN_SEVERITY,
+};
+use crate::policy::usage;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = ".bicep-reviewer.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    best_practices_file: Option<String>,
+    /// Default scan targets when no --bicep-file/--path is given
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Gitignore-style patterns added to --exclude
+    #[serde(default)]
+    ignore: Vec<String>,
+    #[serde(default)]
+    categories: Option<Vec<String>>,
+    #[serde(default)]
+    minimum_severity: Option<u8>,
+    #[serde(default)]
+    fail_on_severity: Option<u8>,
+    #[serde(default)]
+    max_findings: Option<usize>,
+    #[serde(default)]
+    concurrency: Option<usize>,
+    #[serde(default)]
+    simple: Option<bool>,
+    #[serde(default)]
+    rules_only: Option<bool>,
+    #[serde(default)]
+    debug: Option<bool>,
+    /// `false` turns the LLM response cache off
+    #[serde(default)]
+    cache: Option<bool>,
+    /// Baseline of known findings
+    #[serde(default)]
+    baseline: Option<String>,
+    #[serde(default)]
+    diff_context: Option<usize>,
+    #[serde(default)]
+    full_review: Option<bool>,
+    #[serde(default)]
+    model: ModelSettings,
+    #[serde(default)]
+    rules: RuleToggles,
+    #[serde(default)]
+    outputs: Vec<OutputSpec>,
+    #[serde(default)]
+    overrides: Vec<OverrideSpec>,
+}
+
+/// `[model]`: exported to the provider env vars unless those are already set
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelSettings {
+    provider: Option<String>,
+    model: Option<String>,
+    base_url: Option<String>,
+    api_version: Option<String>,
+}
+
+/// `[rules]` or per-override rule switches; entries are rule ids or globs like `naming/*`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleToggles {
+    #[serde(default)]
+    enable: Vec<String>,
+    #[serde(default)]
+    disable: Vec<String>,
+}
+
+/// `[[outputs]]`: one rendered report; without `path` it goes to stdout
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSpec {
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `[[overrides]]`: settings for files matching any of `paths`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverrideSpec {
+    paths: Vec<String>,
+    #[serde(default)]
+    minimum_severity: Option<u8>,
+    #[serde(default)]
+    categories: Option<Vec<String>>,
+    #[serde(default)]
+    enable: Vec<String>,
+    #[serde(default)]
+    disable: Vec<String>,
+}
+
+/// Compiled rule switches
+struct RuleFilter {
+    enable: GlobSet,
+    disable: GlobSet,
+}
+
+impl RuleFilter {
+    fn new(enable: &[String], disable: &[String]) -> Result<Self, String> {
+        Ok(RuleFilter {
+            enable: glob_set(enable, false)?,
+            disable: glob_set(disable, false)?,
+        })
+    }
+
+    /// Apply to the current state: disable first, then re-enable
+    fn apply(&self, enabled: bool, rule_id: &str) -> bool {
+        if self.enable.is_match(rule_id) {
+            true
+        } else if self.disable.is_match(rule_id) {
+            false
+        } else {
+            enabled
+        }
+    }
+}
+
+struct PathOverride {
+    paths: GlobSet,
+    minimum_severity: Option<u8>,
+    categories: Option<Vec<String>>,
+    rules: RuleFilter,
+}
+
+/// Settings after merging flags, env vars and the config file
+pub struct Settings {
+    /// Config file in use, for debug output
+    pub source: Option<PathBuf>,
+    pub best_practices_file: Option<String>,
+    pub paths: Vec<String>,
+    pub ignore: Vec<String>,
+    pub categories: Option<Vec<String>>,
+    pub debug: bool,
+    pub minimum_severity: u8,
+    pub simple: bool,
+    pub rules_only: bool,
+    pub fail_on_severity: u8,
+    pub max_findings: Option<usize>,
+    pub concurrency: usize,
+    pub outputs: Vec<OutputSpec>,
+    pub baseline: Option<String>,
+    pub write_baseline: bool,
+    /// Context lines around changed lines in PR reviews
+    pub diff_context: usize,
+    /// Review whole files in PRs, not just the changed lines
+    pub full_review: bool,
+    /// Directory of the config file; globs match paths relative to it
+    root: PathBuf,
+    rules: RuleFilter,
+    overrides: Vec<PathOverride>,
+}
+
+/// The config file named by --config, else the nearest .bicep-reviewer.toml
+/// between the working directory and the repository root
+pub fn locate(explicit: Option<&str>) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    if let Some(path) = explicit {
+        if !Path::new(path).is_file() {
+            return Err(usage(format!("Config file not found: {}", path)));
+        }
+        return Ok(Some(PathBuf::from(path)));
+    }
+    let cwd = std::env::current_dir()?;
+    let mut relative = PathBuf::new();
+    for dir in cwd.ancestors() {
+        if dir.join(CONFIG_FILE_NAME).is_file() {
+            return Ok(Some(relative.join(CONFIG_FILE_NAME)));
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+        relative.push("..");
+    }
+    Ok(None)
+}
+
+fn read_config(path: &Path) -> Result<ConfigFile, Box<dyn Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| usage(format!("Cannot read {}: {}", path.display(), e)))?;
+    toml::from_str(&text)
+        .map_err(|e| usage(format!("Invalid config {}: {}", path.display(), e)))
+}
+
+impl Settings {
+    /// Merge flags over env vars over the config file over the defaults
+    pub fn resolve(options: &ReviewOptions) -> Result<Self, Box<dyn Error>> {
+        let source = locate(options.config.as_deref())?;
+        let file = match &source {
+            Some(path) => read_config(path)?,
+            None => ConfigFile::default(),
+        };
+        let root = source
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let invalid = |e: String| {
+            usage(format!(
+                "Invalid config {}: {}",
+                source.as_deref().unwrap_or(Path::new(CONFIG_FILE_NAME)).display(),
+                e
+            ))
+        };
+
+        let env_debug = std::env::var("BICEP_DEBUG").ok().map(|v| v == "true");
+        if file.cache == Some(false) && std::env::var("BICEP_CACHE").is_err() {
+            std::env::set_var("BICEP_CACHE", "off");
+        }
+
+        let mut overrides = Vec::new();
+        for o in &file.overrides {
+            overrides.push(PathOverride {
+                paths: glob_set(&o.paths, true).map_err(invalid)?,
+                minimum_severity: o.minimum_severity,
+                categories: o.categories.clone(),
+                rules: RuleFilter::new(&o.enable, &o.disable).map_err(invalid)?,
+            });
+        }
+        let rules = RuleFilter::new(&file.rules.enable, &file.rules.disable).map_err(invalid)?;
+
+        let categories = if options.category.is_empty() {
+            file.categories.clone()
+        } else {
+            Some(options.category.clone())
+        };
+
+        Ok(Settings {
+            best_practices_file: options
+                .best_practices_file
+                .clone()
+                .or_else(|| file.best_practices_file.as_deref().map(|p| in_dir(&root, p))),
+            paths: file.paths.iter().map(|p| in_dir(&root, p)).collect(),
+            ignore: file.ignore.clone(),
+            categories,
+            debug: options.debug.or(env_debug).or(file.debug).unwrap_or(false),
+            minimum_severity: options
+                .minimum_severity
+                .or(file.minimum_severity)
+                .unwrap_or(DEFAULT_MIN_SEVERITY),
+            simple: options.simple.or(file.simple).unwrap_or(false),
+            rules_only: options.rules_only.or(file.rules_only).unwrap_or(false),
+            fail_on_severity: options
+                .fail_on_severity
+                .or(file.fail_on_severity)
+                .unwrap_or(DEFAULT_FAIL_ON_SEVERITY),
+            max_findings: options.max_findings.or(file.max_findings),
+            concurrency: options
+                .concurrency
+                .or(file.concurrency)
+                .unwrap_or(DEFAULT_CONCURRENCY),
+            outputs: file
+                .outputs
+                .iter()
+                .map(|o| OutputSpec {
+                    format: o.format,
+                    path: o.path.as_deref().map(|p| in_dir(&root, p)),
+                })
+                .collect(),
+            baseline: options
+                .baseline
+                .clone()
+                .or_else(|| file.baseline.as_deref().map(|p| in_dir(&root, p))),
+            write_baseline: options.write_baseline,
+            diff_context: options
+                .diff_context
+                .or(file.diff_context)
+                .unwrap_or(DEFAULT_DIFF_CONTEXT),
+            full_review: options.full_review.or(file.full_review).unwrap_or(false),
+            source,
+            root,
+            rules,
+            overrides,
+        }
+        .with_model_env(&file.model))
+    }
+
+    /// Export `[model]` settings for the provider, keeping env vars already set
+    fn with_model_env(self, model: &ModelSettings) -> Self {
+        let set_default = |var: &str, value: &Option<String>| {
+            if let (Some(v), Err(_)) = (value, std::env::var(var)) {
+                std::env::set_var(var, v);
+            }
+        };
+        set_default("LLM_PROVIDER", &model.provider);
+        let (model_var, url_var) = match crate::llm::ProviderKind::from_env() {
+            Ok(crate::llm::ProviderKind::OpenAi) => ("OPENAI_MODEL", "OPENAI_BASE_URL"),
+            Ok(crate::llm::ProviderKind::OpenAiCompatible) => ("LLM_MODEL", "LLM_BASE_URL"),
+            _ => ("AZURE_OPENAI_DEPLOYMENT", "AZURE_OPENAI_ENDPOINT"),
+        };
+        set_default(model_var, &model.model);
+        set_default(url_var, &model.base_url);
+        set_default("AZURE_OPENAI_API_VERSION", &model.api_version);
+        self
+    }
+
+    /// Directory of the config file, which its `ignore` patterns and globs are
+    /// relative to; the working directory without a config file
+    pub fn config_root(&self) -> &Path {
+        if self.root.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &self.root
+        }
+    }
+
+    /// Overrides whose globs match the file, in config order
+    fn overrides_for<'a>(&'a self, file: &str) -> impl Iterator<Item = &'a PathOverride> {
+        let relative = self.project_path(file);
+        self.overrides
+            .iter()
+            .filter(move |o| o.paths.is_match(&relative))
+    }
+
+    /// Minimum severity reported for a file
+    pub fn minimum_severity_for(&self, file: &str) -> u8 {
+        self.overrides_for(file)
+            .filter_map(|o| o.minimum_severity)
+            .last()
+            .unwrap_or(self.minimum_severity)
+    }
+
+    /// Categories the LLM reviews for a file
+    pub fn categories_for(&self, file: &str, catalog: &RuleCatalog) -> Vec<String> {
+        self.overrides_for(file)
+            .filter_map(|o| o.categories.clone())
+            .last()
+            .unwrap_or_else(|| self.categories(catalog))
+    }
+
+    /// Categories for the whole run
+    pub fn categories(&self, catalog: &RuleCatalog) -> Vec<String> {
+        self.categories
+            .clone()
+            .unwrap_or_else(|| catalog.categories())
+    }
+
+    /// Whether a rule runs for a file, after `[rules]` and matching overrides
+    pub fn rule_enabled(&self, file: &str, rule_id: &str) -> bool {
+        let global = self.rules.apply(true, rule_id);
+        self.overrides_for(file)
+            .fold(global, |enabled, o| o.rules.apply(enabled, rule_id))
+    }
+
+    /// File path relative to the config directory, for glob matching and
+    /// baseline fingerprints. PR paths (`/infra/main.bicep`) are already
+    /// relative to the repository.
+    pub fn project_path(&self, file: &str) -> String {
+        let local = Path::new(file);
+        if let (Ok(abs), Ok(root)) = (fs::canonicalize(local), fs::canonicalize(self.config_root())) {
+            if let Ok(rel) = abs.strip_prefix(root) {
+                return rel.to_string_lossy().replace('\\', "/");
+            }
+        }
+        file.trim_start_matches('/').replace('\\', "/")
+    }
+}
+
+/// Resolve a path from the config file against the config file's directory
+fn in_dir(root: &Path, path: &str) -> String {
+    if root.as_os_str().is_empty() || Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        root.join(path).to_string_lossy().to_string()
+    }
+}
+
+fn glob_set(patterns: &[String], literal_separator: bool) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob: Glob = GlobBuilder::new(pattern.trim_start_matches("./"))
+            .literal_separator(literal_separator)
+            .build()
+            .map_err(|e| format!("invalid glob '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Tests that set BICEP_DEBUG run one at a time
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Fresh directory holding a config file with `toml`
+    fn project(name: &str, toml: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bicep-reviewer-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CONFIG_FILE_NAME), toml).unwrap();
+        dir
+    }
+
+    fn resolve(dir: &Path, options: ReviewOptions) -> Settings {
+        Settings::resolve(&ReviewOptions {
+            config: Some(dir.join(CONFIG_FILE_NAME).to_string_lossy().to_string()),
+            ..options
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_win_over_env_over_file() {
+        let _env = ENV.lock().unwrap();
+        let dir = project("precedence", "minimum_severity = 2\nconcurrency = 3\ndebug = false\ncategories = [\"Naming\"]\n");
+
+        std::env::remove_var("BICEP_DEBUG");
+        let settings = resolve(&dir, ReviewOptions::default());
+        assert_eq!(settings.minimum_severity, 2);
+        assert_eq!(settings.concurrency, 3);
+        assert_eq!(settings.fail_on_severity, DEFAULT_FAIL_ON_SEVERITY);
+        assert_eq!(settings.categories, Some(vec!["Naming".to_string()]));
+        assert!(!settings.debug);
+
+        std::env::set_var("BICEP_DEBUG", "true");
+        let settings = resolve(
+            &dir,
+            ReviewOptions {
+                minimum_severity: Some(4),
+                category: vec!["Outputs".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(settings.minimum_severity, 4);
+        assert_eq!(settings.categories, Some(vec!["Outputs".to_string()]));
+        assert!(settings.debug);
+
+        let settings = resolve(
+            &dir,
+            ReviewOptions {
+                debug: Some(false),
+                ..Default::default()
+            },
+        );
+        std::env::remove_var("BICEP_DEBUG");
+        assert!(!settings.debug);
+    }
+
+    #[test]
+    fn overrides_match_globs_relative_to_the_config() {
+        let dir = project(
+            "overrides",
+            "minimum_severity = 2\n\n\
+             [[overrides]]\npaths = [\"modules/*.bicep\"]\nminimum_severity = 4\n\n\
+             [[overrides]]\npaths = [\"modules/legacy.bicep\"]\nminimum_severity = 5\ncategories = [\"Naming\"]\n",
+        );
+        let settings = resolve(&dir, ReviewOptions::default());
+
+        assert_eq!(settings.minimum_severity_for("/main.bicep"), 2);
+        assert_eq!(settings.minimum_severity_for("/modules/app.bicep"), 4);
+        // Later overrides win; `*` does not cross directories
+        assert_eq!(settings.minimum_severity_for("/modules/legacy.bicep"), 5);
+        assert_eq!(settings.minimum_severity_for("/modules/nested/app.bicep"), 2);
+
+        let catalog = RuleCatalog::default();
+        assert_eq!(settings.categories_for("/modules/legacy.bicep", &catalog), vec!["Naming".to_string()]);
+    }
+
+    #[test]
+    fn rules_disable_then_enable_and_overrides_apply_in_order() {
+        let dir = project(
+            "rules",
+            "[rules]\ndisable = [\"naming/*\"]\nenable = [\"naming/keep\"]\n\n\
+             [[overrides]]\npaths = [\"legacy/**\"]\ndisable = [\"naming/keep\", \"outputs/*\"]\n\n\
+             [[overrides]]\npaths = [\"legacy/app.bicep\"]\nenable = [\"outputs/no-secrets\"]\n",
+        );
+        let settings = resolve(&dir, ReviewOptions::default());
+
+        assert!(!settings.rule_enabled("/main.bicep", "naming/other"));
+        assert!(settings.rule_enabled("/main.bicep", "naming/keep"));
+        assert!(settings.rule_enabled("/main.bicep", "outputs/no-secrets"));
+
+        assert!(!settings.rule_enabled("/legacy/old.bicep", "naming/keep"));
+        assert!(!settings.rule_enabled("/legacy/old.bicep", "outputs/no-secrets"));
+        assert!(settings.rule_enabled("/legacy/app.bicep", "outputs/no-secrets"));
+        assert!(!settings.rule_enabled("/legacy/app.bicep", "outputs/other"));
+    }
+
+    #[test]
+    fn paths_resolve_against_the_config_directory() {
+        let dir = project("paths", "baseline = \"baseline.json\"\npaths = [\"infra\"]\n");
+        fs::create_dir_all(dir.join("infra")).unwrap();
+        fs::write(dir.join("infra/main.bicep"), "").unwrap();
+        let settings = resolve(&dir, ReviewOptions::default());
+
+        assert_eq!(settings.baseline, Some(dir.join("baseline.json").to_string_lossy().to_string()));
+        assert_eq!(settings.paths, vec![dir.join("infra").to_string_lossy().to_string()]);
+        assert_eq!(settings.config_root(), dir.as_path());
+
+        let local = dir.join("infra/main.bicep");
+        assert_eq!(settings.project_path(&local.to_string_lossy()), "infra/main.bicep");
+        // Repository paths of PRs need not exist locally
+        assert_eq!(settings.project_path("/infra/app.bicep"), "infra/app.bicep");
+        assert_eq!(settings.project_path("modules\\app.bicep"), "modules/app.bicep");
+    }
+
+    #[test]
+    fn ignore_is_anchored_at_the_config_directory() {
+        let dir = project("ignore", "ignore = [\"infra/legacy/\"]\n");
+        let settings = resolve(&dir, ReviewOptions::default());
+        let mut excludes = crate::discover::Excludes::new().unwrap();
+        excludes.add(settings.config_root(), &settings.ignore).unwrap();
+
+        assert!(excludes.is_excluded(&dir.join("infra/legacy/old.bicep"), false));
+        assert!(!excludes.is_excluded(&dir.join("infra/main.bicep"), false));
+        assert!(!excludes.is_excluded(Path::new("infra/legacy/old.bicep"), false));
+    }
+}
@@ -0,0 +1,204 @@
+// diff.rs
+//
+// Lines a change touched, from the base and head version of a file. PR
+// reviews mark these lines in the prompt and drop findings that are not
+// on or near them, so comments only flag what the author changed.
+
+use crate::models::ValidationResult;
+use similar::{DiffOp, TextDiff};
+
+/// Unchanged lines around a hunk that still count as touched
+pub const DEFAULT_DIFF_CONTEXT: usize = 3;
+
+/// Changed head lines, 1-based and inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub hunks: Vec<Hunk>,
+    /// New file: every line counts as changed
+    pub added: bool,
+    pub context: usize,
+}
+
+impl FileDiff {
+    /// Diff of a file that did not exist in the base
+    pub fn added(context: usize) -> Self {
+        FileDiff {
+            hunks: Vec::new(),
+            added: true,
+            context,
+        }
+    }
+
+    /// Line diff from `base` to `head`. A deletion marks the head lines on
+    /// either side of it, since the code around a removed line may now be wrong.
+    pub fn between(base: &str, head: &str, context: usize) -> Self {
+        let diff = TextDiff::from_lines(base, head);
+        let mut hunks: Vec<Hunk> = Vec::new();
+        for op in diff.ops() {
+            let hunk = match *op {
+                DiffOp::Equal { .. } => continue,
+                DiffOp::Delete { new_index, .. } => Hunk {
+                    start: new_index.max(1),
+                    end: new_index + 1,
+                },
+                DiffOp::Insert {
+                    new_index, new_len, ..
+                }
+                | DiffOp::Replace {
+                    new_index, new_len, ..
+                } => Hunk {
+                    start: new_index + 1,
+                    end: new_index + new_len,
+                },
+            };
+            match hunks.last_mut() {
+                Some(last) if hunk.start <= last.end + 1 => last.end = last.end.max(hunk.end),
+                _ => hunks.push(hunk),
+            }
+        }
+        FileDiff {
+            hunks,
+            added: false,
+            context,
+        }
+    }
+
+    /// Whether the change left the content as it was, e.g. a pure rename
+    pub fn is_unchanged(&self) -> bool {
+        !self.added && self.hunks.is_empty()
+    }
+
+    /// Whether a line was added or modified
+    pub fn is_changed(&self, line: usize) -> bool {
+        self.added || self.hunks.iter().any(|h| h.start <= line && line <= h.end)
+    }
+
+    /// Whether a finding overlaps a hunk or its context. Findings without a
+    /// line cannot be attributed to the change and only count for new files.
+    pub fn touches(&self, finding: &ValidationResult) -> bool {
+        if self.added {
+            return true;
+        }
+        let loc = match &finding.location {
+            Some(loc) if !loc.is_file_level() => loc,
+            _ => return false,
+        };
+        let end = loc.end_line.max(loc.start_line);
+        self.hunks.iter().any(|h| {
+            loc.start_line <= h.end + self.context && h.start <= end + self.context
+        })
+    }
+
+    /// Split findings into those the change touched and the rest
+    pub fn partition(
+        &self,
+        findings: Vec<ValidationResult>,
+    ) -> (Vec<ValidationResult>, Vec<ValidationResult>) {
+        findings.into_iter().partition(|f| self.touches(f))
+    }
+
+    /// Changed lines for prompts, e.g. "3-5, 12"
+    pub fn describe(&self) -> String {
+        if self.added {
+            return "all lines (new file)".to_string();
+        }
+        self.hunks
+            .iter()
+            .map(|h| {
+                if h.start == h.end {
+                    h.start.to_string()
+                } else {
+                    format!("{}-{}", h.start, h.end)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Location;
+
+    fn hunks(base: &str, head: &str) -> Vec<(usize, usize)> {
+        FileDiff::between(base, head, DEFAULT_DIFF_CONTEXT)
+            .hunks
+            .iter()
+            .map(|h| (h.start, h.end))
+            .collect()
+    }
+
+    fn finding_at(start_line: usize, end_line: usize) -> ValidationResult {
+        ValidationResult {
+            category: "Naming".to_string(),
+            finding: "f".to_string(),
+            severity: 3,
+            impact: "i".to_string(),
+            rule_id: None,
+            location: Some(Location {
+                start_line,
+                end_line,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn unchanged_content_has_no_hunks() {
+        let diff = FileDiff::between("a\nb\n", "a\nb\n", 3);
+        assert!(diff.is_unchanged());
+        assert!(!diff.touches(&finding_at(1, 1)));
+    }
+
+    #[test]
+    fn marks_inserted_and_replaced_lines() {
+        assert_eq!(hunks("a\nc\n", "a\nb\nc\n"), vec![(2, 2)]);
+        assert_eq!(hunks("a\nb\nc\n", "a\nB\nc\n"), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn deletion_marks_lines_around_it() {
+        assert_eq!(hunks("a\nb\nc\n", "a\nc\n"), vec![(1, 2)]);
+        // At the start of the file only the following line exists
+        assert_eq!(hunks("a\nb\n", "b\n"), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn missing_final_newline_changes_last_line() {
+        assert_eq!(hunks("a\nb", "a\nb\n"), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn keeps_separate_hunks_apart() {
+        let base = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let head = "1\nTWO\n3\n4\n5\n6\n7\n8\nNINE\n";
+        let diff = FileDiff::between(base, head, 3);
+        assert_eq!(diff.describe(), "2, 9");
+        assert!(diff.is_changed(9));
+        assert!(!diff.is_changed(5));
+    }
+
+    #[test]
+    fn touches_within_context() {
+        let diff = FileDiff::between("a\nb\nc\nd\ne\nf\ng\n", "a\nB\nc\nd\ne\nf\ng\n", 3);
+        assert!(diff.touches(&finding_at(5, 5)));
+        assert!(!diff.touches(&finding_at(6, 7)));
+        // A multi-line finding overlapping the hunk
+        assert!(diff.touches(&finding_at(1, 6)));
+    }
+
+    #[test]
+    fn file_level_findings_only_touch_new_files() {
+        let whole_file = finding_at(0, 0);
+        assert!(!FileDiff::between("a\n", "b\n", 3).touches(&whole_file));
+        assert!(FileDiff::added(3).touches(&whole_file));
+        assert_eq!(FileDiff::added(3).describe(), "all lines (new file)");
+    }
+}
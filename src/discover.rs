@@ -0,0 +1,278 @@
+// discover.rs
+//
+// Expands `--path` arguments (files, directories and globs) into the list
+// of .bicep files to review. Directory walks honour .gitignore and
+// .ignore files; `--exclude` and the config file's `ignore` add further
+// gitignore-style patterns.
+
+use crate::policy::usage;
+use globset::GlobBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const BICEP_EXTENSION: &str = "bicep";
+
+/// Find every .bicep file under the given paths, sorted and de-duplicated.
+///
+/// Files named explicitly are always kept, even if they match an exclude.
+pub fn discover_bicep_files(
+    paths: &[String],
+    exclude: &Excludes,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut found = BTreeSet::new();
+
+    for arg in paths {
+        let path = Path::new(arg);
+        if path.is_file() {
+            found.insert(normalize(path));
+            continue;
+        }
+
+        let (root, matcher) = if path.is_dir() {
+            (path.to_path_buf(), None)
+        } else if is_glob(arg) {
+            let glob = GlobBuilder::new(arg.trim_start_matches("./"))
+                .literal_separator(true)
+                .build()
+                .map_err(|e| usage(format!("Invalid glob '{}': {}", arg, e)))?;
+            (glob_root(arg), Some(glob.compile_matcher()))
+        } else {
+            return Err(usage(format!("Path not found: {}", arg)));
+        };
+        if !root.exists() {
+            continue;
+        }
+
+        let filter = exclude.clone();
+        let walker = WalkBuilder::new(&root)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !filter.is_excluded(entry.path(), is_dir)
+            })
+            .build();
+
+        for entry in walker {
+            let entry = entry?;
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let file = entry.path();
+            if file.extension().and_then(|e| e.to_str()) != Some(BICEP_EXTENSION) {
+                continue;
+            }
+            let normalized = normalize(file);
+            if matcher.as_ref().is_some_and(|m| !m.is_match(&normalized)) {
+                continue;
+            }
+            found.insert(normalized);
+        }
+    }
+
+    if found.is_empty() {
+        return Err(usage(format!(
+            "No .bicep files found under {}",
+            paths.join(", ")
+        )));
+    }
+    Ok(found.into_iter().collect())
+}
+
+/// Gitignore-style exclude patterns, each set anchored at its own directory:
+/// the working directory for `--exclude`, the config file's directory for
+/// `ignore`. Paths are matched as absolute paths, so a pattern applies to
+/// the same files whichever directory the tool runs from.
+#[derive(Clone)]
+pub struct Excludes {
+    cwd: PathBuf,
+    sets: Vec<Gitignore>,
+}
+
+impl Excludes {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(Excludes {
+            cwd: fs::canonicalize(".")?,
+            sets: Vec::new(),
+        })
+    }
+
+    /// Add patterns relative to `root`
+    pub fn add(&mut self, root: &Path, patterns: &[String]) -> Result<(), Box<dyn Error>> {
+        if patterns.is_empty() {
+            return Ok(());
+        }
+        let root = fs::canonicalize(root)
+            .map_err(|e| usage(format!("Cannot resolve {}: {}", root.display(), e)))?;
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| usage(format!("Invalid exclude '{}': {}", pattern, e)))?;
+        }
+        self.sets.push(builder.build()?);
+        Ok(())
+    }
+
+    /// Whether a path, relative to the working directory, or one of its
+    /// parents is excluded
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let path = lexical_join(&self.cwd, path);
+        self.sets.iter().any(|set| {
+            path.starts_with(set.path())
+                && set.matched_path_or_any_parents(&path, is_dir).is_ignore()
+        })
+    }
+}
+
+/// `base` joined with `path`, with `.` and `..` resolved without touching
+/// the file system, since a file under review need not exist on disk
+fn lexical_join(base: &Path, path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for part in base.join(path).components() {
+        match part {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn is_glob(text: &str) -> bool {
+    text.contains(['*', '?', '[', '{'])
+}
+
+/// Longest leading part of a glob without wildcards, used as the walk root
+fn glob_root(pattern: &str) -> PathBuf {
+    let mut root = PathBuf::new();
+    for part in Path::new(pattern).components() {
+        if is_glob(&part.as_os_str().to_string_lossy()) {
+            break;
+        }
+        root.push(part);
+    }
+    if root.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        root
+    }
+}
+
+/// Forward slashes, without a leading `./`
+fn normalize(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    text.strip_prefix("./").unwrap_or(&text).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory with the given files, each holding an empty template
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bicep-reviewer-discover-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn excludes(root: &Path, patterns: &[&str]) -> Excludes {
+        let mut excludes = Excludes::new().unwrap();
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        excludes.add(root, &patterns).unwrap();
+        excludes
+    }
+
+    fn relative(dir: &Path, files: Vec<String>) -> Vec<String> {
+        let prefix = format!("{}/", normalize(dir));
+        files.iter().map(|f| f.strip_prefix(&prefix).unwrap_or(f).to_string()).collect()
+    }
+
+    #[test]
+    fn glob_root_stops_at_the_first_wildcard() {
+        assert_eq!(glob_root("infra/**/*.bicep"), PathBuf::from("infra"));
+        assert_eq!(glob_root("./infra/{app,db}/main.bicep"), PathBuf::from("./infra"));
+        assert_eq!(glob_root("*.bicep"), PathBuf::from("."));
+        assert_eq!(glob_root("/abs/dir/mod?.bicep"), PathBuf::from("/abs/dir"));
+    }
+
+    #[test]
+    fn lexical_join_resolves_dots_without_the_file_system() {
+        let base = Path::new("/work/repo");
+        assert_eq!(lexical_join(base, Path::new("./a/../b/c.bicep")), PathBuf::from("/work/repo/b/c.bicep"));
+        assert_eq!(lexical_join(base, Path::new("../other/x.bicep")), PathBuf::from("/work/other/x.bicep"));
+        assert_eq!(lexical_join(base, Path::new("/elsewhere/x.bicep")), PathBuf::from("/elsewhere/x.bicep"));
+    }
+
+    #[test]
+    fn normalize_uses_forward_slashes_without_leading_dot() {
+        assert_eq!(normalize(Path::new("./infra/main.bicep")), "infra/main.bicep");
+        assert_eq!(normalize(Path::new("infra\\main.bicep")), "infra/main.bicep");
+        assert_eq!(normalize(Path::new("../main.bicep")), "../main.bicep");
+    }
+
+    #[test]
+    fn excludes_are_anchored_at_their_root() {
+        let dir = tree("anchored", &["legacy/old.bicep", "app/legacy/keep.bicep", "app/main.test.bicep"]);
+        let excludes = excludes(&dir, &["/legacy/", "*.test.bicep"]);
+
+        assert!(excludes.is_excluded(&dir.join("legacy"), true));
+        assert!(excludes.is_excluded(&dir.join("legacy/old.bicep"), false));
+        assert!(!excludes.is_excluded(&dir.join("app/legacy/keep.bicep"), false));
+        assert!(excludes.is_excluded(&dir.join("app/main.test.bicep"), false));
+        assert!(excludes.is_excluded(&dir.join("app/../legacy/old.bicep"), false));
+        // Outside the root the patterns do not apply
+        assert!(!excludes.is_excluded(Path::new("/legacy/old.bicep"), false));
+    }
+
+    #[test]
+    fn relative_paths_are_matched_from_the_working_directory() {
+        let excludes = excludes(Path::new("."), &["target/"]);
+        assert!(excludes.is_excluded(Path::new("target/debug/x.bicep"), false));
+        assert!(excludes.is_excluded(Path::new("./src/../target/x.bicep"), false));
+        assert!(!excludes.is_excluded(Path::new("src/target.bicep"), false));
+        assert!(!Excludes::new().unwrap().is_excluded(Path::new("target"), true));
+    }
+
+    #[test]
+    fn walks_directories_and_globs_honouring_excludes() {
+        let dir = tree(
+            "walk",
+            &["main.bicep", "notes.md", "legacy/old.bicep", "modules/app.bicep", "modules/gen/out.bicep"],
+        );
+        fs::write(dir.join(".gitignore"), "gen/\n").unwrap();
+        let excludes = excludes(&dir, &["legacy/"]);
+
+        let found = discover_bicep_files(&[dir.to_string_lossy().to_string()], &excludes).unwrap();
+        assert_eq!(relative(&dir, found), vec!["main.bicep", "modules/app.bicep"]);
+
+        let glob = format!("{}/*/*.bicep", dir.display());
+        let found = discover_bicep_files(&[glob], &excludes).unwrap();
+        assert_eq!(relative(&dir, found), vec!["modules/app.bicep"]);
+    }
+
+    #[test]
+    fn named_files_bypass_excludes() {
+        let dir = tree("named", &["legacy/old.bicep"]);
+        let excludes = excludes(&dir, &["legacy/"]);
+        let old = dir.join("legacy/old.bicep").to_string_lossy().to_string();
+
+        let found = discover_bicep_files(&[old.clone(), old.clone()], &excludes).unwrap();
+        assert_eq!(relative(&dir, found), vec!["legacy/old.bicep"]);
+
+        let err = discover_bicep_files(&[dir.to_string_lossy().to_string()], &excludes).unwrap_err();
+        assert!(err.to_string().starts_with("No .bicep files found under"));
+        let err = discover_bicep_files(&[dir.join("missing.bicep").to_string_lossy().to_string()], &excludes).unwrap_err();
+        assert!(err.to_string().starts_with("Path not found:"));
+    }
+}
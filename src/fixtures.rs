@@ -0,0 +1,99 @@
+// fixtures.rs
+//
+// Record/replay of LLM and search responses for offline runs. With
+// `--record <dir>` every request/response pair is written to
+// `<dir>/<kind>-<sha256>.json`; with `--replay <dir>` the same requests are
+// answered from those files and no network call is made.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+pub const RECORD_DIR_VAR: &str = "BICEP_RECORD_DIR";
+pub const REPLAY_DIR_VAR: &str = "BICEP_REPLAY_DIR";
+
+/// Active fixture mode, taken from BICEP_RECORD_DIR / BICEP_REPLAY_DIR
+pub enum FixtureMode {
+    Off,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl FixtureMode {
+    pub fn from_env() -> Self {
+        if let Ok(dir) = std::env::var(REPLAY_DIR_VAR) {
+            FixtureMode::Replay(PathBuf::from(dir))
+        } else if let Ok(dir) = std::env::var(RECORD_DIR_VAR) {
+            FixtureMode::Record(PathBuf::from(dir))
+        } else {
+            FixtureMode::Off
+        }
+    }
+}
+
+pub fn is_replay() -> bool {
+    matches!(FixtureMode::from_env(), FixtureMode::Replay(_))
+}
+
+/// Stable key for a request: hex SHA-256 of its JSON form
+pub fn request_hash(request: &impl Serialize) -> Result<String, Box<dyn Error>> {
+    let json = serde_json::to_vec(request)?;
+    Ok(format!("{:x}", Sha256::digest(&json)))
+}
+
+fn fixture_path(dir: &std::path::Path, kind: &str, key: &str) -> PathBuf {
+    dir.join(format!("{}-{}.json", kind, key))
+}
+
+/// In replay mode, the recorded response for this request.
+///
+/// Returns `Ok(None)` when not replaying; a missing fixture is an error so
+/// offline runs never fall through to the network.
+pub fn replay<T: DeserializeOwned>(
+    kind: &str,
+    request: &impl Serialize,
+) -> Result<Option<T>, Box<dyn Error>> {
+    let dir = match FixtureMode::from_env() {
+        FixtureMode::Replay(dir) => dir,
+        _ => return Ok(None),
+    };
+    let key = request_hash(request)?;
+    let path = fixture_path(&dir, kind, &key);
+    let text = fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "No recorded {} response for request {} ({}): {}",
+            kind,
+            key,
+            path.display(),
+            e
+        )
+    })?;
+    let fixture: serde_json::Value = serde_json::from_str(&text)?;
+    Ok(Some(serde_json::from_value(fixture["response"].clone())?))
+}
+
+/// In record mode, store the request/response pair
+pub fn record(
+    kind: &str,
+    request: &impl Serialize,
+    response: &impl Serialize,
+) -> Result<(), Box<dyn Error>> {
+    let dir = match FixtureMode::from_env() {
+        FixtureMode::Record(dir) => dir,
+        _ => return Ok(()),
+    };
+    let key = request_hash(request)?;
+    let fixture = serde_json::json!({
+        "kind": kind,
+        "request": request,
+        "response": response,
+    });
+    fs::write(
+        fixture_path(&dir, kind, &key),
+        serde_json::to_string_pretty(&fixture)?,
+    )?;
+    Ok(())
+}
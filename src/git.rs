@@ -0,0 +1,278 @@
+// git.rs
+//
+// Changed .bicep files and their base and head versions from the local git
+// repository, for reviewing a commit range or the staged changes without a
+// hosting service. Runs the `git` executable in the working directory.
+
+use crate::policy::usage;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// Which changes to review
+#[derive(Debug, Clone)]
+pub enum Changes {
+    /// `base..head`, `base...head` (from the merge base) or a single revision
+    /// (up to HEAD)
+    Range(String),
+    /// The index against HEAD, as for a pre-commit hook
+    Staged,
+}
+
+/// A changed file, with the versions to diff
+#[derive(Debug)]
+pub struct ChangedFile {
+    /// Path relative to the working directory, for reports and config globs
+    pub path: String,
+    /// Head version
+    pub content: String,
+    /// Base version; `None` for a file the changes added
+    pub base: Option<String>,
+}
+
+/// Revisions the changes go from and to; `None` for the index
+struct Revisions {
+    base: String,
+    head: Option<String>,
+}
+
+/// Run git in `dir` and return its stdout; a failure is a usage error since
+/// it usually means a bad range or no repository
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Cannot run git: {}", e))?;
+    if !output.status.success() {
+        return Err(usage(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+impl Changes {
+    fn revisions(&self, dir: &Path) -> Result<Revisions, Box<dyn Error>> {
+        match self {
+            Changes::Staged => Ok(Revisions {
+                base: "HEAD".to_string(),
+                head: None,
+            }),
+            Changes::Range(range) => {
+                let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+                let (base, head) = if let Some((a, b)) = range.split_once("...") {
+                    let merge_base = git(dir, &["merge-base", &or_head(a), &or_head(b)])?;
+                    (merge_base.trim().to_string(), or_head(b))
+                } else if let Some((a, b)) = range.split_once("..") {
+                    (or_head(a), or_head(b))
+                } else {
+                    (range.clone(), "HEAD".to_string())
+                };
+                Ok(Revisions {
+                    base,
+                    head: Some(head),
+                })
+            }
+        }
+    }
+}
+
+/// Changed .bicep files with their head and base content; deleted files are left out
+pub fn changed_bicep_files(changes: &Changes) -> Result<Vec<ChangedFile>, Box<dyn Error>> {
+    changed_bicep_files_in(Path::new("."), changes)
+}
+
+/// Changed .bicep files, with paths relative to `dir`
+fn changed_bicep_files_in(dir: &Path, changes: &Changes) -> Result<Vec<ChangedFile>, Box<dyn Error>> {
+    let revs = changes.revisions(dir)?;
+    // Paths from git are relative to the repository root
+    let prefix = git(dir, &["rev-parse", "--show-prefix"])?.trim().to_string();
+
+    let mut args = vec!["diff", "--name-status", "-z", "-M", "--diff-filter=ACMR"];
+    match &revs.head {
+        Some(head) => {
+            args.push(&revs.base);
+            args.push(head);
+        }
+        None => args.push("--cached"),
+    }
+    args.extend(["--", ":(top,glob)**/*.bicep"]);
+    let listing = git(dir, &args)?;
+
+    let show = |rev: &str, repo_path: &str| git(dir, &["show", &format!("{}:{}", rev, repo_path)]);
+    let mut files = Vec::new();
+    for change in name_status(&listing)? {
+        let content = match &revs.head {
+            Some(head) => show(head, change.path)?,
+            None => show("", change.path)?,
+        };
+        let base = match change.status {
+            "A" => None,
+            _ => Some(show(&revs.base, change.old_path.unwrap_or(change.path))?),
+        };
+        files.push(ChangedFile {
+            path: relative_to(&prefix, change.path),
+            content,
+            base,
+        });
+    }
+    Ok(files)
+}
+
+/// One record of `git diff --name-status -z`
+#[derive(Debug, PartialEq, Eq)]
+struct NameStatus<'a> {
+    /// `A`, `M`, or `R`/`C` with a similarity score
+    status: &'a str,
+    /// Source of a rename or copy
+    old_path: Option<&'a str>,
+    path: &'a str,
+}
+
+/// Records of `git diff --name-status -z` output
+fn name_status(listing: &str) -> Result<Vec<NameStatus<'_>>, Box<dyn Error>> {
+    let mut out = Vec::new();
+    let mut fields = listing.split('\0').filter(|f| !f.is_empty());
+    while let Some(status) = fields.next() {
+        // Renames and copies list the old path before the new one
+        let old_path = match status.chars().next() {
+            Some('R') | Some('C') => fields.next(),
+            _ => None,
+        };
+        let path = fields
+            .next()
+            .ok_or_else(|| format!("Unexpected git diff output: {}", listing))?;
+        out.push(NameStatus {
+            status,
+            old_path,
+            path,
+        });
+    }
+    Ok(out)
+}
+
+/// Repository path made relative to the working directory at `prefix`
+fn relative_to(prefix: &str, repo_path: &str) -> String {
+    match repo_path.strip_prefix(prefix) {
+        Some(rest) => rest.to_string(),
+        None => {
+            let depth = prefix.split('/').filter(|p| !p.is_empty()).count();
+            format!("{}{}", "../".repeat(depth), repo_path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Fresh repository with an initial commit on `main`
+    fn repo(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bicep-reviewer-git-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("infra")).unwrap();
+        run(&dir, &["init", "-q", "-b", "main"]);
+        fs::write(dir.join("infra/main.bicep"), "param a string\n").unwrap();
+        fs::write(dir.join("infra/app.bicep"), "param b string\nparam c string\nparam d string\n").unwrap();
+        commit(&dir, "initial");
+        dir
+    }
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let mut all = vec!["-c", "user.name=t", "-c", "user.email=t@example.com", "-c", "commit.gpgsign=false"];
+        all.extend(args);
+        git(dir, &all).unwrap()
+    }
+
+    fn commit(dir: &Path, message: &str) -> String {
+        run(dir, &["add", "-A"]);
+        run(dir, &["commit", "-q", "-m", message]);
+        run(dir, &["rev-parse", "HEAD"]).trim().to_string()
+    }
+
+    fn revisions(dir: &Path, range: &str) -> (String, Option<String>) {
+        let revs = Changes::Range(range.to_string()).revisions(dir).unwrap();
+        (revs.base, revs.head)
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let dir = repo("ranges");
+        let base = run(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+        run(&dir, &["checkout", "-q", "-b", "feature"]);
+        fs::write(dir.join("infra/main.bicep"), "param a string\nparam e string\n").unwrap();
+        commit(&dir, "feature");
+        run(&dir, &["checkout", "-q", "main"]);
+        fs::write(dir.join("infra/other.bicep"), "param o string\n").unwrap();
+        let main = commit(&dir, "main");
+
+        assert_eq!(revisions(&dir, "main..feature"), ("main".to_string(), Some("feature".to_string())));
+        assert_eq!(revisions(&dir, "main.."), ("main".to_string(), Some("HEAD".to_string())));
+        // Three dots diff from the merge base
+        assert_eq!(revisions(&dir, "main...feature"), (base.clone(), Some("feature".to_string())));
+        assert_eq!(revisions(&dir, "feature...main"), (base, Some("main".to_string())));
+        // A single revision goes up to HEAD
+        assert_eq!(revisions(&dir, &main), (main, Some("HEAD".to_string())));
+
+        let changed = changed_bicep_files_in(&dir, &Changes::Range("main...feature".to_string())).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].path, "infra/main.bicep");
+        assert_eq!(changed[0].base.as_deref(), Some("param a string\n"));
+        assert_eq!(changed[0].content, "param a string\nparam e string\n");
+    }
+
+    #[test]
+    fn reads_renames_and_added_files_relative_to_the_working_directory() {
+        let dir = repo("renames");
+        let base = run(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+        run(&dir, &["mv", "infra/app.bicep", "infra/web.bicep"]);
+        fs::write(dir.join("infra/web.bicep"), "param b string\nparam c string\nparam d string\nparam x string\n").unwrap();
+        fs::write(dir.join("infra/new.bicep"), "param n string\n").unwrap();
+        fs::remove_file(dir.join("infra/main.bicep")).unwrap();
+        run(&dir, &["add", "-A"]);
+
+        let staged = changed_bicep_files_in(&dir.join("infra"), &Changes::Staged).unwrap();
+        let summary: Vec<_> = staged.iter().map(|f| (f.path.as_str(), f.base.as_deref())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("new.bicep", None),
+                ("web.bicep", Some("param b string\nparam c string\nparam d string\n")),
+            ]
+        );
+
+        commit(&dir, "rename");
+        let range = changed_bicep_files_in(&dir, &Changes::Range(base)).unwrap();
+        let paths: Vec<_> = range.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["infra/new.bicep", "infra/web.bicep"]);
+    }
+
+    #[test]
+    fn splits_name_status_records() {
+        let listing = "M\0a.bicep\0R087\0old.bicep\0new.bicep\0C100\0src.bicep\0copy.bicep\0A\0added.bicep\0";
+        let records = name_status(listing).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                NameStatus { status: "M", old_path: None, path: "a.bicep" },
+                NameStatus { status: "R087", old_path: Some("old.bicep"), path: "new.bicep" },
+                NameStatus { status: "C100", old_path: Some("src.bicep"), path: "copy.bicep" },
+                NameStatus { status: "A", old_path: None, path: "added.bicep" },
+            ]
+        );
+        assert!(name_status("R100\0old.bicep\0").is_err());
+    }
+
+    #[test]
+    fn makes_repository_paths_relative() {
+        assert_eq!(relative_to("", "infra/main.bicep"), "infra/main.bicep");
+        assert_eq!(relative_to("infra/", "infra/main.bicep"), "main.bicep");
+        assert_eq!(relative_to("infra/", "modules/app.bicep"), "../modules/app.bicep");
+        assert_eq!(relative_to("infra/modules/", "main.bicep"), "../../main.bicep");
+    }
+}
@@ -0,0 +1,376 @@
+// github.rs
+//
+// GitHub REST client for pull request reviews: changed files, file
+// contents, review comments and check runs. The API base URL comes from
+// --api-url / GITHUB_API_URL, for GitHub Enterprise Server or a local mock.
+
+use crate::http;
+use crate::models::GitHubArgs;
+use crate::policy::usage;
+use crate::services::is_debug_enabled;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+const API_VERSION: &str = "2022-11-28";
+const USER_AGENT: &str = "bicep-reviewer";
+const PAGE_SIZE: usize = 100;
+/// GitHub accepts at most this many annotations per check run request
+pub const MAX_ANNOTATIONS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequest {
+    pub head: CommitRef,
+    pub base: CommitRef,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitRef {
+    pub sha: String,
+}
+
+/// File changed in a pull request
+#[derive(Debug, Deserialize)]
+pub struct ChangedFile {
+    pub filename: String,
+    /// added, removed, modified, renamed, copied, changed or unchanged
+    pub status: String,
+    /// Unified diff; missing for binary or very large changes
+    #[serde(default)]
+    pub patch: Option<String>,
+    #[serde(default)]
+    pub previous_filename: Option<String>,
+}
+
+/// Existing review comment, for recognising the tool's earlier comments
+#[derive(Debug, Deserialize)]
+pub struct ReviewComment {
+    #[serde(default)]
+    pub body: String,
+}
+
+/// Existing review, whose body lists findings outside the diff
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewReview {
+    pub commit_id: String,
+    pub body: String,
+    /// Always `COMMENT`; the check run carries the verdict
+    pub event: String,
+    pub comments: Vec<NewReviewComment>,
+}
+
+/// Review comment on lines of the PR's side of the diff
+#[derive(Debug, Serialize)]
+pub struct NewReviewComment {
+    pub path: String,
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    pub side: String,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckRun {
+    pub name: String,
+    pub head_sha: String,
+    pub status: String,
+    /// `success` or `failure`
+    pub conclusion: String,
+    pub output: CheckOutput,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckOutput {
+    pub title: String,
+    pub summary: String,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Annotation {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// `notice`, `warning` or `failure`
+    pub annotation_level: String,
+    pub title: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedCheckRun {
+    id: u64,
+}
+
+/// Client for one repository
+pub struct GitHubClient {
+    api_url: String,
+    token: String,
+    owner: String,
+    repo: String,
+}
+
+impl GitHubClient {
+    pub fn new(args: &GitHubArgs) -> Result<Self, Box<dyn Error>> {
+        let (owner, repo) = args
+            .repository
+            .split_once('/')
+            .filter(|(o, r)| !o.is_empty() && !r.is_empty() && !r.contains('/'))
+            .ok_or_else(|| usage(format!("--repository must be owner/name, got '{}'", args.repository)))?;
+        Ok(GitHubClient {
+            api_url: args.api_url.trim_end_matches('/').to_string(),
+            token: args.token.clone(),
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        })
+    }
+
+    fn repo_url(&self, path: &str) -> String {
+        format!(
+            "{}/repos/{}/{}/{}",
+            self.api_url,
+            urlencoding::encode(&self.owner),
+            urlencoding::encode(&self.repo),
+            path
+        )
+    }
+
+    fn with_headers(&self, request: RequestBuilder, accept: &str) -> RequestBuilder {
+        request
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Accept", accept)
+            .header("X-GitHub-Api-Version", API_VERSION)
+            .header("User-Agent", USER_AGENT)
+    }
+
+    async fn send(&self, what: &str, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        if is_debug_enabled() {
+            if let Some(r) = request.try_clone().and_then(|r| r.build().ok()) {
+                println!("(DEBUG) github {} => {} {}", what, r.method(), r.url());
+            }
+        }
+        let resp = http::send(self.with_headers(request, "application/vnd.github+json")).await?;
+        if !resp.status().is_success() {
+            let st = resp.status();
+            let body = resp.text().await?;
+            return Err(format!("GitHub {} API error: status={}, body={}", what, st, body).into());
+        }
+        Ok(resp)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, what: &str, url: &str) -> Result<T, Box<dyn Error>> {
+        Ok(self.send(what, http::client().get(url)).await?.json().await?)
+    }
+
+    /// All pages of a list endpoint
+    async fn get_pages<T: DeserializeOwned>(&self, what: &str, path: &str) -> Result<Vec<T>, Box<dyn Error>> {
+        let mut out = Vec::new();
+        for page in 1.. {
+            let url = format!("{}?per_page={}&page={}", self.repo_url(path), PAGE_SIZE, page);
+            let items: Vec<T> = self.get_json(what, &url).await?;
+            let last = items.len() < PAGE_SIZE;
+            out.extend(items);
+            if last {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    pub async fn pull_request(&self, number: u64) -> Result<PullRequest, Box<dyn Error>> {
+        self.get_json("pull request", &self.repo_url(&format!("pulls/{}", number)))
+            .await
+    }
+
+    pub async fn changed_files(&self, number: u64) -> Result<Vec<ChangedFile>, Box<dyn Error>> {
+        self.get_pages("pull request files", &format!("pulls/{}/files", number))
+            .await
+    }
+
+    pub async fn review_comments(&self, number: u64) -> Result<Vec<ReviewComment>, Box<dyn Error>> {
+        self.get_pages("review comments", &format!("pulls/{}/comments", number))
+            .await
+    }
+
+    pub async fn reviews(&self, number: u64) -> Result<Vec<Review>, Box<dyn Error>> {
+        self.get_pages("reviews", &format!("pulls/{}/reviews", number))
+            .await
+    }
+
+    /// Raw file content at a commit; `None` when the file does not exist there
+    pub async fn file_content(&self, path: &str, sha: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let encoded: Vec<_> = path.split('/').map(|p| urlencoding::encode(p).into_owned()).collect();
+        let url = format!("{}?ref={}", self.repo_url(&format!("contents/{}", encoded.join("/"))), sha);
+        let request = self.with_headers(http::client().get(&url), "application/vnd.github.raw");
+        let resp = http::send(request).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let st = resp.status();
+            let body = resp.text().await?;
+            return Err(format!("GitHub contents API error: status={}, body={}", st, body).into());
+        }
+        Ok(Some(resp.text().await?))
+    }
+
+    pub async fn create_review(&self, number: u64, review: &NewReview) -> Result<(), Box<dyn Error>> {
+        let url = self.repo_url(&format!("pulls/{}/reviews", number));
+        self.send("create review", http::client().post(&url).json(review))
+            .await?;
+        Ok(())
+    }
+
+    /// Create a completed check run. Annotations beyond the first
+    /// MAX_ANNOTATIONS are added by updating the run in batches.
+    pub async fn create_check_run(&self, run: CheckRun) -> Result<(), Box<dyn Error>> {
+        let mut batches = run.output.annotations.chunks(MAX_ANNOTATIONS);
+        let first = CheckRun {
+            output: CheckOutput {
+                annotations: batches.next().map(|b| b.to_vec()).unwrap_or_default(),
+                ..run.output.clone()
+            },
+            ..run
+        };
+        let created: CreatedCheckRun = self
+            .send("create check run", http::client().post(self.repo_url("check-runs")).json(&first))
+            .await?
+            .json()
+            .await?;
+
+        for batch in batches {
+            let output = CheckOutput {
+                annotations: batch.to_vec(),
+                ..first.output.clone()
+            };
+            let url = self.repo_url(&format!("check-runs/{}", created.id));
+            self.send(
+                "update check run",
+                http::client()
+                    .patch(&url)
+                    .json(&serde_json::json!({ "output": output })),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Lines on the new side of a unified diff that GitHub accepts review
+/// comments on: added lines and the context lines shown around them, mapped
+/// to the index of their hunk. A multi-line comment must stay in one hunk.
+pub fn commentable_lines(patch: &str) -> HashMap<usize, usize> {
+    let mut out = HashMap::new();
+    let mut line = 0;
+    let mut hunk = 0;
+    for text in patch.lines() {
+        if let Some(header) = text.strip_prefix("@@") {
+            hunk += 1;
+            // @@ -a,b +c,d @@
+            line = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok())
+                .unwrap_or(0);
+            continue;
+        }
+        if line == 0 {
+            continue;
+        }
+        match text.chars().next() {
+            Some('-') => {}
+            Some('\\') => {} // "\ No newline at end of file"
+            _ => {
+                out.insert(line, hunk);
+                line += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Lines a review comment on `start..=end` anchors to, as `(start_line,
+/// line)`: the last line, or the first when only that one is in the diff.
+/// `start_line` is only set when both ends are in the same hunk, since
+/// GitHub rejects the whole review otherwise. `None` when neither end can
+/// take a comment.
+pub fn anchor(
+    (start, end): (usize, usize),
+    commentable: &HashMap<usize, usize>,
+) -> Option<(Option<usize>, usize)> {
+    match (commentable.get(&start), commentable.get(&end)) {
+        (start_hunk, Some(end_hunk)) => {
+            let same_hunk = start < end && start_hunk == Some(end_hunk);
+            Some((same_hunk.then_some(start), end))
+        }
+        (Some(_), None) => Some((None, start)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(patch: &str) -> Vec<usize> {
+        let mut out: Vec<_> = commentable_lines(patch).into_keys().collect();
+        out.sort_unstable();
+        out
+    }
+
+    #[test]
+    fn includes_added_and_context_lines() {
+        assert_eq!(lines("@@ -4,3 +4,4 @@ param a string\n a\n+b\n c\n-d\n+e\n"), vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn reads_headers_without_counts() {
+        assert_eq!(lines("@@ -1 +1 @@\n-a\n+b\n"), vec![1]);
+    }
+
+    #[test]
+    fn deletion_only_hunk_keeps_context() {
+        assert_eq!(lines("@@ -2,3 +2,2 @@\n a\n-b\n c\n"), vec![2, 3]);
+        assert!(lines("@@ -1,2 +0,0 @@\n-a\n-b\n").is_empty());
+    }
+
+    #[test]
+    fn ignores_no_newline_marker() {
+        assert_eq!(lines("@@ -1 +1,2 @@\n a\n\\ No newline at end of file\n+b\n"), vec![1, 2]);
+    }
+
+    #[test]
+    fn restarts_numbering_per_hunk() {
+        assert_eq!(lines("@@ -1 +1,2 @@\n+a\n b\n@@ -20,1 +21,1 @@\n-c\n+d\n"), vec![1, 2, 21]);
+    }
+
+    #[test]
+    fn numbers_hunks() {
+        let commentable = commentable_lines("@@ -1 +1,2 @@\n+a\n b\n@@ -20,1 +21,1 @@\n-c\n+d\n");
+        assert_eq!(commentable[&1], commentable[&2]);
+        assert_ne!(commentable[&2], commentable[&21]);
+    }
+
+    #[test]
+    fn anchors_ranges_within_one_hunk() {
+        let commentable = commentable_lines("@@ -1 +1,3 @@\n+a\n b\n c\n@@ -20,1 +21,2 @@\n-c\n+d\n e\n");
+        assert_eq!(anchor((1, 3), &commentable), Some((Some(1), 3)));
+        assert_eq!(anchor((2, 2), &commentable), Some((None, 2)));
+        // Across hunks only the last line is commented on
+        assert_eq!(anchor((2, 22), &commentable), Some((None, 22)));
+        // One end outside the diff
+        assert_eq!(anchor((10, 21), &commentable), Some((None, 21)));
+        assert_eq!(anchor((3, 10), &commentable), Some((None, 3)));
+        assert_eq!(anchor((10, 12), &commentable), None);
+    }
+}
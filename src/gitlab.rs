@@ -0,0 +1,334 @@
+// gitlab.rs
+//
+// GitLab REST (v4) client for merge request reviews: diffs, file contents,
+// discussions, notes and commit statuses. The API base URL comes from
+// --api-url / CI_API_V4_URL, for self-managed instances or a local mock.
+
+use crate::http;
+use crate::models::GitLabArgs;
+use crate::services::is_debug_enabled;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+const PAGE_SIZE: usize = 100;
+/// GitLab rejects longer commit status descriptions
+const MAX_DESCRIPTION: usize = 255;
+
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    pub diff_refs: DiffRefs,
+}
+
+/// Commits a merge request diff is computed from
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiffRefs {
+    pub base_sha: String,
+    pub start_sha: String,
+    pub head_sha: String,
+}
+
+/// File changed in a merge request
+#[derive(Debug, Deserialize)]
+pub struct MergeRequestDiff {
+    pub old_path: String,
+    pub new_path: String,
+    #[serde(default)]
+    pub new_file: bool,
+    #[serde(default)]
+    pub deleted_file: bool,
+    /// Unified diff without file headers; empty for binary or collapsed changes
+    #[serde(default)]
+    pub diff: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Discussion {
+    pub id: String,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Note {
+    pub id: u64,
+    #[serde(default)]
+    pub body: String,
+    /// Notes of standalone comments, such as the summary, cannot be resolved
+    #[serde(default)]
+    pub resolvable: bool,
+    #[serde(default)]
+    pub resolved: bool,
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default)]
+    pub resolved_by: Option<User>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct User {
+    pub id: u64,
+}
+
+impl Note {
+    /// Resolved by whoever wrote the note; for the tool's notes, resolved
+    /// by the tool because the finding was gone rather than by a reviewer
+    pub fn resolved_by_author(&self) -> bool {
+        self.resolved && self.resolved_by.is_some() && self.resolved_by == self.author
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewDiscussion {
+    pub body: String,
+    /// Diff position; `None` starts an overview discussion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+}
+
+/// Line in the merge request diff. Added lines only have `new_line`;
+/// unchanged lines need both `old_line` and `new_line`.
+#[derive(Debug, Serialize)]
+pub struct Position {
+    pub position_type: String,
+    #[serde(flatten)]
+    pub refs: DiffRefs,
+    pub old_path: String,
+    pub new_path: String,
+    pub new_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitStatus {
+    /// `success` or `failed`
+    pub state: String,
+    pub name: String,
+    pub description: String,
+}
+
+/// Client for one merge request
+pub struct GitLabClient {
+    api_url: String,
+    token: String,
+    project: String,
+    iid: u64,
+}
+
+impl GitLabClient {
+    pub fn new(args: &GitLabArgs) -> Self {
+        GitLabClient {
+            api_url: args.api_url.trim_end_matches('/').to_string(),
+            token: args.token.clone(),
+            project: args.project.clone(),
+            iid: args.merge_request_iid,
+        }
+    }
+
+    /// URL under the project; a path like `group/name` is encoded as one segment
+    fn project_url(&self, path: &str) -> String {
+        format!(
+            "{}/projects/{}/{}",
+            self.api_url,
+            urlencoding::encode(&self.project),
+            path
+        )
+    }
+
+    fn mr_url(&self, path: &str) -> String {
+        self.project_url(&format!("merge_requests/{}{}", self.iid, path))
+    }
+
+    async fn send(&self, what: &str, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        if is_debug_enabled() {
+            if let Some(r) = request.try_clone().and_then(|r| r.build().ok()) {
+                println!("(DEBUG) gitlab {} => {} {}", what, r.method(), r.url());
+            }
+        }
+        let resp = http::send(request.header("PRIVATE-TOKEN", &self.token)).await?;
+        if !resp.status().is_success() {
+            let st = resp.status();
+            let body = resp.text().await?;
+            return Err(format!("GitLab {} API error: status={}, body={}", what, st, body).into());
+        }
+        Ok(resp)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, what: &str, url: &str) -> Result<T, Box<dyn Error>> {
+        Ok(self.send(what, http::client().get(url)).await?.json().await?)
+    }
+
+    /// All pages of a list endpoint
+    async fn get_pages<T: DeserializeOwned>(&self, what: &str, url: &str) -> Result<Vec<T>, Box<dyn Error>> {
+        let mut out = Vec::new();
+        for page in 1.. {
+            let url = format!("{}?per_page={}&page={}", url, PAGE_SIZE, page);
+            let items: Vec<T> = self.get_json(what, &url).await?;
+            let last = items.len() < PAGE_SIZE;
+            out.extend(items);
+            if last {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    pub async fn merge_request(&self) -> Result<MergeRequest, Box<dyn Error>> {
+        self.get_json("merge request", &self.mr_url("")).await
+    }
+
+    pub async fn diffs(&self) -> Result<Vec<MergeRequestDiff>, Box<dyn Error>> {
+        self.get_pages("merge request diffs", &self.mr_url("/diffs")).await
+    }
+
+    pub async fn discussions(&self) -> Result<Vec<Discussion>, Box<dyn Error>> {
+        self.get_pages("discussions", &self.mr_url("/discussions")).await
+    }
+
+    /// Raw file content at a commit; `None` when the file does not exist there
+    pub async fn file_content(&self, path: &str, sha: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let url = format!(
+            "{}?ref={}",
+            self.project_url(&format!("repository/files/{}/raw", urlencoding::encode(path))),
+            urlencoding::encode(sha)
+        );
+        let resp = http::send(http::client().get(&url).header("PRIVATE-TOKEN", &self.token)).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let st = resp.status();
+            let body = resp.text().await?;
+            return Err(format!("GitLab repository files API error: status={}, body={}", st, body).into());
+        }
+        Ok(Some(resp.text().await?))
+    }
+
+    pub async fn create_discussion(&self, discussion: &NewDiscussion) -> Result<(), Box<dyn Error>> {
+        let url = self.mr_url("/discussions");
+        self.send("create discussion", http::client().post(&url).json(discussion))
+            .await?;
+        Ok(())
+    }
+
+    /// Resolve a discussion when its finding is gone, or reopen it when the
+    /// finding is back
+    pub async fn set_discussion_resolved(&self, discussion_id: &str, resolved: bool) -> Result<(), Box<dyn Error>> {
+        let url = self.mr_url(&format!(
+            "/discussions/{}?resolved={}",
+            urlencoding::encode(discussion_id),
+            resolved
+        ));
+        self.send("resolve discussion", http::client().put(&url))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_note(&self, body: &str) -> Result<(), Box<dyn Error>> {
+        let url = self.mr_url("/notes");
+        self.send(
+            "create note",
+            http::client().post(&url).json(&serde_json::json!({ "body": body })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_note(&self, note_id: u64, body: &str) -> Result<(), Box<dyn Error>> {
+        let url = self.mr_url(&format!("/notes/{}", note_id));
+        self.send(
+            "update note",
+            http::client().put(&url).json(&serde_json::json!({ "body": body })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_commit_status(&self, sha: &str, mut status: CommitStatus) -> Result<(), Box<dyn Error>> {
+        if status.description.chars().count() > MAX_DESCRIPTION {
+            status.description = status.description.chars().take(MAX_DESCRIPTION - 3).collect::<String>() + "...";
+        }
+        let url = self.project_url(&format!("statuses/{}", urlencoding::encode(sha)));
+        self.send("commit status", http::client().post(&url).json(&status))
+            .await?;
+        Ok(())
+    }
+}
+
+/// New-side lines of a unified diff that a discussion can be positioned on,
+/// mapped to their old-side line for unchanged lines (`None` when added)
+pub fn diff_lines(diff: &str) -> HashMap<usize, Option<usize>> {
+    let mut out = HashMap::new();
+    let (mut old, mut new) = (0, 0);
+    for text in diff.lines() {
+        if let Some(header) = text.strip_prefix("@@") {
+            // @@ -a,b +c,d @@
+            let start = |sign: char| {
+                header
+                    .split_whitespace()
+                    .find_map(|part| part.strip_prefix(sign))
+                    .and_then(|range| range.split(',').next())
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0)
+            };
+            old = start('-');
+            new = start('+');
+            continue;
+        }
+        if new == 0 {
+            continue;
+        }
+        match text.chars().next() {
+            Some('+') => {
+                out.insert(new, None);
+                new += 1;
+            }
+            Some('-') => old += 1,
+            Some('\\') => {} // "\ No newline at end of file"
+            _ => {
+                out.insert(new, Some(old));
+                old += 1;
+                new += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_added_and_context_lines() {
+        let lines = diff_lines("@@ -2,3 +2,4 @@ resource stg\n a\n+b\n c\n-d\n+e\n");
+        let expected: HashMap<_, _> = [(2, Some(2)), (3, None), (4, Some(3)), (5, None)].into_iter().collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn reads_headers_without_counts() {
+        let lines = diff_lines("@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(lines, [(1, None)].into_iter().collect());
+    }
+
+    #[test]
+    fn deletion_only_hunk_has_no_lines() {
+        assert!(diff_lines("@@ -3,2 +2,0 @@\n-x\n-y\n").is_empty());
+        assert!(diff_lines("@@ -1,2 +0,0 @@\n-x\n-y\n").is_empty());
+    }
+
+    #[test]
+    fn ignores_no_newline_marker() {
+        let lines = diff_lines("@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n");
+        assert_eq!(lines, [(1, Some(1)), (2, None)].into_iter().collect());
+    }
+
+    #[test]
+    fn restarts_numbering_per_hunk() {
+        let lines = diff_lines("@@ -1,1 +1,2 @@\n+a\n b\n@@ -10,1 +11,1 @@\n-c\n+d\n");
+        assert_eq!(lines, [(1, None), (2, Some(1)), (11, None)].into_iter().collect());
+    }
+}
@@ -0,0 +1,194 @@
+// http.rs
+//
+// Shared HTTP layer for the LLM, search and hosting service calls: one
+// connection pool, a timeout per call, and retries with exponential
+// backoff and jitter for throttling (429), server errors (5xx) and
+// transport failures. `Retry-After` and `retry-after-ms` are honoured.
+// POST and PATCH requests may have taken effect when they fail with a
+// server error or timeout, so unless the caller marks them idempotent they
+// are only retried when throttled or when the connection was never made.
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DEFAULT_MAX_RETRIES: u32 = 4;
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Upper bound for server-requested waits, so a bad header cannot stall the run
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Retry and timeout settings, read from HTTP_MAX_RETRIES and HTTP_TIMEOUT_SECS
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        let max_retries = std::env::var("HTTP_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_RETRIES);
+        let timeout_secs = std::env::var("HTTP_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        RetryPolicy {
+            max_retries,
+            timeout: Duration::from_secs(timeout_secs),
+        }
+    }
+}
+
+/// Process-wide client so connections are reused across calls
+pub fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+/// Send a request, retrying transient failures.
+///
+/// Non-retryable responses (and the last retryable one) are returned as-is,
+/// so callers keep their own status handling.
+pub async fn send(request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+    let idempotent = request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .is_some_and(|r| !matches!(*r.method(), Method::POST | Method::PATCH));
+    send_with_retries(request, idempotent).await
+}
+
+/// Send a POST without side effects, such as a chat completion, with the
+/// full retry policy of idempotent requests
+pub async fn send_idempotent(request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+    send_with_retries(request, true).await
+}
+
+async fn send_with_retries(request: RequestBuilder, idempotent: bool) -> Result<Response, Box<dyn Error>> {
+    let policy = RetryPolicy::from_env();
+    let mut attempt = 0;
+    loop {
+        let this_try = request
+            .try_clone()
+            .ok_or("request body cannot be retried")?
+            .timeout(policy.timeout);
+
+        let wait = match this_try.send().await {
+            Ok(resp) if !is_retryable(resp.status(), idempotent) || attempt >= policy.max_retries => {
+                return Ok(resp)
+            }
+            Ok(resp) => {
+                let wait = retry_after(resp.headers()).unwrap_or_else(|| backoff(attempt));
+                log_retry(&format!("HTTP {}", resp.status()), attempt, policy.max_retries, wait);
+                wait
+            }
+            Err(e) if is_transient(&e, idempotent) && attempt < policy.max_retries => {
+                let wait = backoff(attempt);
+                log_retry(&e.to_string(), attempt, policy.max_retries, wait);
+                wait
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+/// Throttled requests were not processed, so they are always safe to repeat
+fn is_retryable(status: StatusCode, idempotent: bool) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (idempotent && (status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()))
+}
+
+/// A failed connect never reached the server; other failures may have
+fn is_transient(err: &reqwest::Error, idempotent: bool) -> bool {
+    err.is_connect() || (idempotent && (err.is_timeout() || err.is_request()))
+}
+
+/// Exponential backoff with equal jitter: half fixed, half random
+fn backoff(attempt: u32) -> Duration {
+    let exp = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_DELAY);
+    let half = exp / 2;
+    let jitter_ms = random_u64() % (half.as_millis() as u64 + 1);
+    half + Duration::from_millis(jitter_ms)
+}
+
+/// Server-requested wait from `retry-after-ms` (Azure OpenAI) or `Retry-After`
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let wait = if let Some(ms) = header("retry-after-ms").and_then(|v| v.trim().parse::<u64>().ok()) {
+        Duration::from_millis(ms)
+    } else {
+        let value = header(RETRY_AFTER.as_str())?.trim();
+        match value.parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => {
+                let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+                (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                    .to_std()
+                    .unwrap_or_default()
+            }
+        }
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+fn log_retry(reason: &str, attempt: u32, max_retries: u32, wait: Duration) {
+    eprintln!(
+        "Warning: {}, retrying in {} ms ({}/{})",
+        reason,
+        wait.as_millis(),
+        attempt + 1,
+        max_retries
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn retries_server_errors_only_when_idempotent() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS, false));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY, true));
+        assert!(!is_retryable(StatusCode::BAD_GATEWAY, false));
+        assert!(!is_retryable(StatusCode::REQUEST_TIMEOUT, false));
+        assert!(!is_retryable(StatusCode::NOT_FOUND, true));
+    }
+
+    #[test]
+    fn prefers_retry_after_ms() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("250"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(250)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("999999999"));
+        assert_eq!(retry_after(&headers), Some(MAX_RETRY_AFTER));
+    }
+
+    #[test]
+    fn backoff_stays_within_bounds() {
+        for attempt in 0..10 {
+            let exp = BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_DELAY);
+            let wait = backoff(attempt);
+            assert!(wait >= exp / 2 && wait <= exp, "attempt {}: {:?}", attempt, wait);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+// lexer.rs
+
+use crate::ast::{Comment, CommentKind, Position, Span};
+use crate::parser::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Int(i64),
+    String(Vec<StringSegment>),
+    MultilineString(String),
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    DoubleColon,
+    Dot,
+    Ellipsis,
+    Question,
+    DotQuestion,
+    BracketQuestion,
+    DoubleQuestion,
+    At,
+    Assign,
+    Equals,
+    NotEquals,
+    EqualsInsensitive,
+    NotEqualsInsensitive,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Not,
+    And,
+    Or,
+    Pipe,
+    Arrow,
+    Newline,
+    Eof,
+}
+
+/// Piece of a single-quoted string; interpolations are re-lexed by the parser
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringSegment {
+    Text(String),
+    Interpolation { start: Position, end: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub struct Lexer<'a> {
+    src: &'a str,
+    end: usize,
+    pos: Position,
+    pub comments: Vec<Comment>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Lexer {
+            src,
+            end: src.len(),
+            pos: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            comments: Vec::new(),
+        }
+    }
+
+    /// Lex only `src[start.offset..end]`, keeping positions relative to the whole file
+    pub fn with_range(src: &'a str, start: Position, end: usize) -> Self {
+        Lexer {
+            src,
+            end,
+            pos: start,
+            comments: Vec::new(),
+        }
+    }
+
+    /// Lex everything up to the end of the range
+    pub fn tokenize(mut self) -> Result<(Vec<Token>, Vec<Comment>), ParseError> {
+        let mut tokens = Vec::new();
+        loop {
+            let tok = self.next_token()?;
+            let eof = tok.kind == TokenKind::Eof;
+            tokens.push(tok);
+            if eof {
+                break;
+            }
+        }
+        Ok((tokens, self.comments))
+    }
+
+    fn peek(&self) -> Option<char> {
+        if self.pos.offset >= self.end {
+            return None;
+        }
+        self.src[self.pos.offset..self.end].chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        if self.pos.offset >= self.end {
+            return None;
+        }
+        self.src[self.pos.offset..self.end].chars().nth(n)
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.src[self.pos.offset..self.end].starts_with(s)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos.offset += c.len_utf8();
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::new(message, Span::new(self.pos, self.pos))
+    }
+
+    fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_trivia()?;
+        let start = self.pos;
+
+        let c = match self.peek() {
+            Some(c) => c,
+            None => {
+                return Ok(Token {
+                    kind: TokenKind::Eof,
+                    span: Span::new(start, start),
+                })
+            }
+        };
+
+        let kind = if c == '\n' {
+            self.bump();
+            TokenKind::Newline
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(ch) = self.peek() {
+                if ch.is_ascii_alphanumeric() || ch == '_' {
+                    ident.push(ch);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+            TokenKind::Ident(ident)
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(ch) = self.peek() {
+                if ch.is_ascii_digit() {
+                    digits.push(ch);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+            let value = digits
+                .parse::<i64>()
+                .map_err(|_| ParseError::new("integer literal out of range", Span::new(start, self.pos)))?;
+            TokenKind::Int(value)
+        } else if self.starts_with("'''") {
+            self.lex_multiline_string()?
+        } else if c == '\'' {
+            TokenKind::String(self.lex_string()?)
+        } else {
+            self.lex_punct()?
+        };
+
+        Ok(Token {
+            kind,
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\r') => {
+                    self.bump();
+                }
+                Some('/') if self.peek_at(1) == Some('/') => {
+                    let start = self.pos;
+                    self.bump();
+                    self.bump();
+                    self.line_comment(start, CommentKind::Line);
+                }
+                Some('#') => {
+                    let start = self.pos;
+                    self.bump();
+                    self.line_comment(start, CommentKind::Pragma);
+                }
+                Some('/') if self.peek_at(1) == Some('*') => {
+                    let start = self.pos;
+                    self.bump();
+                    self.bump();
+                    let mut text = String::new();
+                    loop {
+                        if self.starts_with("*/") {
+                            self.bump();
+                            self.bump();
+                            break;
+                        }
+                        match self.bump() {
+                            Some(ch) => text.push(ch),
+                            None => return Err(self.error("unterminated block comment")),
+                        }
+                    }
+                    self.comments.push(Comment {
+                        text,
+                        kind: CommentKind::Block,
+                        span: Span::new(start, self.pos),
+                    });
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Record the rest of the line as a comment starting at `start`
+    fn line_comment(&mut self, start: Position, kind: CommentKind) {
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                break;
+            }
+            text.push(ch);
+            self.bump();
+        }
+        self.comments.push(Comment {
+            text: text.trim_end_matches('\r').to_string(),
+            kind,
+            span: Span::new(start, self.pos),
+        });
+    }
+
+    fn lex_multiline_string(&mut self) -> Result<TokenKind, ParseError> {
+        for _ in 0..3 {
+            self.bump();
+        }
+        // A newline directly after the opening quotes is not part of the value
+        if self.starts_with("\r\n") {
+            self.bump();
+            self.bump();
+        } else if self.peek() == Some('\n') {
+            self.bump();
+        }
+        let mut text = String::new();
+        loop {
+            if self.starts_with("'''") {
+                for _ in 0..3 {
+                    self.bump();
+                }
+                return Ok(TokenKind::MultilineString(text));
+            }
+            match self.bump() {
+                Some(ch) => text.push(ch),
+                None => return Err(self.error("unterminated multi-line string")),
+            }
+        }
+    }
+
+    fn lex_string(&mut self) -> Result<Vec<StringSegment>, ParseError> {
+        self.bump(); // opening quote
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => {
+                    self.bump();
+                    break;
+                }
+                Some('\\') => {
+                    self.bump();
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('\\') => '\\',
+                        Some('\'') => '\'',
+                        Some('$') => '$',
+                        Some('u') => self.lex_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    text.push(escaped);
+                }
+                Some('$') if self.peek_at(1) == Some('{') => {
+                    if !text.is_empty() {
+                        segments.push(StringSegment::Text(std::mem::take(&mut text)));
+                    }
+                    self.bump();
+                    self.bump();
+                    let start = self.pos;
+                    self.skip_interpolation()?;
+                    segments.push(StringSegment::Interpolation {
+                        start,
+                        end: self.pos.offset,
+                    });
+                    self.bump(); // closing brace
+                }
+                Some(ch) => {
+                    text.push(ch);
+                    self.bump();
+                }
+            }
+        }
+        if !text.is_empty() || segments.is_empty() {
+            segments.push(StringSegment::Text(text));
+        }
+        Ok(segments)
+    }
+
+    fn lex_unicode_escape(&mut self) -> Result<char, ParseError> {
+        if self.bump() != Some('{') {
+            return Err(self.error("expected '{' in unicode escape"));
+        }
+        let mut hex = String::new();
+        while let Some(ch) = self.bump() {
+            if ch == '}' {
+                break;
+            }
+            hex.push(ch);
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    /// Advance to the `}` closing an interpolation, skipping nested braces and strings
+    fn skip_interpolation(&mut self) -> Result<(), ParseError> {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string interpolation")),
+                Some('}') if depth == 0 => return Ok(()),
+                Some('}') => {
+                    depth -= 1;
+                    self.bump();
+                }
+                Some('{') => {
+                    depth += 1;
+                    self.bump();
+                }
+                Some('\'') => {
+                    self.lex_string()?;
+                }
+                Some(_) => {
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn lex_punct(&mut self) -> Result<TokenKind, ParseError> {
+        const MULTI: [(&str, TokenKind); 14] = [
+            ("...", TokenKind::Ellipsis),
+            ("=~", TokenKind::EqualsInsensitive),
+            ("!~", TokenKind::NotEqualsInsensitive),
+            ("==", TokenKind::Equals),
+            ("!=", TokenKind::NotEquals),
+            ("<=", TokenKind::LessOrEqual),
+            (">=", TokenKind::GreaterOrEqual),
+            ("&&", TokenKind::And),
+            ("||", TokenKind::Or),
+            ("=>", TokenKind::Arrow),
+            ("::", TokenKind::DoubleColon),
+            ("??", TokenKind::DoubleQuestion),
+            (".?", TokenKind::DotQuestion),
+            ("[?", TokenKind::BracketQuestion),
+        ];
+        for (text, kind) in MULTI.iter() {
+            if self.starts_with(text) {
+                for _ in 0..text.len() {
+                    self.bump();
+                }
+                return Ok(kind.clone());
+            }
+        }
+        let c = self.bump().unwrap_or_default();
+        let kind = match c {
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '.' => TokenKind::Dot,
+            '?' => TokenKind::Question,
+            '@' => TokenKind::At,
+            '=' => TokenKind::Assign,
+            '<' => TokenKind::Less,
+            '>' => TokenKind::Greater,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '!' => TokenKind::Not,
+            '|' => TokenKind::Pipe,
+            other => return Err(self.error(format!("unexpected character '{}'", other))),
+        };
+        Ok(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        let (tokens, _) = Lexer::new(src).tokenize().unwrap();
+        tokens.into_iter().map(|t| t.kind).collect()
+    }
+
+    fn comments(src: &str) -> Vec<Comment> {
+        Lexer::new(src).tokenize().unwrap().1
+    }
+
+    #[test]
+    fn lexes_declaration() {
+        assert_eq!(
+            kinds("param count int = 3\n"),
+            vec![
+                TokenKind::Ident("param".to_string()),
+                TokenKind::Ident("count".to_string()),
+                TokenKind::Ident("int".to_string()),
+                TokenKind::Assign,
+                TokenKind::Int(3),
+                TokenKind::Newline,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_longest_operator() {
+        assert_eq!(
+            kinds("a.?b ?? c =~ d::e"),
+            vec![
+                TokenKind::Ident("a".to_string()),
+                TokenKind::DotQuestion,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::DoubleQuestion,
+                TokenKind::Ident("c".to_string()),
+                TokenKind::EqualsInsensitive,
+                TokenKind::Ident("d".to_string()),
+                TokenKind::DoubleColon,
+                TokenKind::Ident("e".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn tracks_spans() {
+        let (tokens, _) = Lexer::new("var x = 1\n  y").tokenize().unwrap();
+        let y = &tokens[5];
+        assert_eq!(y.kind, TokenKind::Ident("y".to_string()));
+        assert_eq!(y.span.start, Position { offset: 12, line: 2, column: 3 });
+        assert_eq!(y.span.end, Position { offset: 13, line: 2, column: 4 });
+    }
+
+    #[test]
+    fn splits_interpolated_string() {
+        let src = "'a${b}c\\'d'";
+        let segments = match &kinds(src)[0] {
+            TokenKind::String(segments) => segments.clone(),
+            other => panic!("expected a string, got {:?}", other),
+        };
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], StringSegment::Text("a".to_string()));
+        match segments[1] {
+            StringSegment::Interpolation { start, end } => assert_eq!(&src[start.offset..end], "b"),
+            _ => panic!("expected an interpolation"),
+        }
+        assert_eq!(segments[2], StringSegment::Text("c'd".to_string()));
+    }
+
+    #[test]
+    fn skips_nested_braces_in_interpolation() {
+        let src = "'${{ a: '}' }.a}'";
+        match &kinds(src)[0] {
+            TokenKind::String(segments) => match segments[..] {
+                [StringSegment::Interpolation { start, end }] => {
+                    assert_eq!(&src[start.offset..end], "{ a: '}' }.a")
+                }
+                _ => panic!("expected one interpolation, got {:?}", segments),
+            },
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_multiline_string_verbatim() {
+        assert_eq!(
+            kinds("'''\nline ${x}\n  two'''")[0],
+            TokenKind::MultilineString("line ${x}\n  two".to_string())
+        );
+    }
+
+    #[test]
+    fn collects_comments_and_pragmas() {
+        let found = comments("// line\n#disable-next-line no-unused-params\nparam p string /* block */\n");
+        let summary: Vec<_> = found.iter().map(|c| (c.kind, c.text.as_str(), c.span.start.line)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (CommentKind::Line, " line", 1),
+                (CommentKind::Pragma, "disable-next-line no-unused-params", 2),
+                (CommentKind::Block, " block ", 3),
+            ]
+        );
+        assert_eq!(found[1].span.start.column, 1);
+    }
+
+    #[test]
+    fn rejects_unterminated_string() {
+        let err = Lexer::new("var s = 'abc\n").tokenize().unwrap_err();
+        assert_eq!(err.message, "unterminated string");
+        assert_eq!(err.span.start.line, 1);
+    }
+}
@@ -0,0 +1,223 @@
+// llm.rs
+
+use crate::http;
+use crate::services::{ChatCompletionRequest, ChatCompletionResponse};
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Provider used when LLM_PROVIDER is not set
+pub const DEFAULT_PROVIDER: &str = "azure";
+
+const DEFAULT_AZURE_API_VERSION: &str = "2023-03-15-preview";
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_COMPATIBLE_BASE_URL: &str = "http://localhost:11434/v1";
+
+/// A chat-completion backend
+#[async_trait(?Send)]
+pub trait LlmProvider {
+    /// Short provider name used in debug output
+    fn name(&self) -> &'static str;
+
+    /// Model (or deployment) that requests are sent to
+    fn model(&self) -> &str;
+
+    /// Send a chat completion request
+    async fn chat(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, Box<dyn Error>>;
+}
+
+/// Which provider implementation to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    AzureOpenAi,
+    OpenAi,
+    OpenAiCompatible,
+}
+
+impl ProviderKind {
+    pub fn parse(value: &str) -> Result<Self, Box<dyn Error>> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "azure" | "azure-openai" => Ok(ProviderKind::AzureOpenAi),
+            "openai" => Ok(ProviderKind::OpenAi),
+            "openai-compatible" | "compatible" | "local" | "ollama" | "vllm" | "llamacpp"
+            | "llama.cpp" => Ok(ProviderKind::OpenAiCompatible),
+            other => Err(format!(
+                "Unknown LLM provider '{}' (expected azure, openai or openai-compatible)",
+                other
+            )
+            .into()),
+        }
+    }
+
+    /// Read LLM_PROVIDER, falling back to Azure OpenAI
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let value = std::env::var("LLM_PROVIDER").unwrap_or_else(|_| DEFAULT_PROVIDER.to_string());
+        Self::parse(&value)
+    }
+
+    /// Short name as accepted by LLM_PROVIDER
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProviderKind::AzureOpenAi => "azure",
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::OpenAiCompatible => "openai-compatible",
+        }
+    }
+
+    /// Env vars that must be present for this provider
+    pub fn required_env_vars(&self) -> &'static [&'static str] {
+        match self {
+            ProviderKind::AzureOpenAi => &[
+                "AZURE_OPENAI_ENDPOINT",
+                "AZURE_OPENAI_API_KEY",
+                "AZURE_OPENAI_DEPLOYMENT",
+            ],
+            ProviderKind::OpenAi => &["OPENAI_API_KEY", "OPENAI_MODEL"],
+            ProviderKind::OpenAiCompatible => &["LLM_MODEL"],
+        }
+    }
+}
+
+// ------------------------------------------------------------
+// Azure OpenAI
+
+pub struct AzureOpenAiProvider {
+    endpoint: String,
+    api_key: String,
+    deployment: String,
+    api_version: String,
+}
+
+impl AzureOpenAiProvider {
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(AzureOpenAiProvider {
+            endpoint: std::env::var("AZURE_OPENAI_ENDPOINT")?,
+            api_key: std::env::var("AZURE_OPENAI_API_KEY")?,
+            deployment: std::env::var("AZURE_OPENAI_DEPLOYMENT")?,
+            api_version: std::env::var("AZURE_OPENAI_API_VERSION")
+                .unwrap_or_else(|_| DEFAULT_AZURE_API_VERSION.to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmProvider for AzureOpenAiProvider {
+    fn name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn model(&self) -> &str {
+        &self.deployment
+    }
+
+    async fn chat(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, Box<dyn Error>> {
+        let url = format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.endpoint.trim_end_matches('/'),
+            self.deployment,
+            self.api_version
+        );
+
+        let resp = http::send_idempotent(
+            http::client()
+                .post(&url)
+                .header("api-key", &self.api_key)
+                .json(request),
+        )
+        .await?
+        .error_for_status()?;
+
+        Ok(resp.json::<ChatCompletionResponse>().await?)
+    }
+}
+
+// ------------------------------------------------------------
+// OpenAI and OpenAI-compatible servers (Ollama, vLLM, llama.cpp)
+
+pub struct OpenAiProvider {
+    name: &'static str,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiProvider {
+    /// The public OpenAI API
+    pub fn openai_from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(OpenAiProvider {
+            name: "openai",
+            base_url: std::env::var("OPENAI_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_OPENAI_BASE_URL.to_string()),
+            api_key: Some(std::env::var("OPENAI_API_KEY")?),
+            model: std::env::var("OPENAI_MODEL")?,
+        })
+    }
+
+    /// Any server exposing /chat/completions; the API key is optional
+    pub fn compatible_from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(OpenAiProvider {
+            name: "openai-compatible",
+            base_url: std::env::var("LLM_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_COMPATIBLE_BASE_URL.to_string()),
+            api_key: std::env::var("LLM_API_KEY").ok().filter(|k| !k.is_empty()),
+            model: std::env::var("LLM_MODEL")?,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, Box<dyn Error>> {
+        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
+
+        let mut body = request.clone();
+        body.model = self.model.clone();
+
+        let mut builder = http::client().post(&url).json(&body);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let resp = http::send_idempotent(builder).await?.error_for_status()?;
+        Ok(resp.json::<ChatCompletionResponse>().await?)
+    }
+}
+
+// ------------------------------------------------------------
+// Selection
+
+/// Build the provider selected by LLM_PROVIDER
+pub fn provider_from_env() -> Result<Box<dyn LlmProvider>, Box<dyn Error>> {
+    let provider: Box<dyn LlmProvider> = match ProviderKind::from_env()? {
+        ProviderKind::AzureOpenAi => Box::new(AzureOpenAiProvider::from_env()?),
+        ProviderKind::OpenAi => Box::new(OpenAiProvider::openai_from_env()?),
+        ProviderKind::OpenAiCompatible => Box::new(OpenAiProvider::compatible_from_env()?),
+    };
+    Ok(provider)
+}
+
+/// Model name to put in outgoing requests
+pub fn configured_model() -> String {
+    let var = match ProviderKind::from_env() {
+        Ok(ProviderKind::OpenAi) => "OPENAI_MODEL",
+        Ok(ProviderKind::OpenAiCompatible) => "LLM_MODEL",
+        _ => "AZURE_OPENAI_DEPLOYMENT",
+    };
+    std::env::var(var).unwrap_or_default()
+}
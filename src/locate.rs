@@ -0,0 +1,265 @@
+// locate.rs
+//
+// Verifies the line numbers the model cites and fills in locations for
+// findings that have none, using the parsed symbols and the raw source.
+
+use crate::ast::Program;
+use crate::models::{Location, ValidationResult};
+use std::collections::HashMap;
+
+/// Maximum number of source lines copied into a finding's snippet
+const MAX_SNIPPET_LINES: usize = 6;
+
+/// Resolve and verify locations of all findings for one file.
+///
+/// A cited range is kept when it is inside the file and mentions one of
+/// the symbols named in the finding; otherwise the finding is moved to the
+/// symbol's declaration or first occurrence. Findings that cannot be
+/// anchored anywhere get a file-level location.
+pub fn resolve_locations(
+    findings: &mut [ValidationResult],
+    file_path: &str,
+    source: &str,
+    program: Option<&Program>,
+) {
+    let lines: Vec<&str> = source.lines().collect();
+    let declarations: HashMap<&str, Location> = program
+        .map(|p| {
+            p.symbols()
+                .into_iter()
+                .map(|(_, ident)| {
+                    (
+                        ident.name.as_str(),
+                        Location {
+                            start_line: ident.span.start.line,
+                            start_column: ident.span.start.column,
+                            end_line: ident.span.end.line,
+                            end_column: ident.span.end.column,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for finding in findings.iter_mut() {
+        let mentioned = mentioned_symbols(&finding.finding);
+        let cited = finding.location.take();
+        finding.location = Some(
+            match resolve(cited, &mentioned, &lines, &declarations) {
+                Some(mut loc) => {
+                    loc.file_path = Some(file_path.to_string());
+                    loc.snippet = Some(snippet(&lines, &loc));
+                    loc
+                }
+                None => Location::file_level(file_path),
+            },
+        );
+    }
+}
+
+/// Words quoted with backticks or single quotes in the finding text
+fn mentioned_symbols(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for quote in ['`', '\''] {
+        let parts: Vec<&str> = text.split(quote).collect();
+        for (i, part) in parts.iter().enumerate() {
+            let word = part.trim();
+            if i % 2 == 1 && i + 1 < parts.len() && !word.is_empty() && !word.contains(' ') {
+                out.push(word.trim_start_matches('@').to_string());
+            }
+        }
+    }
+    out
+}
+
+fn resolve(
+    cited: Option<Location>,
+    mentioned: &[String],
+    lines: &[&str],
+    declarations: &HashMap<&str, Location>,
+) -> Option<Location> {
+    if lines.is_empty() {
+        return None;
+    }
+
+    if let Some(mut loc) = cited.filter(|l| l.start_line >= 1 && l.start_line <= lines.len()) {
+        loc.end_line = loc.end_line.clamp(loc.start_line, lines.len());
+
+        // Columns supplied by the rule engine are exact already
+        if loc.start_column > 0 && loc.end_column > 0 {
+            return Some(loc);
+        }
+
+        let cited_text = lines[loc.start_line - 1..loc.end_line].join("\n");
+        if mentioned.is_empty() || mentioned.iter().any(|m| find_identifier(&cited_text, m).is_some()) {
+            return Some(with_columns(loc, mentioned, lines));
+        }
+        // The model cited a line that does not mention the symbol; fall through
+    }
+
+    for name in mentioned {
+        if let Some(decl) = declarations.get(name.as_str()) {
+            return Some(decl.clone());
+        }
+    }
+    for name in mentioned {
+        if let Some(idx) = lines.iter().position(|l| find_identifier(l, name).is_some()) {
+            let loc = Location {
+                start_line: idx + 1,
+                end_line: idx + 1,
+                ..Default::default()
+            };
+            return Some(with_columns(loc, mentioned, lines));
+        }
+    }
+    None
+}
+
+/// Narrow to the first mentioned symbol on the start line, or span the whole trimmed lines
+fn with_columns(mut loc: Location, mentioned: &[String], lines: &[&str]) -> Location {
+    let start_text = lines[loc.start_line - 1];
+    for name in mentioned {
+        if let Some(byte_idx) = find_identifier(start_text, name) {
+            let col = start_text[..byte_idx].chars().count() + 1;
+            loc.end_line = loc.start_line;
+            loc.start_column = col;
+            loc.end_column = col + name.chars().count();
+            return loc;
+        }
+    }
+    let indent = start_text.chars().take_while(|c| c.is_whitespace()).count();
+    loc.start_column = indent + 1;
+    loc.end_column = lines[loc.end_line - 1].trim_end().chars().count() + 1;
+    loc
+}
+
+/// Byte offset of the first occurrence of `name` that is not part of a
+/// longer identifier, so `a` does not match inside `param`
+fn find_identifier(text: &str, name: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    if name.is_empty() {
+        return None;
+    }
+    text.match_indices(name).map(|(idx, _)| idx).find(|&idx| {
+        let before = text[..idx].chars().next_back();
+        let after = text[idx + name.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+fn snippet(lines: &[&str], loc: &Location) -> String {
+    let end = loc.end_line.min(loc.start_line + MAX_SNIPPET_LINES - 1);
+    lines
+        .get(loc.start_line.saturating_sub(1)..end.min(lines.len()))
+        .unwrap_or(&[])
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    const SRC: &str = "\
+param a string
+param names array
+var prefix = 'x${a}'
+output count int = length(names)
+";
+
+    fn located(text: &str, cited: Option<(usize, usize)>, with_program: bool) -> Location {
+        let program = parse(SRC).unwrap();
+        let mut findings = vec![ValidationResult {
+            category: "Parameters".to_string(),
+            finding: text.to_string(),
+            severity: 3,
+            impact: String::new(),
+            rule_id: None,
+            location: cited.map(|(start_line, end_line)| Location {
+                start_line,
+                end_line,
+                ..Default::default()
+            }),
+        }];
+        resolve_locations(&mut findings, "main.bicep", SRC, with_program.then_some(&program));
+        findings.remove(0).location.unwrap()
+    }
+
+    fn span(loc: &Location) -> (usize, usize, usize, usize) {
+        (loc.start_line, loc.start_column, loc.end_line, loc.end_column)
+    }
+
+    #[test]
+    fn finds_whole_identifiers_only() {
+        assert_eq!(find_identifier("param a string", "a"), Some(6));
+        assert_eq!(find_identifier("param names array", "name"), None);
+        assert_eq!(find_identifier("length(names)", "names"), Some(7));
+        assert_eq!(find_identifier("var prefix = 'x${a}'", "a"), Some(17));
+        assert_eq!(find_identifier("x", ""), None);
+    }
+
+    #[test]
+    fn collects_quoted_symbols() {
+        assert_eq!(
+            mentioned_symbols("Parameter `a` feeds 'prefix' and `@secure` but not `two words`"),
+            vec!["a", "secure", "prefix"]
+        );
+    }
+
+    #[test]
+    fn narrows_a_verified_citation_to_the_symbol() {
+        let loc = located("Parameter `a` has no description.", Some((1, 1)), true);
+        assert_eq!(span(&loc), (1, 7, 1, 8));
+        assert_eq!(loc.file_path.as_deref(), Some("main.bicep"));
+        assert_eq!(loc.snippet.as_deref(), Some("param a string"));
+    }
+
+    #[test]
+    fn moves_a_citation_that_only_contains_the_letters() {
+        // Line 2 mentions `names`, not `name`; without a declaration the
+        // finding has nowhere to go
+        let loc = located("Use `name` consistently.", Some((2, 2)), true);
+        assert!(loc.is_file_level());
+
+        let loc = located("Parameter `a` is only used once.", Some((2, 2)), true);
+        assert_eq!(span(&loc), (1, 7, 1, 8));
+    }
+
+    #[test]
+    fn falls_back_to_the_first_whole_word_occurrence() {
+        let loc = located("`count` shadows a function name.", None, false);
+        assert_eq!(span(&loc), (4, 8, 4, 13));
+        let loc = located("Parameter `a` is interpolated.", Some((9, 9)), false);
+        assert_eq!(span(&loc), (1, 7, 1, 8));
+    }
+
+    #[test]
+    fn spans_whole_lines_without_a_symbol() {
+        let loc = located("Consider a naming convention.", Some((2, 3)), true);
+        assert_eq!(span(&loc), (2, 1, 3, 21));
+    }
+
+    #[test]
+    fn keeps_exact_rule_engine_columns() {
+        let program = parse(SRC).unwrap();
+        let exact = Location {
+            start_line: 3,
+            start_column: 5,
+            end_line: 3,
+            end_column: 11,
+            ..Default::default()
+        };
+        let mut findings = vec![ValidationResult {
+            category: "Variables".to_string(),
+            finding: "Unrelated `zzz`".to_string(),
+            severity: 2,
+            impact: String::new(),
+            rule_id: Some("variables/no-unused".to_string()),
+            location: Some(exact),
+        }];
+        resolve_locations(&mut findings, "main.bicep", SRC, Some(&program));
+        assert_eq!(span(findings[0].location.as_ref().unwrap()), (3, 5, 3, 11));
+    }
+}
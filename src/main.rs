@@ -1,16 +1,45 @@
+mod ast;
+mod azure_devops;
+mod baseline;
+mod cache;
+mod catalog;
+mod config;
+mod diff;
+mod discover;
+mod fixtures;
+mod git;
+mod github;
+mod gitlab;
+mod http;
+mod lexer;
+mod llm;
+mod locate;
 mod models;
+mod parser;
+mod policy;
+mod report;
+mod rulepack;
+mod rules;
+mod sarif;
 mod services;
+mod suppress;
+mod threads;
 
 #[macro_use]
 extern crate prettytable;
 
+use crate::catalog::RuleCatalog;
+use crate::config::Settings;
+use crate::diff::FileDiff;
 use crate::models::*;
+use crate::policy::{usage, FailPolicy};
 use crate::services::*;
 use clap::{Parser, Subcommand};
-use prettytable::{Cell, Row, Table};
+use futures::future::join_all;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 use std::{fs, process};
-
-const TABLE_WRAP_WIDTH: usize = 60;
+use tokio::sync::Semaphore;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -18,213 +47,554 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
+    /// Settings shared with the subcommands
+    #[command(flatten)]
+    options: ReviewOptions,
+
     /// Path to local Bicep file (for direct analysis)
     #[arg(long)]
     bicep_file: Option<String>,
 
-    /// Path to best practices MD file (for direct analysis)
+    /// Files, directories or globs to scan for .bicep files (repeatable)
     #[arg(long)]
-    best_practices_file: Option<String>,
+    path: Vec<String>,
 
-    /// Debug mode
+    /// Gitignore-style pattern to leave out of directory and glob scans (repeatable)
     #[arg(long)]
-    debug: Option<bool>,
+    exclude: Vec<String>,
 
-    /// Minimum severity level (1-5)
-    #[arg(long)]
-    minimum_severity: Option<u8>,
+    /// Output format for local analysis
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
 
-    /// Simple mode - single prompt without categories
+    /// Write the report to this file instead of stdout
     #[arg(long)]
-    simple: Option<bool>,
+    output: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Azure DevOps PR-based analysis
-    Azure(AzureDevOpsArgs),
+    Azure(Box<AzureDevOpsArgs>),
+    /// GitHub pull request analysis
+    Github(Box<GitHubArgs>),
+    /// GitLab merge request analysis
+    Gitlab(Box<GitLabArgs>),
+    /// Review the changes in a local git range or the staged changes
+    Review(Box<GitReviewArgs>),
+    /// Inspect or prune the LLM response cache
+    Cache(CacheArgs),
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     dotenv::dotenv().ok();
-    
-    let cli = Cli::parse();
 
-    // Determine if we're doing local analysis or using a subcommand
-    match (cli.bicep_file, cli.command) {
-        // Local file analysis
-        (Some(bicep_file), None) => {
-            let args = Args {
-                bicep_file,
-                best_practices_file: cli.best_practices_file
-                    .ok_or("--best-practices-file is required")?,
-                category: None,
-                debug: cli.debug.unwrap_or(false),
-                minimum_severity: cli.minimum_severity.unwrap_or(DEFAULT_MIN_SEVERITY),
-                simple: cli.simple.unwrap_or(false),
-            };
+    let code = match run(Cli::parse()).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            policy::exit_code_for(e.as_ref())
+        }
+    };
+    process::exit(code);
+}
 
-            let config = init_local_config(&args)?;
-            debug_local_args(&args, config.debug);
+/// Run the selected mode and return the exit code
+async fn run(cli: Cli) -> Result<i32, Box<dyn std::error::Error>> {
+    // Determine if we're doing local analysis or using a subcommand
+    let local = cli.bicep_file.is_some() || !cli.path.is_empty();
+    match (local, cli.command) {
+        // Local file analysis: explicit targets, or `paths` from the config file
+        (_, None) => {
+            let settings = prepare_settings(&cli.options)?;
+            if !local && settings.paths.is_empty() {
+                return Err(usage(
+                    "Must specify either --bicep-file, --path or a subcommand",
+                ));
+            }
+            let policy = FailPolicy::new(settings.fail_on_severity, settings.max_findings)?;
 
-            let reviews = analyze_local_bicep(&config).await?;
-            let report = finalize_report(reviews, config.minimum_severity).await?;
+            let started = Instant::now();
+            let mut targets: Vec<String> = cli.bicep_file.into_iter().collect();
+            targets.extend(cli.path);
+            let outputs = match (cli.format, cli.output) {
+                (None, None) if !settings.outputs.is_empty() => settings.outputs.clone(),
+                (format, path) => vec![config::OutputSpec {
+                    format: format.unwrap_or_default(),
+                    path,
+                }],
+            };
+            let config = init_local_config(settings, targets, cli.exclude, outputs)?;
+            run_local(config, &policy, started).await
+        }
 
-            println!("\n{}", report);
-            if report.contains("5 (Critical)") {
-                process::exit(1);
-            }
+        // Local git review
+        (false, Some(Commands::Review(args))) => {
+            let settings = prepare_change_settings(&cli.options)?;
+            let policy = FailPolicy::new(settings.fail_on_severity, settings.max_findings)?;
+            let started = Instant::now();
+            let config = init_git_config(settings, *args)?;
+            run_local(config, &policy, started).await
         }
 
         // Azure DevOps analysis
-        (None, Some(Commands::Azure(args))) => {
-            if args.debug {
-                std::env::set_var("BICEP_DEBUG", "true");
-            }
-            check_env_vars()?;
-            debug_ado_args(&args);
-            run_pr_review(args).await?;
+        (false, Some(Commands::Azure(args))) => {
+            let settings = prepare_change_settings(&cli.options)?;
+            debug_ado_args(&args, &settings);
+            let policy = FailPolicy::new(settings.fail_on_severity, settings.max_findings)?;
+            let findings = run_pr_review(*args, &settings, &policy).await?;
+            Ok(policy.evaluate(&findings.iter().collect::<Vec<_>>()))
         }
 
-        // Invalid combinations
-        (Some(_), Some(_)) => {
-            return Err("Cannot specify both --bicep-file and a subcommand".into());
+        // GitHub mode
+        (false, Some(Commands::Github(args))) => {
+            let settings = prepare_change_settings(&cli.options)?;
+            let policy = FailPolicy::new(settings.fail_on_severity, settings.max_findings)?;
+            let findings = run_github_review(*args, &settings, &policy).await?;
+            Ok(policy.evaluate(&findings.iter().collect::<Vec<_>>()))
         }
-        (None, None) => {
-            return Err("Must specify either --bicep-file or a subcommand".into());
+
+        // GitLab mode
+        (false, Some(Commands::Gitlab(args))) => {
+            let settings = prepare_change_settings(&cli.options)?;
+            let policy = FailPolicy::new(settings.fail_on_severity, settings.max_findings)?;
+            let findings = run_gitlab_review(*args, &settings, &policy).await?;
+            Ok(policy.evaluate(&findings.iter().collect::<Vec<_>>()))
         }
+
+        // Cache maintenance
+        (false, Some(Commands::Cache(args))) => run_cache_command(args),
+
+        // Invalid combinations
+        (true, Some(_)) => Err(usage("Cannot specify both --bicep-file/--path and a subcommand")),
     }
+}
 
-    Ok(())
+/// Merge flags, env vars and the config file, then export what services read from env
+fn prepare_settings(options: &ReviewOptions) -> Result<Settings, Box<dyn std::error::Error>> {
+    set_llm_provider(options.llm_provider.as_deref())?;
+    let settings = Settings::resolve(options)?;
+    set_debug(settings.debug);
+    set_fixture_mode(options.record.as_deref(), options.replay.as_deref())?;
+    set_no_cache(options.no_cache);
+    check_settings(&settings)?;
+    if !settings.rules_only {
+        check_env_vars()?;
+    }
+    Ok(settings)
+}
+
+/// Settings for the reviews of changes, which can apply a baseline but not write one
+fn prepare_change_settings(options: &ReviewOptions) -> Result<Settings, Box<dyn std::error::Error>> {
+    let settings = prepare_settings(options)?;
+    if settings.write_baseline {
+        return Err(usage("--write-baseline is only supported for local analysis without a subcommand"));
+    }
+    Ok(settings)
+}
+
+/// Baseline of accepted findings; none while a new one is being written
+fn load_baseline(settings: &Settings) -> Result<Option<baseline::Baseline>, Box<dyn std::error::Error>> {
+    match (&settings.baseline, settings.write_baseline) {
+        (Some(path), false) => Ok(Some(baseline::Baseline::load(path)?)),
+        _ => Ok(None),
+    }
 }
 
 /// -------------------------------------------------------
 /// LOCAL STRUCT & FUNCS
-
 struct LocalConfig {
-    bicep_content: String,
-    best_practices: String,
+    files: Vec<SourceFile>,
+    catalog: RuleCatalog,
+    /// Categories of the whole run, for reports
     categories: Vec<String>,
-    minimum_severity: u8,
-    debug: bool,
-    simple: bool,
+    settings: Settings,
+    outputs: Vec<config::OutputSpec>,
+    /// Baseline of accepted findings, loaded before any review runs
+    known: Option<baseline::Baseline>,
+}
+
+/// One Bicep file to review
+struct SourceFile {
+    path: String,
+    content: String,
+    /// Lines changed in a git review; `None` reviews the whole file
+    diff: Option<FileDiff>,
 }
 
-/// Build local config from command line Args
-fn init_local_config(args: &Args) -> Result<LocalConfig, Box<dyn std::error::Error>> {
-    check_local_args(args)?;
-    check_env_vars()?;
-    set_debug(args.debug);
+/// Discover and parse the files to review and load the best practices
+fn init_local_config(
+    settings: Settings,
+    mut targets: Vec<String>,
+    exclude: Vec<String>,
+    outputs: Vec<config::OutputSpec>,
+) -> Result<LocalConfig, Box<dyn std::error::Error>> {
+    if targets.is_empty() {
+        targets = settings.paths.clone();
+    }
+    let mut excludes = discover::Excludes::new()?;
+    excludes.add(std::path::Path::new("."), &exclude)?;
+    excludes.add(settings.config_root(), &settings.ignore)?;
+
+    let mut files = Vec::new();
+    for path in discover::discover_bicep_files(&targets, &excludes)? {
+        let content = read_input(&path)?;
+        files.push(SourceFile {
+            path,
+            content,
+            diff: None,
+        });
+    }
+    let catalog = load_best_practices(settings.best_practices_file.as_deref())?;
+    let categories = settings.categories(&catalog);
+    let known = load_baseline(&settings)?;
 
-    let bicep_content = fs::read_to_string(&args.bicep_file)?;
-    let best_practices = fs::read_to_string(&args.best_practices_file)?;
+    Ok(LocalConfig {
+        files,
+        catalog,
+        categories,
+        settings,
+        outputs,
+        known,
+    })
+}
 
-    let categories = match &args.category {
-        Some(cat) => vec![cat.clone()],
-        None => DEFAULT_CATEGORIES.iter().map(|&s| s.to_string()).collect(),
+/// Changed files of a git range or the index, with their diffs, and the best
+/// practices. Files that match `ignore` or whose content did not change are
+/// left out.
+fn init_git_config(settings: Settings, args: GitReviewArgs) -> Result<LocalConfig, Box<dyn std::error::Error>> {
+    let changes = match args.git_range {
+        Some(range) => git::Changes::Range(range),
+        None => git::Changes::Staged,
     };
+    let mut ignore = discover::Excludes::new()?;
+    ignore.add(settings.config_root(), &settings.ignore)?;
+    let mut files = Vec::new();
+    for changed in git::changed_bicep_files(&changes)? {
+        if ignore.is_excluded(std::path::Path::new(&changed.path), false) {
+            continue;
+        }
+        let diff = head_diff(&settings, changed.base.as_deref(), &changed.content);
+        if diff.as_ref().is_some_and(FileDiff::is_unchanged) {
+            continue;
+        }
+        files.push(SourceFile {
+            path: changed.path,
+            content: changed.content,
+            diff,
+        });
+    }
+    if files.is_empty() {
+        eprintln!("No changed Bicep files to review");
+    }
+    let catalog = load_best_practices(settings.best_practices_file.as_deref())?;
+    let categories = settings.categories(&catalog);
+    let outputs = match (args.format, args.output) {
+        (None, None) if !settings.outputs.is_empty() => settings.outputs.clone(),
+        (format, path) => vec![config::OutputSpec {
+            format: format.unwrap_or_default(),
+            path,
+        }],
+    };
+    let known = load_baseline(&settings)?;
 
     Ok(LocalConfig {
-        bicep_content,
-        best_practices,
+        files,
+        catalog,
         categories,
-        minimum_severity: args.minimum_severity,
-        debug: args.debug,
-        simple: args.simple,
+        settings,
+        outputs,
+        known,
     })
 }
 
-/// Check arguments
-fn check_local_args(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
-    if !(1..=5).contains(&args.minimum_severity) {
-        return Err("Minimum severity must be 1..=5".into());
+/// Review the files of a local run and render the configured outputs
+async fn run_local(
+    config: LocalConfig,
+    policy: &FailPolicy,
+    started: Instant,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    debug_local_config(&config);
+
+    // Files and categories run concurrently; join_all keeps the input order
+    let settings = &config.settings;
+    let reviews_started = Instant::now();
+    let limit = Semaphore::new(settings.concurrency);
+    let reviews = join_all(config.files.iter().map(|file| {
+        if settings.debug {
+            println!("(DEBUG) Reviewing file: {}", file.path);
+        }
+        review_changed_file(
+            settings,
+            &config.catalog,
+            config.known.as_ref(),
+            &file.path,
+            &file.content,
+            file.diff.clone(),
+            &limit,
+        )
+    }))
+    .await;
+    let reviews_ms = reviews_started.elapsed().as_millis() as u64;
+
+    let mut report = FinalReport {
+        findings: Vec::new(),
+        metadata: None,
+    };
+    let mut suppressed = Vec::new();
+    let mut baselined = 0;
+    let mut rules_ms = 0;
+    let mut baseline_entries = Vec::new();
+    for (file, review) in config.files.iter().zip(reviews) {
+        let review = review?;
+        suppressed.extend(review.suppressed);
+        baselined += review.baselined;
+        rules_ms += review.rules_ms;
+        if settings.write_baseline {
+            let project_path = settings.project_path(&file.path);
+            baseline_entries.extend(
+                review.report.findings.iter()
+                    .map(|f| baseline::entry(f, &project_path, review.program.as_ref())),
+            );
+        }
+        report.findings.extend(review.report.findings);
+    }
+
+    if settings.write_baseline {
+        let path = settings
+            .baseline
+            .as_deref()
+            .unwrap_or(baseline::DEFAULT_BASELINE_FILE);
+        let count = baseline_entries.len();
+        baseline::write(path, baseline_entries)?;
+        println!("Baseline with {} findings written to {}", count, path);
+        return Ok(policy::EXIT_OK);
+    }
+    let findings = filter_by_severity(&report, settings);
+
+    let metadata = build_metadata(
+        &config,
+        (suppressed.len(), baselined),
+        Timings {
+            total_ms: started.elapsed().as_millis() as u64,
+            rules_ms,
+            // The rules run on the same task as the LLM reviews
+            llm_ms: reviews_ms.saturating_sub(rules_ms),
+        },
+    );
+    let ctx = report::RenderContext {
+        min_severity: config.settings.minimum_severity,
+        catalog: &config.catalog,
+        categories: &config.categories,
+        metadata: &metadata,
+        suppressed: &suppressed,
+    };
+    for output in &config.outputs {
+        let rendered = report::render(output.format, &findings, &ctx)?;
+        write_output(output.path.as_deref(), output.format, &rendered)?;
+    }
+
+    Ok(policy.evaluate(&findings))
+}
+
+/// Read an input file; a missing or unreadable file is a usage error
+fn read_input(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    fs::read_to_string(path).map_err(|e| usage(format!("Cannot read {}: {}", path, e)))
+}
+
+/// Read and parse the best-practices markdown or rule pack
+fn load_best_practices(path: Option<&str>) -> Result<RuleCatalog, Box<dyn std::error::Error>> {
+    let path = path.ok_or_else(|| {
+        usage(format!(
+            "--best-practices-file is required (or set best_practices_file in {})",
+            config::CONFIG_FILE_NAME
+        ))
+    })?;
+    RuleCatalog::load(path, &read_input(path)?)
+}
+
+/// Built-in rule findings plus the matchers of the rule pack, minus disabled rules
+fn static_findings(
+    settings: &Settings,
+    catalog: &RuleCatalog,
+    path: &str,
+    content: &str,
+    program: Option<&ast::Program>,
+) -> Vec<ValidationResult> {
+    let mut findings = program.map(rules::run_rules).unwrap_or_default();
+    findings.extend(rulepack::run_matchers(catalog, content, program));
+    findings.retain(|f| match &f.rule_id {
+        Some(id) => settings.rule_enabled(path, id),
+        None => true,
+    });
+    findings
+}
+
+/// Parse Bicep source, warning (but not failing) on syntax errors
+fn parse_bicep(path: &str, content: &str) -> Option<ast::Program> {
+    match parser::parse(content) {
+        Ok(program) => {
+            for err in &program.errors {
+                eprintln!("Warning: {}: {}", path, err);
+            }
+            Some(program)
+        }
+        Err(e) => {
+            eprintln!("Warning: {}: could not parse Bicep: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Check the resolved settings
+fn check_settings(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    if !(1..=5).contains(&settings.minimum_severity) {
+        return Err(usage("Minimum severity must be 1..=5"));
+    }
+    if settings.concurrency == 0 {
+        return Err(usage("--concurrency must be at least 1"));
     }
     Ok(())
 }
 
-/// Basic environment var check
+/// Basic environment var check; replay mode needs no endpoints
 fn check_env_vars() -> Result<(), Box<dyn std::error::Error>> {
-    let needed = [
-        "AZURE_OPENAI_ENDPOINT",
-        "AZURE_OPENAI_API_KEY",
-        "AZURE_OPENAI_DEPLOYMENT",
+    if fixtures::is_replay() {
+        return Ok(());
+    }
+    let provider = crate::llm::ProviderKind::from_env()?;
+    let search = [
         "AZURE_SEARCH_ENDPOINT",
         "AZURE_SEARCH_ADMIN_KEY",
         "AZURE_SEARCH_INDEX",
     ];
-    let missing: Vec<_> = needed
+    let missing: Vec<_> = provider
+        .required_env_vars()
         .iter()
+        .chain(search.iter())
         .filter(|var| std::env::var(var).is_err())
         .collect();
 
     if !missing.is_empty() {
-        return Err(format!("Missing required env vars: {:?}", missing).into());
+        return Err(usage(format!("Missing required env vars: {:?}", missing)));
     }
     Ok(())
 }
 
+/// Validate and export the provider choice so services pick it up
+fn set_llm_provider(provider: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(p) = provider {
+        crate::llm::ProviderKind::parse(p).map_err(|e| usage(e.to_string()))?;
+        std::env::set_var("LLM_PROVIDER", p);
+    }
+    Ok(())
+}
+
+/// Export --record/--replay so services pick them up
+fn set_fixture_mode(
+    record: Option<&str>,
+    replay: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match (record, replay) {
+        (Some(_), Some(_)) => Err(usage("--record and --replay cannot be combined")),
+        (Some(dir), None) => {
+            fs::create_dir_all(dir)
+                .map_err(|e| usage(format!("Cannot create record dir {}: {}", dir, e)))?;
+            std::env::set_var(fixtures::RECORD_DIR_VAR, dir);
+            Ok(())
+        }
+        (None, Some(dir)) => {
+            if !std::path::Path::new(dir).is_dir() {
+                return Err(usage(format!("Replay dir not found: {}", dir)));
+            }
+            std::env::set_var(fixtures::REPLAY_DIR_VAR, dir);
+            Ok(())
+        }
+        (None, None) => Ok(()),
+    }
+}
+
+fn set_no_cache(no_cache: bool) {
+    if no_cache {
+        std::env::set_var("BICEP_CACHE", "off");
+    }
+}
+
 fn set_debug(debug: bool) {
     if debug {
         std::env::set_var("BICEP_DEBUG", "true");
     }
 }
 
-fn debug_local_args(args: &Args, debug: bool) {
-    if debug {
-        println!("(DEBUG) Local Args => bicep_file={}, best_practices_file={}, category={:?}, minSeverity={}, debug={}",
-            args.bicep_file, args.best_practices_file, args.category, args.minimum_severity, args.debug);
+fn debug_local_config(cfg: &LocalConfig) {
+    let s = &cfg.settings;
+    if s.debug {
+        println!("(DEBUG) Local config => config={:?}, files={:?}, best_practices_file={:?}, categories={:?}, minSeverity={}, outputs={:?}",
+            s.source, cfg.files.iter().map(|f| &f.path).collect::<Vec<_>>(), s.best_practices_file, cfg.categories, s.minimum_severity, cfg.outputs);
     }
 }
 
-/// Analyze local Bicep code
-async fn analyze_local_bicep(cfg: &LocalConfig) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    if cfg.simple {
-        // Simple mode: single prompt
-        if cfg.debug {
-            println!("(DEBUG) Running simple analysis without categories");
-        }
-        let result = services::validate_simple(&cfg.bicep_content, &cfg.best_practices).await?;
-        Ok(vec![result])
+/// Describe the run for machine-readable outputs
+/// `dropped` counts the suppressed and the baselined findings
+fn build_metadata(cfg: &LocalConfig, dropped: (usize, usize), timings: Timings) -> ReportMetadata {
+    let (provider, model) = if cfg.settings.rules_only {
+        (None, None)
     } else {
-        // Original category-based analysis
-        let mut reviews = Vec::new();
-        for cat in &cfg.categories {
-            if cfg.debug {
-                println!("(DEBUG) Analyzing category: {}", cat);
-            }
-            let rev = services::analyze_category(
-                &cfg.bicep_content,
-                &cfg.best_practices,
-                cat,
-                cfg.debug,
-            )
-            .await?;
-            reviews.push(rev);
-        }
-        Ok(reviews)
+        (
+            crate::llm::ProviderKind::from_env()
+                .ok()
+                .map(|p| p.name().to_string()),
+            Some(crate::llm::configured_model()),
+        )
+    };
+    ReportMetadata {
+        tool: crate::sarif::TOOL_NAME.to_string(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        provider,
+        model,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        minimum_severity: cfg.settings.minimum_severity,
+        files: cfg.files.iter().map(|f| f.path.clone()).collect(),
+        suppressed: dropped.0,
+        baselined: dropped.1,
+        timings,
     }
 }
 
-/// Convert reviews into final JSON -> markdown
-async fn finalize_report(
-    review_texts: Vec<String>,
-    min_severity: u8,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let request = build_final_report_request(&review_texts);
-    let response = call_azure_openai(&request).await?;
-    let report: FinalReport = serde_json::from_str(&response.choices[0].message.content)?;
-
-    let findings = filter_by_severity(&report, min_severity);
-    build_markdown(&findings, min_severity)
+/// Print the report, or write it to `--output`
+fn write_output(
+    path: Option<&str>,
+    format: OutputFormat,
+    rendered: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match path {
+        Some(p) => {
+            fs::write(p, rendered)?;
+            println!("Report written to {}", p);
+        }
+        None if format == OutputFormat::Markdown => println!("\n{}", rendered),
+        None => println!("{}", rendered),
+    }
+    Ok(())
 }
 
-/// Create the final LLM request
+/// Create the final LLM request; `known` findings are listed so the model skips them
 fn build_final_report_request(
     review_texts: &[String],
+    known: &[ValidationResult],
 ) -> crate::services::ChatCompletionRequest {
+    let known_section = if known.is_empty() {
+        String::new()
+    } else {
+        let lines: Vec<_> = known.iter().map(|f| format!("- {}", f.finding)).collect();
+        format!(
+            "These issues are already reported by static checks; do not include them again:\n{}\n\n",
+            lines.join("\n")
+        )
+    };
     crate::services::ChatCompletionRequest {
-        model: std::env::var("AZURE_OPENAI_DEPLOYMENT").unwrap_or_default(),
+        model: crate::llm::configured_model(),
         messages: vec![crate::services::ChatMessage {
             role: "user".to_string(),
             content: format!(
@@ -232,8 +602,12 @@ fn build_final_report_request(
                  Remove trivial suggestions.\n\
                  Return exactly:\n\
                  {{\n  \"findings\": [\n    {{\n\
-                 \"category\": \"...\",\n\"finding\": \"...\",\n\"severity\": 1-5,\n\"impact\": \"...\"\n    }}\n  ]\n}}\n\n\
-                 reviews:\n{}",
+                 \"category\": \"...\",\n\"finding\": \"...\",\n\"severity\": 1-5,\n\"impact\": \"...\",\n\
+                 \"location\": {{ \"start_line\": 1, \"end_line\": 1 }}\n    }}\n  ]\n}}\n\n\
+                 Keep the line numbers cited in the reviews; use null for location when none was given. \
+                 Quote symbol names in backticks.\n\n\
+                 {}reviews:\n{}",
+                known_section,
                 review_texts.join("\n\n")
             ),
         }],
@@ -244,224 +618,868 @@ fn build_final_report_request(
     }
 }
 
-/// Filter findings >= minSeverity, sort desc
+/// Filter findings >= minSeverity (per file), group by file, sort severity desc
 fn filter_by_severity<'a>(
     report: &'a FinalReport,
-    min_severity: u8,
+    settings: &Settings,
 ) -> Vec<&'a ValidationResult> {
     let mut out: Vec<_> = report
         .findings
         .iter()
-        .filter(|f| f.severity >= min_severity)
+        .filter(|f| {
+            let min_severity = match f.location.as_ref().and_then(|l| l.file_path.as_deref()) {
+                Some(file) => settings.minimum_severity_for(file),
+                None => settings.minimum_severity,
+            };
+            f.severity >= min_severity
+        })
         .collect();
-    out.sort_by(|a, b| b.severity.cmp(&a.severity));
+    out.sort_by_key(|f| {
+        let file = f.location.as_ref().and_then(|l| l.file_path.clone());
+        (file, std::cmp::Reverse(f.severity))
+    });
     out
 }
 
-/// Build final markdown table
-fn build_markdown(
-    findings: &[&ValidationResult],
-    min_severity: u8,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut out = String::new();
-    out.push_str("# Bicep Code Review Results\n\n");
+/// -------------------------------------------------------
+/// Cache subcommand
+fn run_cache_command(args: CacheArgs) -> Result<i32, Box<dyn std::error::Error>> {
+    let cfg = cache::CacheConfig::from_env();
+    match args.action {
+        CacheAction::Inspect { list } => {
+            let entries = cache::entries(&cfg)?;
+            let total: u64 = entries.iter().map(|e| e.size).sum();
+            let expired = entries.iter().filter(|e| e.age > cfg.ttl).count();
+            println!("Cache directory: {}", cfg.dir.display());
+            println!("Enabled: {}", cfg.enabled);
+            println!(
+                "Entries: {} ({} expired), {:.1} KiB of {} MiB",
+                entries.len(),
+                expired,
+                total as f64 / 1024.0,
+                cfg.max_bytes / (1024 * 1024)
+            );
+            println!("TTL: {} h", cfg.ttl.as_secs() / 3600);
+            if list {
+                for e in &entries {
+                    println!(
+                        "{}  {:>8} B  {:>4}  {}",
+                        e.key,
+                        e.size,
+                        cache::format_age(e.age),
+                        cache::entry_model(e).unwrap_or_default()
+                    );
+                }
+            }
+        }
+        CacheAction::Prune { all } => {
+            let summary = cache::prune(&cfg, all)?;
+            println!(
+                "Removed {} entries, freed {:.1} KiB",
+                summary.removed,
+                summary.freed_bytes as f64 / 1024.0
+            );
+        }
+    }
+    Ok(policy::EXIT_OK)
+}
+
+/// -------------------------------------------------------
+/// ADO PR-based flow
+fn debug_ado_args(args: &AzureDevOpsArgs, settings: &Settings) {
+    if settings.debug {
+        println!("(DEBUG) Azure DevOps Args => org={}, project={}, repo={}, prId={}, pat=***, config={:?}, bestPractices={:?}, minSeverity={}",
+            args.organization, args.project, args.repository, args.pull_request_id, settings.source, settings.best_practices_file, settings.minimum_severity
+        );
+    }
+}
 
-    if findings.is_empty() {
-        out.push_str(&format!(
-            "No issues found with severity {} or higher.\n",
-            min_severity
-        ));
-        return Ok(out);
+/// The main function for PR-based analysis; returns the reported findings of all files
+async fn run_pr_review(
+    args: AzureDevOpsArgs,
+    settings: &Settings,
+    policy: &FailPolicy,
+) -> Result<Vec<ValidationResult>, Box<dyn std::error::Error>> {
+    // 1) Resolve repo GUID and the PR's source branch
+    let ado = azure_devops::AzureDevOpsClient::new(&args);
+    let pr_id = args.pull_request_id;
+    let repo_id = ado.repository_id(&args.repository).await?;
+    if settings.debug {
+        println!("(DEBUG) Found repo GUID: {repo_id}");
     }
+    let pr = ado.pull_request(&repo_id, pr_id).await?;
+    let branch = pr.source_ref_name.trim_start_matches("refs/heads/");
 
-    out.push_str(&format!(
-        "Found {} issues with severity {} or higher.\n\n",
-        findings.len(),
-        min_severity
-    ));
+    // 2) Find changed .bicep files
+    let files = ado.modified_bicep_files(&repo_id, pr_id).await?;
+
+    if files.is_empty() && settings.debug {
+        println!("(DEBUG) No changed Bicep files in PR #{}, nothing to do", args.pull_request_id);
+    }
 
-    let mut table = Table::new();
-    table.add_row(row![b => "Category", "Finding", "Severity", "Impact"]);
+    // 3) Load best practices and the baseline
+    let catalog = load_best_practices(settings.best_practices_file.as_deref())?;
+    let known = load_baseline(settings)?;
+
+    // 4) Review changed files concurrently; join_all keeps the PR's file order
+    let limit = Semaphore::new(settings.concurrency);
+    let reports = join_all(files.iter().map(|f| {
+        review_pr_file(&ado, settings, &repo_id, branch, &catalog, known.as_ref(), f, &limit)
+    }))
+    .await;
+
+    // 5) One thread per finding on its lines, keyed by the finding's fingerprint
+    let mut reported = Vec::new();
+    let mut desired = Vec::new();
+    let mut seen = HashMap::new();
+    for (f, review) in files.iter().zip(reports) {
+        let (review, content) = review?;
+        for (key, finding) in keyed_findings(settings, &f.path, &review, &mut seen) {
+            desired.push(threads::DesiredThread {
+                content: threads::with_marker(&format_pr_comment(&finding), &key),
+                key,
+                thread_context: Some(ThreadContext::for_location(&f.path, finding.location.as_ref(), &content)),
+                status: ThreadStatus::Active,
+            });
+            reported.push(finding);
+        }
+    }
 
-    for f in findings {
-        let label = match f.severity {
-            5 => "5 (Critical) ⚠️",
-            4 => "4 (Serious)",
-            3 => "3 (Important)",
-            2 => "2 (Minor)",
-            _ => "1 (Suggestion)",
+    // 6) Create, update, reopen or resolve threads to match this run
+    let existing = ado.threads(&repo_id, pr_id).await?;
+    let actions = threads::plan(&existing, &desired);
+    let counts = apply_thread_actions(&ado, &repo_id, pr_id, &desired, actions).await?;
+    println!("PR threads: {}", counts);
+
+    // 7) Summary thread and PR status; the summary stays open while the policy fails
+    let violations = policy.violations(&reported.iter().collect::<Vec<_>>());
+    let summary = threads::DesiredThread {
+        key: threads::SUMMARY_KEY.to_string(),
+        thread_context: None,
+        content: threads::with_marker(
+            &format_pr_summary(
+                &files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+                &reported,
+                &violations,
+            ),
+            threads::SUMMARY_KEY,
+        ),
+        status: if violations.is_empty() {
+            ThreadStatus::Closed
+        } else {
+            ThreadStatus::Active
+        },
+    };
+    let actions = threads::plan_single(&existing, &summary);
+    apply_thread_actions(&ado, &repo_id, pr_id, std::slice::from_ref(&summary), actions).await?;
+    ado.create_pull_request_status(&repo_id, pr_id, &review_status(&reported, &violations))
+        .await?;
+
+    Ok(reported)
+}
+
+/// -------------------------------------------------------
+/// GitHub mode: review comments on the changed lines and a check run
+async fn run_github_review(
+    args: GitHubArgs,
+    settings: &Settings,
+    policy: &FailPolicy,
+) -> Result<Vec<ValidationResult>, Box<dyn std::error::Error>> {
+    // 1) Pull request and its changed .bicep files
+    let gh = github::GitHubClient::new(&args)?;
+    let pr = gh.pull_request(args.pull_request).await?;
+    let files: Vec<_> = gh
+        .changed_files(args.pull_request)
+        .await?
+        .into_iter()
+        .filter(|f| f.filename.ends_with(".bicep") && f.status != "removed")
+        .collect();
+    if settings.debug {
+        println!(
+            "(DEBUG) {} changed Bicep files in PR #{} ({}..{})",
+            files.len(),
+            args.pull_request,
+            pr.base.sha,
+            pr.head.sha
+        );
+    }
+
+    // 2) Load best practices and the baseline
+    let catalog = load_best_practices(settings.best_practices_file.as_deref())?;
+    let known = load_baseline(settings)?;
+
+    // 3) Review the head version of each file, focused on the diff to the base
+    let limit = Semaphore::new(settings.concurrency);
+    let reports = join_all(files.iter().map(|f| async {
+        let (content, base) = {
+            let _permit = limit.acquire().await?;
+            let content = gh
+                .file_content(&f.filename, &pr.head.sha)
+                .await?
+                .ok_or_else(|| format!("{} not found at {}", f.filename, pr.head.sha))?;
+            let base = match (settings.full_review, f.status.as_str()) {
+                (true, _) | (_, "added") => Ok(None),
+                _ => {
+                    let base_path = f.previous_filename.as_deref().unwrap_or(&f.filename);
+                    gh.file_content(base_path, &pr.base.sha).await
+                }
+            };
+            (content, base)
         };
-        let wrapped_find = textwrap::fill(&f.finding, TABLE_WRAP_WIDTH);
-        let wrapped_imp = textwrap::fill(&f.impact, TABLE_WRAP_WIDTH);
+        review_head_version(settings, &catalog, known.as_ref(), &f.filename, &content, base, &limit).await
+    }))
+    .await;
 
-        table.add_row(Row::new(vec![
-            Cell::new(&f.category),
-            Cell::new(&wrapped_find),
-            Cell::new(label),
-            Cell::new(&wrapped_imp),
-        ]));
+    // 4) Review comments on lines GitHub can anchor to; annotations for everything.
+    //    Findings outside the diff are listed in the review body, so earlier
+    //    review bodies count as posted too.
+    let mut already_posted: HashSet<String> = gh
+        .review_comments(args.pull_request)
+        .await?
+        .iter()
+        .filter_map(|c| threads::marker_key(&c.body).map(str::to_string))
+        .collect();
+    for review in gh.reviews(args.pull_request).await? {
+        let body = review.body.unwrap_or_default();
+        already_posted.extend(threads::marker_keys(&body).into_iter().map(str::to_string));
     }
+    let mut reported = Vec::new();
+    let mut comments = Vec::new();
+    let mut outside_diff = Vec::new();
+    let mut annotations = Vec::new();
+    let mut seen = HashMap::new();
+    for (f, review) in files.iter().zip(reports) {
+        let review = review?;
+        let commentable = f.patch.as_deref().map(github::commentable_lines).unwrap_or_default();
+        for (key, finding) in keyed_findings(settings, &f.filename, &review, &mut seen) {
+            let lines = finding
+                .location
+                .as_ref()
+                .filter(|l| !l.is_file_level())
+                .map(|l| (l.start_line, l.end_line.max(l.start_line)));
 
-    let table_str = table
-        .to_string()
-        .lines()
-        .map(|line| format!("    {}", line))
-        .collect::<Vec<_>>()
-        .join("\n");
+            annotations.push(github::Annotation {
+                path: f.filename.clone(),
+                start_line: lines.map_or(1, |l| l.0),
+                end_line: lines.map_or(1, |l| l.1),
+                annotation_level: annotation_level(finding.severity, policy).to_string(),
+                title: format!("{} (severity {})", finding.category, finding.severity),
+                message: format!("{}\n\nImpact: {}", finding.finding, finding.impact),
+            });
 
-    out.push_str("```\n");
-    out.push_str(&table_str);
-    out.push_str("\n```\n");
+            if !already_posted.contains(&key) {
+                let anchor = lines.and_then(|l| github::anchor(l, &commentable));
+                match anchor {
+                    Some((start_line, line)) => comments.push(github::NewReviewComment {
+                        path: f.filename.clone(),
+                        line,
+                        start_line,
+                        side: "RIGHT".to_string(),
+                        body: threads::with_marker(&format_pr_comment(&finding), &key),
+                    }),
+                    None => outside_diff.push(format!(
+                        "- `{}` {}: {} {}",
+                        f.filename,
+                        finding.location.as_ref().map_or("file".to_string(), |l| l.line_label()),
+                        finding.finding,
+                        threads::marker(&key)
+                    )),
+                }
+            }
+            reported.push(finding);
+        }
+    }
 
-    if findings.iter().any(|f| f.severity == 5) {
-        out.push_str("\n⚠️ **CRITICAL ISSUES FOUND**\n");
+    if !comments.is_empty() || !outside_diff.is_empty() {
+        let mut body = format!("Bicep review: {} new finding(s).", comments.len() + outside_diff.len());
+        if !outside_diff.is_empty() {
+            body.push_str("\n\nOutside the diff:\n");
+            body.push_str(&outside_diff.join("\n"));
+        }
+        let new_comments = comments.len();
+        gh.create_review(
+            args.pull_request,
+            &github::NewReview {
+                commit_id: pr.head.sha.clone(),
+                body,
+                event: "COMMENT".to_string(),
+                comments,
+            },
+        )
+        .await?;
+        println!("Posted a review with {} line comment(s)", new_comments);
+    } else {
+        println!("No new findings to comment on");
     }
 
-    Ok(out)
+    // 5) Check run with the policy verdict and all findings as annotations
+    let violations = policy.violations(&reported.iter().collect::<Vec<_>>());
+    let paths: Vec<_> = files.iter().map(|f| f.filename.as_str()).collect();
+    gh.create_check_run(github::CheckRun {
+        name: GITHUB_CHECK_NAME.to_string(),
+        head_sha: pr.head.sha.clone(),
+        status: "completed".to_string(),
+        conclusion: if violations.is_empty() { "success" } else { "failure" }.to_string(),
+        output: github::CheckOutput {
+            title: review_status(&reported, &violations).description,
+            summary: format_pr_summary(&paths, &reported, &violations),
+            annotations,
+        },
+    })
+    .await?;
+
+    Ok(reported)
 }
 
-/// -------------------------------------------------------
-/// ADO PR-based flow
+/// Check run annotation level: findings that fail the policy are failures
+fn annotation_level(severity: u8, policy: &FailPolicy) -> &'static str {
+    if policy.fail_on_severity > 0 && severity >= policy.fail_on_severity {
+        "failure"
+    } else if severity >= 3 {
+        "warning"
+    } else {
+        "notice"
+    }
+}
 
-fn debug_ado_args(args: &AzureDevOpsArgs) {
-    if args.debug {
-        println!("(DEBUG) Azure DevOps Args => org={}, project={}, repo={}, prId={}, pat=***, bestPractices={}, minSeverity={}, debug={}",
-            args.organization, args.project, args.repository, args.pull_request_id, args.best_practices_file, args.minimum_severity, args.debug
+/// -------------------------------------------------------
+/// GitLab mode: discussions on the changed lines, a summary note and a
+/// commit status
+async fn run_gitlab_review(
+    args: GitLabArgs,
+    settings: &Settings,
+    policy: &FailPolicy,
+) -> Result<Vec<ValidationResult>, Box<dyn std::error::Error>> {
+    // 1) Merge request and its changed .bicep files
+    let gl = gitlab::GitLabClient::new(&args);
+    let refs = gl.merge_request().await?.diff_refs;
+    let files: Vec<_> = gl
+        .diffs()
+        .await?
+        .into_iter()
+        .filter(|f| f.new_path.ends_with(".bicep") && !f.deleted_file)
+        .collect();
+    if settings.debug {
+        println!(
+            "(DEBUG) {} changed Bicep files in MR !{} ({}..{})",
+            files.len(),
+            args.merge_request_iid,
+            refs.base_sha,
+            refs.head_sha
         );
     }
-}
 
-/// The main function for PR-based analysis
-async fn run_pr_review(args: AzureDevOpsArgs) -> Result<(), Box<dyn std::error::Error>> {
-    // 1) Resolve repo GUID
-    let repo_id = get_repository_id(
-        &args.organization,
-        &args.project,
-        &args.repository,
-        &args.pat,
-    )
-    .await?;
-    if args.debug {
-        println!("(DEBUG) Found repo GUID: {repo_id}");
+    // 2) Load best practices and the baseline
+    let catalog = load_best_practices(settings.best_practices_file.as_deref())?;
+    let known = load_baseline(settings)?;
+
+    // 3) Review the head version of each file, focused on the diff to the base
+    let limit = Semaphore::new(settings.concurrency);
+    let reports = join_all(files.iter().map(|f| async {
+        let (content, base) = {
+            let _permit = limit.acquire().await?;
+            let content = gl
+                .file_content(&f.new_path, &refs.head_sha)
+                .await?
+                .ok_or_else(|| format!("{} not found at {}", f.new_path, refs.head_sha))?;
+            let base = if settings.full_review || f.new_file {
+                Ok(None)
+            } else {
+                gl.file_content(&f.old_path, &refs.base_sha).await
+            };
+            (content, base)
+        };
+        review_head_version(settings, &catalog, known.as_ref(), &f.new_path, &content, base, &limit).await
+    }))
+    .await;
+
+    // 4) One discussion per finding not posted before, on its diff line if it
+    //    has one. A finding that comes back reopens the discussion the tool
+    //    resolved; discussions a reviewer resolved stay resolved.
+    let discussions = gl.discussions().await?;
+    let notes: Vec<_> = discussions.iter().flat_map(|d| &d.notes).collect();
+    let already_posted: HashSet<&str> = notes.iter().filter_map(|n| threads::marker_key(&n.body)).collect();
+    let mut owned: HashMap<&str, &gitlab::Discussion> = HashMap::new();
+    for d in &discussions {
+        if let Some(key) = d.notes.first().and_then(|n| threads::marker_key(&n.body)) {
+            owned.entry(key).or_insert(d);
+        }
+    }
+    let mut reported = Vec::new();
+    let mut reported_keys = HashSet::new();
+    let (mut posted, mut reopened) = (0, 0);
+    let mut seen = HashMap::new();
+    for (f, review) in files.iter().zip(reports) {
+        let review = review?;
+        let diff_lines = gitlab::diff_lines(&f.diff);
+        for (key, finding) in keyed_findings(settings, &f.new_path, &review, &mut seen) {
+            if let Some(discussion) = owned.get(key.as_str()) {
+                if discussion.notes[0].resolved_by_author() {
+                    gl.set_discussion_resolved(&discussion.id, false).await?;
+                    reopened += 1;
+                }
+            } else if !already_posted.contains(key.as_str()) {
+                let loc = finding.location.as_ref().filter(|l| !l.is_file_level());
+                let position = loc
+                    .and_then(|l| {
+                        [l.start_line, l.end_line]
+                            .into_iter()
+                            .find_map(|line| diff_lines.get(&line).map(|old| (line, *old)))
+                    })
+                    .map(|(new_line, old_line)| gitlab::Position {
+                        position_type: "text".to_string(),
+                        refs: refs.clone(),
+                        old_path: f.old_path.clone(),
+                        new_path: f.new_path.clone(),
+                        new_line,
+                        old_line,
+                    });
+                let body = match position {
+                    Some(_) => format_pr_comment(&finding),
+                    None => format!(
+                        "`{}` {}\n\n{}",
+                        f.new_path,
+                        loc.map_or("file".to_string(), |l| l.line_label()),
+                        format_pr_comment(&finding)
+                    ),
+                };
+                gl.create_discussion(&gitlab::NewDiscussion {
+                    body: threads::with_marker(&body, &key),
+                    position,
+                })
+                .await?;
+                posted += 1;
+            }
+            reported_keys.insert(key);
+            reported.push(finding);
+        }
     }
+    println!("Posted {} new MR discussion(s), reopened {}", posted, reopened);
 
-    // 2) Find changed .bicep files
-    let files = list_modified_bicep_files(
-        &args.organization,
-        &args.project,
-        &repo_id,
-        args.pull_request_id,
-        &args.pat,
-        args.debug,
+    // 5) Resolve the tool's discussions whose finding is gone. Resolved ones
+    //    stay resolved, since a reviewer may have resolved them on purpose.
+    let mut resolved = 0;
+    for discussion in &discussions {
+        let first = match discussion.notes.first() {
+            Some(note) if note.resolvable && !note.resolved => note,
+            _ => continue,
+        };
+        match threads::marker_key(&first.body) {
+            Some(key) if threads::is_finding_key(key) && !reported_keys.contains(key) => {
+                gl.set_discussion_resolved(&discussion.id, true).await?;
+                resolved += 1;
+            }
+            _ => {}
+        }
+    }
+    if resolved > 0 {
+        println!("Resolved {} MR discussion(s) whose finding is gone", resolved);
+    }
+
+    // 6) Summary note, edited in place, and the commit status of the head
+    let violations = policy.violations(&reported.iter().collect::<Vec<_>>());
+    let paths: Vec<_> = files.iter().map(|f| f.new_path.as_str()).collect();
+    let summary = threads::with_marker(
+        &format_pr_summary(&paths, &reported, &violations),
+        threads::SUMMARY_KEY,
+    );
+    match notes
+        .iter()
+        .find(|n| threads::marker_key(&n.body) == Some(threads::SUMMARY_KEY))
+    {
+        Some(note) if note.body == summary => {}
+        Some(note) => gl.update_note(note.id, &summary).await?,
+        None => gl.create_note(&summary).await?,
+    }
+    gl.set_commit_status(
+        &refs.head_sha,
+        gitlab::CommitStatus {
+            state: if violations.is_empty() { "success" } else { "failed" }.to_string(),
+            name: GITLAB_STATUS_NAME.to_string(),
+            description: review_status(&reported, &violations).description,
+        },
     )
     .await?;
 
-    if files.is_empty() && args.debug {
-        println!("(DEBUG) No changed Bicep files in PR #{}, nothing to do", args.pull_request_id);
+    Ok(reported)
+}
+
+/// Run planned thread changes against the PR
+async fn apply_thread_actions(
+    ado: &azure_devops::AzureDevOpsClient,
+    repo_id: &str,
+    pr_id: i32,
+    desired: &[threads::DesiredThread],
+    actions: Vec<threads::ThreadAction>,
+) -> Result<threads::SyncCounts, Box<dyn std::error::Error>> {
+    let mut counts = threads::SyncCounts::default();
+    for action in actions {
+        match &action {
+            threads::ThreadAction::Create(i) => {
+                ado.create_thread(repo_id, pr_id, &desired[*i].to_thread()).await?;
+            }
+            threads::ThreadAction::UpdateComment {
+                thread_id,
+                comment_id,
+                desired: i,
+            } => {
+                ado.update_comment(repo_id, pr_id, *thread_id, *comment_id, &desired[*i].content)
+                    .await?;
+            }
+            threads::ThreadAction::SetStatus { thread_id, status } => {
+                ado.update_thread_status(repo_id, pr_id, *thread_id, *status).await?;
+            }
+            threads::ThreadAction::Keep(_) => {}
+        }
+        counts.record(&action);
     }
+    Ok(counts)
+}
 
-    // 3) Load best practices
-    let best_md = fs::read_to_string(&args.best_practices_file)?;
+/// PR status for branch policies: failed when the findings violate the policy
+fn review_status(findings: &[ValidationResult], violations: &[String]) -> PullRequestStatus {
+    let (state, description) = if violations.is_empty() {
+        (
+            "succeeded",
+            format!("Bicep review passed with {} finding(s)", findings.len()),
+        )
+    } else {
+        ("failed", format!("Bicep review failed: {}", violations.join("; ")))
+    };
+    PullRequestStatus {
+        state: state.to_string(),
+        description,
+        context: StatusContext {
+            genre: PR_STATUS_GENRE.to_string(),
+            name: PR_STATUS_NAME.to_string(),
+        },
+    }
+}
 
-    // 4) For each changed Bicep file, get content + analyze
-    for f in files {
-        if args.debug {
-            println!("(DEBUG) Reviewing file: {}", f.path);
+/// Summary comment: policy result, findings by severity and by file
+fn format_pr_summary(paths: &[&str], findings: &[ValidationResult], violations: &[String]) -> String {
+    let mut out = String::from("## Bicep Review Summary\n\n");
+    if violations.is_empty() {
+        out.push_str("✅ **Passed** the review policy.\n");
+    } else {
+        for v in violations {
+            out.push_str(&format!("❌ **Failed:** {}\n", v));
         }
+    }
+    out.push_str(&format!(
+        "\nReviewed {} changed Bicep file(s), {} finding(s) reported.\n",
+        paths.len(),
+        findings.len()
+    ));
+    if findings.is_empty() {
+        return out;
+    }
 
-        let content = get_file_content(
-            &args.organization,
-            &args.project,
-            &repo_id,
-            args.pull_request_id,
-            &f.path,
-            &f.object_id,
-            &args.pat,
-        )
-        .await?;
+    out.push_str("\n| Severity | Findings |\n|---|---|\n");
+    for severity in (1..=5).rev() {
+        let count = findings.iter().filter(|f| f.severity == severity).count();
+        if count > 0 {
+            out.push_str(&format!(
+                "| {} {} | {} |\n",
+                severity_emoji(severity),
+                report::severity_label(severity),
+                count
+            ));
+        }
+    }
+
+    out.push_str("\n| File | Findings | Highest severity |\n|---|---|---|\n");
+    for path in paths {
+        let in_file: Vec<_> = findings
+            .iter()
+            .filter(|x| x.location.as_ref().and_then(|l| l.file_path.as_deref()) == Some(*path))
+            .collect();
+        let highest = in_file.iter().map(|x| x.severity).max();
+        out.push_str(&format!(
+            "| `{}` | {} | {} |\n",
+            path,
+            in_file.len(),
+            highest.map_or("-".to_string(), |s| s.to_string())
+        ));
+    }
+    out
+}
+
+/// Fetch one changed file and review it; the semaphore bounds remote calls.
+/// The parsed program is returned for fingerprinting the findings, and the
+/// content for anchoring their threads.
+#[allow(clippy::too_many_arguments)]
+async fn review_pr_file(
+    ado: &azure_devops::AzureDevOpsClient,
+    settings: &Settings,
+    repo_id: &str,
+    branch: &str,
+    catalog: &RuleCatalog,
+    known: Option<&baseline::Baseline>,
+    f: &PullRequestFile,
+    limit: &Semaphore,
+) -> Result<(FileReview, String), Box<dyn std::error::Error>> {
+    if settings.debug {
+        println!("(DEBUG) Reviewing file: {}", f.path);
+    }
 
-        if args.debug {
-            println!("(DEBUG) Retrieved {} bytes of content for {}", content.len(), f.path);
+    let content = {
+        let _permit = limit.acquire().await?;
+        ado.item_content(repo_id, &f.path, branch).await?
+    };
+
+    if settings.debug {
+        println!("(DEBUG) Retrieved {} bytes of content for {}", content.len(), f.path);
+    }
+
+    // Focus on the lines this PR changed unless a full review was asked for
+    let diff = if settings.full_review {
+        None
+    } else {
+        pr_file_diff(ado, settings, repo_id, f, &content, limit).await
+    };
+    let review = review_changed_file(settings, catalog, known, &f.path, &content, diff, limit).await?;
+    Ok((review, content))
+}
+
+/// A reviewed file: the findings to report, the parsed program for
+/// fingerprinting them, and what inline suppressions and the baseline dropped
+struct FileReview {
+    report: FinalReport,
+    program: Option<ast::Program>,
+    suppressed: Vec<ValidationResult>,
+    baselined: usize,
+    /// Time spent in the built-in rules and rule pack matchers
+    rules_ms: u64,
+}
+
+/// Review a file, locally or in a PR: built-in rules and the LLM review, then
+/// inline suppressions, the diff focus and the baseline. Progress goes to
+/// stderr so reports on stdout stay machine-readable.
+async fn review_changed_file(
+    settings: &Settings,
+    catalog: &RuleCatalog,
+    known: Option<&baseline::Baseline>,
+    path: &str,
+    content: &str,
+    diff: Option<FileDiff>,
+    limit: &Semaphore,
+) -> Result<FileReview, Box<dyn std::error::Error>> {
+    match &diff {
+        Some(d) if d.is_unchanged() => {
+            eprintln!("No content changes in {}, skipping review", path);
+            return Ok(FileReview {
+                report: FinalReport {
+                    findings: Vec::new(),
+                    metadata: None,
+                },
+                program: None,
+                suppressed: Vec::new(),
+                baselined: 0,
+                rules_ms: 0,
+            });
+        }
+        Some(d) if settings.debug => {
+            println!("(DEBUG) Changed lines in {}: {}", path, d.describe());
         }
+        _ => {}
+    }
 
-        let response_content = if args.simple {
-            // Simple mode: direct analysis
-            if args.debug {
-                println!("(DEBUG) Using simple mode analysis");
-            }
-            services::validate_simple(&content, &best_md).await?
-        } else {
-            // Category-based analysis
-            let mut cat_reviews = Vec::new();
-            for cat in DEFAULT_CATEGORIES.iter() {
-                println!("Validating file {} against category {}", f.path, cat);
-                let rev = analyze_category(&content, &best_md, cat, args.debug).await?;
-                if args.debug {
+    let rules_started = Instant::now();
+    let program = parse_bicep(path, content);
+    let rule_findings = static_findings(settings, catalog, path, content, program.as_ref());
+    let rules_ms = rules_started.elapsed().as_millis() as u64;
+
+    let response_content = if settings.rules_only {
+        // Rules-only mode: no LLM findings
+        r#"{"findings": []}"#.to_string()
+    } else if settings.simple {
+        // Simple mode: direct analysis
+        if settings.debug {
+            println!("(DEBUG) Using simple mode analysis");
+        }
+        let _permit = limit.acquire().await?;
+        services::validate_simple(content, &catalog.document, diff.as_ref()).await?
+    } else {
+        // Category-based analysis, categories run concurrently
+        let categories = settings.categories_for(path, catalog);
+        let cat_reviews = join_all(categories.iter().map(|cat| {
+            let diff = diff.as_ref();
+            async move {
+                let _permit = limit.acquire().await?;
+                eprintln!("Validating file {} against category {}", path, cat);
+                let rev = analyze_category(content, catalog, cat, diff, settings.debug).await?;
+                if settings.debug {
                     println!("(DEBUG) Category {} review:\n{}", cat, rev);
                 }
-                cat_reviews.push(rev);
+                Ok::<_, Box<dyn std::error::Error>>(rev)
             }
+        }))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
 
-            let request = build_final_report_request(&cat_reviews);
-            if args.debug {
-                println!("(DEBUG) OpenAI request:\n{}", serde_json::to_string_pretty(&request)?);
-            }
+        let request = build_final_report_request(&cat_reviews, &rule_findings);
+        if settings.debug {
+            println!("(DEBUG) OpenAI request:\n{}", serde_json::to_string_pretty(&request)?);
+        }
 
-            let response = call_azure_openai(&request).await?;
-            response.choices[0].message.content.clone()
-        };
+        let _permit = limit.acquire().await?;
+        let response = call_llm(&request).await?;
+        response.choices[0].message.content.clone()
+    };
 
-        if args.debug {
-            println!("(DEBUG) Final response content:\n{}", response_content);
+    if settings.debug {
+        println!("(DEBUG) Final response content:\n{}", response_content);
+    }
+
+    // Parse results
+    let mut report = match serde_json::from_str::<FinalReport>(&response_content) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Failed to parse JSON response: {}", e);
+            eprintln!("Response content was:\n{}", response_content);
+            return Err("JSON parsing failed".into());
         }
+    };
+    report.findings.extend(rule_findings);
+    locate::resolve_locations(&mut report.findings, path, content, program.as_ref());
 
-        // Parse results and create comment
-        let report = match serde_json::from_str::<FinalReport>(&response_content) {
-            Ok(r) => r,
-            Err(e) => {
-                eprintln!("Failed to parse JSON response: {}", e);
-                eprintln!("Response content was:\n{}", response_content);
-                return Err("JSON parsing failed".into());
+    let (kept, suppressed) = suppress::partition(report.findings, content, program.as_ref());
+    if !suppressed.is_empty() {
+        eprintln!("Suppressed {} findings in {} by inline comments", suppressed.len(), path);
+    }
+    let kept = match &diff {
+        Some(diff) => {
+            let (touched, untouched) = diff.partition(kept);
+            if !untouched.is_empty() {
+                eprintln!("Skipped {} findings outside the changed lines of {}", untouched.len(), path);
             }
-        };
+            touched
+        }
+        None => kept,
+    };
+    let mut baselined = 0;
+    report.findings = match known {
+        Some(known) => {
+            let (new, old) = known.partition(kept, &settings.project_path(path), program.as_ref());
+            if !old.is_empty() {
+                eprintln!("Skipped {} baseline findings in {}", old.len(), path);
+            }
+            baselined = old.len();
+            new
+        }
+        None => kept,
+    };
+    Ok(FileReview {
+        report,
+        program,
+        suppressed,
+        baselined,
+        rules_ms,
+    })
+}
 
-        let findings = filter_by_severity(&report, args.minimum_severity);
-        if !findings.is_empty() {
-            let comment = format_pr_comment(&f.path, &findings);
-            create_review_thread(
-                &args.organization,
-                &args.project,
-                args.pull_request_id,
-                &repo_id,
-                &f.path,
-                &comment,
-                &args.pat,
-            )
-            .await?;
+/// Findings of a reviewed PR file that reach the minimum severity, each with
+/// the marker key its comment carries
+fn keyed_findings(
+    settings: &Settings,
+    path: &str,
+    review: &FileReview,
+    seen: &mut HashMap<String, usize>,
+) -> Vec<(String, ValidationResult)> {
+    let project_path = settings.project_path(path);
+    filter_by_severity(&review.report, settings)
+        .into_iter()
+        .map(|finding| {
+            let fingerprint = baseline::entry(finding, &project_path, review.program.as_ref()).fingerprint;
+            (threads::finding_key(&fingerprint, seen), finding.clone())
+        })
+        .collect()
+}
+
+/// Review the head version of a file changed on a hosting service, focused
+/// on the diff to `base` (`Ok(None)` for a new file). When the base version
+/// could not be fetched the whole file is reviewed.
+async fn review_head_version(
+    settings: &Settings,
+    catalog: &RuleCatalog,
+    known: Option<&baseline::Baseline>,
+    path: &str,
+    content: &str,
+    base: Result<Option<String>, Box<dyn std::error::Error>>,
+    limit: &Semaphore,
+) -> Result<FileReview, Box<dyn std::error::Error>> {
+    let diff = match base {
+        Ok(base) => head_diff(settings, base.as_deref(), content),
+        Err(e) => {
+            eprintln!("Cannot fetch the base version of {}, reviewing the whole file: {}", path, e);
+            None
         }
+    };
+    review_changed_file(settings, catalog, known, path, content, diff, limit).await
+}
+
+/// Diff that focuses the review of a head version; `base` is `None` for a
+/// new file. `None` with --full-review, to review the whole file.
+fn head_diff(settings: &Settings, base: Option<&str>, head: &str) -> Option<FileDiff> {
+    if settings.full_review {
+        return None;
     }
+    Some(match base {
+        Some(base) => FileDiff::between(base, head, settings.diff_context),
+        None => FileDiff::added(settings.diff_context),
+    })
+}
 
-    Ok(())
+/// Diff of a PR file against its base version. New files count as entirely
+/// changed; when the base cannot be fetched the whole file is reviewed.
+async fn pr_file_diff(
+    ado: &azure_devops::AzureDevOpsClient,
+    settings: &Settings,
+    repo_id: &str,
+    f: &PullRequestFile,
+    content: &str,
+    limit: &Semaphore,
+) -> Option<FileDiff> {
+    let base_id = match &f.original_object_id {
+        Some(id) if !f.change_type.contains("add") => id,
+        _ => return Some(FileDiff::added(settings.diff_context)),
+    };
+    let _permit = limit.acquire().await.ok()?;
+    match ado.blob_content(repo_id, base_id).await {
+        Ok(base) => Some(FileDiff::between(&base, content, settings.diff_context)),
+        Err(e) => {
+            eprintln!("Cannot fetch the base version of {}, reviewing the whole file: {}", f.path, e);
+            None
+        }
+    }
 }
 
-/// Build an ADO comment for one file
-fn format_pr_comment(file_path: &str, findings: &[&ValidationResult]) -> String {
-    let mut out = format!("## Bicep Review Results for `{}`\n\n", file_path);
-    for f in findings {
-        let sev_emoji = match f.severity {
-            5 => "🚨",
-            4 => "⚠️",
-            3 => "⚡",
-            2 => "ℹ️",
-            _ => "💡",
-        };
-        out.push_str(&format!(
-            "### {emoji} Severity {sev}: {finding}\n**Impact:** {impact}\n\n",
-            emoji = sev_emoji,
-            sev = f.severity,
-            finding = f.finding,
-            impact = f.impact
-        ));
+fn severity_emoji(severity: u8) -> &'static str {
+    match severity {
+        5 => "🚨",
+        4 => "⚠️",
+        3 => "⚡",
+        2 => "ℹ️",
+        _ => "💡",
+    }
+}
+
+/// Build an ADO comment for one finding; the thread is anchored to the
+/// finding's lines, so the code is not quoted
+fn format_pr_comment(f: &ValidationResult) -> String {
+    let mut out = format!(
+        "### {emoji} Severity {sev}: {finding}\n",
+        emoji = severity_emoji(f.severity),
+        sev = f.severity,
+        finding = f.finding,
+    );
+    out.push_str(&format!("**Category:** {}", f.category));
+    if let Some(rule) = &f.rule_id {
+        out.push_str(&format!(" (`{}`)", rule));
     }
+    out.push_str("\n\n");
+    out.push_str(&format!("**Impact:** {}\n", f.impact));
     out
 }
@@ -1,11 +1,17 @@
 // models.rs
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 
 /// Default min severity
 pub const DEFAULT_MIN_SEVERITY: u8 = 3;
 
+/// Default failure threshold: any critical finding fails the run
+pub const DEFAULT_FAIL_ON_SEVERITY: u8 = 5;
+
+/// Default number of reviews running at the same time
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
 /// Default categories
 pub const DEFAULT_CATEGORIES: [&str; 5] = [
     "Parameters",
@@ -15,40 +21,108 @@ pub const DEFAULT_CATEGORIES: [&str; 5] = [
     "Outputs",
 ];
 
-/// Local usage
-#[derive(Parser, Debug)]
-#[command(author, version, about)]
-pub struct Args {
-    /// Path to local Bicep file
-    #[arg(long)]
-    pub bicep_file: String,
+/// Report format for local analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Markdown report with the findings table (default)
+    #[default]
+    Markdown,
+    /// Plain-text findings table
+    Table,
+    /// Serialized FinalReport with run metadata
+    Json,
+    /// JUnit XML, one test suite per category
+    Junit,
+    /// SARIF 2.1.0 log for code-scanning dashboards
+    Sarif,
+}
 
-    /// Path to best practices MD
-    #[arg(long)]
-    pub best_practices_file: String,
+/// Review settings shared by local analysis and the PR subcommands.
+///
+/// Every field is optional so that unset flags fall through to environment
+/// variables, the `.bicep-reviewer.toml` config file and then the defaults.
+/// Boolean flags accept both `--simple` and `--simple true`.
+#[derive(clap::Args, Debug, Clone, Default)]
+#[command(next_help_heading = "Review Options")]
+pub struct ReviewOptions {
+    /// Config file to use instead of the discovered .bicep-reviewer.toml
+    #[arg(long, global = true)]
+    pub config: Option<String>,
 
-    /// Single optional category
-    #[arg(long)]
-    pub category: Option<String>,
+    /// Best practices file: markdown, or a YAML/TOML/JSON rule pack
+    #[arg(long, global = true)]
+    pub best_practices_file: Option<String>,
 
-    /// Debug
-    #[arg(long)]
-    pub debug: bool,
+    /// Category to review (repeatable); defaults to the rule pack's or the built-in ones
+    #[arg(long, global = true)]
+    pub category: Vec<String>,
 
-    /// Minimum severity
-    #[arg(long, default_value_t = DEFAULT_MIN_SEVERITY)]
-    pub minimum_severity: u8,
+    /// Debug mode
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    pub debug: Option<bool>,
+
+    /// Minimum severity level (1-5)
+    #[arg(long, global = true)]
+    pub minimum_severity: Option<u8>,
 
     /// Simple mode - single prompt without categories
-    #[arg(long)]
-    pub simple: bool,
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    pub simple: Option<bool>,
+
+    /// LLM provider: azure, openai or openai-compatible (overrides LLM_PROVIDER)
+    #[arg(long, global = true)]
+    pub llm_provider: Option<String>,
+
+    /// Only run the built-in rules, without any LLM calls
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    pub rules_only: Option<bool>,
+
+    /// Exit with failure when a finding reaches this severity (0 disables)
+    #[arg(long, global = true)]
+    pub fail_on_severity: Option<u8>,
+
+    /// Exit with failure when more findings than this are reported
+    #[arg(long, global = true)]
+    pub max_findings: Option<usize>,
+
+    /// Maximum number of category and file reviews running at once
+    #[arg(long, global = true)]
+    pub concurrency: Option<usize>,
+
+    /// Store every LLM and search response in this directory
+    #[arg(long, global = true)]
+    pub record: Option<String>,
+
+    /// Answer LLM and search requests from responses recorded in this directory
+    #[arg(long, global = true)]
+    pub replay: Option<String>,
+
+    /// Bypass the on-disk LLM response cache
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Only report findings that are not in this baseline file
+    #[arg(long, global = true)]
+    pub baseline: Option<String>,
+
+    /// Write all current findings to the baseline file and exit
+    #[arg(long, global = true)]
+    pub write_baseline: bool,
+
+    /// Unchanged lines around a change that PR reviews still report on
+    #[arg(long, global = true)]
+    pub diff_context: Option<usize>,
+
+    /// Review whole files in PRs instead of only the changed lines
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    pub full_review: Option<bool>,
 }
 
 /// Azure DevOps usage
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
 pub struct AzureDevOpsArgs {
-    /// Org name or snippet
+    /// Organization name, organization URL, or Azure DevOps Server collection URL
     #[arg(long)]
     pub organization: String,
 
@@ -64,41 +138,194 @@ pub struct AzureDevOpsArgs {
     #[arg(long)]
     pub pat: String,
 
-    /// Best practices MD
+    /// Human-friendly repo name
     #[arg(long)]
-    pub best_practices_file: String,
+    pub repository: String,
+
+    /// REST API version; Azure DevOps Server may need an older one, e.g. 6.0
+    #[arg(long, default_value = "7.1")]
+    pub api_version: String,
+}
 
-    /// Debug
+/// GitHub pull request usage
+#[derive(Parser, Debug)]
+pub struct GitHubArgs {
+    /// Repository as owner/name
+    #[arg(long, env = "GITHUB_REPOSITORY")]
+    pub repository: String,
+
+    /// Pull request number
     #[arg(long)]
-    pub debug: bool,
+    pub pull_request: u64,
 
-    /// Minimum severity
-    #[arg(long, default_value_t = DEFAULT_MIN_SEVERITY)]
-    pub minimum_severity: u8,
+    /// Token allowed to write pull request reviews and checks
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+    pub token: String,
 
-    /// Human-friendly repo name
+    /// REST API base URL, e.g. for GitHub Enterprise Server
+    #[arg(long, env = "GITHUB_API_URL", default_value = "https://api.github.com")]
+    pub api_url: String,
+}
+
+/// GitLab merge request usage
+#[derive(Parser, Debug)]
+pub struct GitLabArgs {
+    /// Project ID or path, e.g. group/name
+    #[arg(long, env = "CI_PROJECT_ID")]
+    pub project: String,
+
+    /// Merge request IID, the number shown in the project
+    #[arg(long, env = "CI_MERGE_REQUEST_IID")]
+    pub merge_request_iid: u64,
+
+    /// Access token with the api scope
+    #[arg(long, env = "GITLAB_TOKEN", hide_env_values = true)]
+    pub token: String,
+
+    /// REST API base URL, e.g. https://gitlab.example.com/api/v4
+    #[arg(long, env = "CI_API_V4_URL", default_value = "https://gitlab.com/api/v4")]
+    pub api_url: String,
+}
+
+/// Local git review usage
+#[derive(Parser, Debug)]
+#[command(group(clap::ArgGroup::new("changes").required(true).args(["git_range", "staged"])))]
+pub struct GitReviewArgs {
+    /// Commit range to review, e.g. main..HEAD, or main...HEAD to diff from the merge base
     #[arg(long)]
-    pub repository: String,
+    pub git_range: Option<String>,
 
-    /// Simple mode - single prompt without categories
+    /// Review the staged changes, e.g. from a pre-commit hook
     #[arg(long)]
-    pub simple: bool,
+    pub staged: bool,
+
+    /// Output format
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write the report to this file instead of stdout
+    #[arg(long)]
+    pub output: Option<String>,
+}
+
+/// Response cache maintenance
+#[derive(Parser, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Show location, size and age of the cached LLM responses
+    Inspect {
+        /// List every entry
+        #[arg(long)]
+        list: bool,
+    },
+    /// Remove expired entries and enforce the size limit
+    Prune {
+        /// Remove every entry
+        #[arg(long)]
+        all: bool,
+    },
 }
 
 /// Validation result
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub category: String,
     pub finding: String,
     #[serde(deserialize_with = "deserialize_severity")]
     pub severity: u8,
     pub impact: String,
+    /// Set for findings produced by the built-in rule engine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    /// Where the finding points in the source, once resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+/// Source location of a finding; lines and columns are 1-based, end column exclusive.
+/// A `start_line` of 0 means the finding applies to the file as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_position")]
+    pub start_line: usize,
+    #[serde(default, deserialize_with = "deserialize_position")]
+    pub start_column: usize,
+    #[serde(default, deserialize_with = "deserialize_position")]
+    pub end_line: usize,
+    #[serde(default, deserialize_with = "deserialize_position")]
+    pub end_column: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+impl Location {
+    /// Whole-file location for findings that cannot be anchored to a line
+    pub fn file_level(file_path: &str) -> Self {
+        Location {
+            file_path: Some(file_path.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_file_level(&self) -> bool {
+        self.start_line == 0
+    }
+
+    /// `L3`, `L3-L7`, or `file` for whole-file findings
+    pub fn line_label(&self) -> String {
+        if self.is_file_level() {
+            "file".to_string()
+        } else if self.end_line > self.start_line {
+            format!("L{}-L{}", self.start_line, self.end_line)
+        } else {
+            format!("L{}", self.start_line)
+        }
+    }
 }
 
 /// Final aggregated JSON
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinalReport {
     pub findings: Vec<ValidationResult>,
+    /// Filled in for machine-readable outputs, never by the LLM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ReportMetadata>,
+}
+
+/// Run metadata attached to machine-readable reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportMetadata {
+    pub tool: String,
+    pub tool_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub generated_at: String,
+    pub minimum_severity: u8,
+    pub files: Vec<String>,
+    /// Findings dropped by inline suppression comments
+    #[serde(default)]
+    pub suppressed: usize,
+    /// Findings hidden because they are in the baseline
+    #[serde(default)]
+    pub baselined: usize,
+    pub timings: Timings,
+}
+
+/// Wall-clock durations in milliseconds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timings {
+    pub total_ms: u64,
+    pub rules_ms: u64,
+    pub llm_ms: u64,
 }
 
 /// Custom deserializer for severity
@@ -120,6 +347,26 @@ where
     }
 }
 
+/// Custom deserializer for line/column numbers the LLM may send as strings or null
+pub fn deserialize_position<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaybeNum {
+        Num(usize),
+        Str(String),
+        Null(()),
+    }
+
+    match MaybeNum::deserialize(deserializer)? {
+        MaybeNum::Num(n) => Ok(n),
+        MaybeNum::Str(s) => Ok(s.trim().parse().unwrap_or(0)),
+        MaybeNum::Null(_) => Ok(0),
+    }
+}
+
 /// Changed file in PR
 #[derive(Debug, Deserialize)]
 pub struct PullRequestFile {
@@ -129,27 +376,174 @@ pub struct PullRequestFile {
     #[serde(rename = "objectId")]
     pub object_id: String,
     #[serde(rename = "originalObjectId")]
-    #[allow(dead_code)]  // Allow dead code as it's part of the API response
     pub original_object_id: Option<String>,
 }
 
 /// Thread creation
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Thread {
     pub comments: Vec<ThreadComment>,
-    pub status: i32,
-    pub thread_context: ThreadContext,
+    pub status: ThreadStatus,
+    /// `None` for PR-level threads such as the review summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_context: Option<ThreadContext>,
+}
+
+/// PR thread status. Sent as its number; read back as a number or as the
+/// name ADO returns (`"active"`, `"fixed"`, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThreadStatus {
+    #[default]
+    Unknown = 0,
+    Active = 1,
+    Fixed = 2,
+    WontFix = 3,
+    Closed = 4,
+    ByDesign = 5,
+    Pending = 6,
+}
+
+impl Serialize for ThreadStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for ThreadStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Num(i32),
+            Name(String),
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Num(1) => ThreadStatus::Active,
+            Raw::Num(2) => ThreadStatus::Fixed,
+            Raw::Num(3) => ThreadStatus::WontFix,
+            Raw::Num(4) => ThreadStatus::Closed,
+            Raw::Num(5) => ThreadStatus::ByDesign,
+            Raw::Num(6) => ThreadStatus::Pending,
+            Raw::Num(_) => ThreadStatus::Unknown,
+            Raw::Name(name) => match name.to_ascii_lowercase().as_str() {
+                "active" => ThreadStatus::Active,
+                "fixed" => ThreadStatus::Fixed,
+                "wontfix" => ThreadStatus::WontFix,
+                "closed" => ThreadStatus::Closed,
+                "bydesign" => ThreadStatus::ByDesign,
+                "pending" => ThreadStatus::Pending,
+                _ => ThreadStatus::Unknown,
+            },
+        })
+    }
+}
+
+/// Thread as listed by the threads API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExistingThread {
+    pub id: i32,
+    #[serde(default)]
+    pub status: ThreadStatus,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub comments: Vec<ExistingComment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExistingComment {
+    pub id: i32,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub is_deleted: bool,
 }
 
 /// Single comment
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ThreadComment {
     pub content: String,
     pub comment_type: i32,
 }
 
-/// Thread context (file path etc.)
-#[derive(Debug, Serialize)]
+/// Thread context: the file, and the line range on the PR's side of the diff.
+/// Without a range the thread is attached to the file as a whole.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ThreadContext {
     pub file_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_file_start: Option<FilePosition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_file_end: Option<FilePosition>,
+}
+
+/// 1-based line and character offset in a file
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FilePosition {
+    pub line: usize,
+    pub offset: usize,
+}
+
+impl ThreadContext {
+    /// Context for a finding in `content`; file-level locations fall back to
+    /// the whole file. Without an end column the range ends with its last line.
+    pub fn for_location(file_path: &str, location: Option<&Location>, content: &str) -> Self {
+        let range = location.filter(|l| !l.is_file_level()).map(|l| {
+            let end_line = l.end_line.max(l.start_line);
+            let end_offset = if l.end_column > 0 {
+                l.end_column
+            } else {
+                content
+                    .lines()
+                    .nth(end_line - 1)
+                    .map_or(0, |line| line.trim_end().chars().count())
+                    + 1
+            };
+            (
+                FilePosition {
+                    line: l.start_line,
+                    offset: l.start_column.max(1),
+                },
+                FilePosition {
+                    line: end_line,
+                    offset: end_offset,
+                },
+            )
+        });
+        ThreadContext {
+            file_path: file_path.to_string(),
+            right_file_start: range.map(|r| r.0),
+            right_file_end: range.map(|r| r.1),
+        }
+    }
+}
+
+/// Genre and name of the PR status; a branch policy requires `bicep-reviewer/review`
+pub const PR_STATUS_GENRE: &str = "bicep-reviewer";
+pub const PR_STATUS_NAME: &str = "review";
+/// Name of the GitHub check run
+pub const GITHUB_CHECK_NAME: &str = "bicep-reviewer";
+/// Name of the GitLab commit status
+pub const GITLAB_STATUS_NAME: &str = "bicep-reviewer";
+
+/// PR status posted for branch policies
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestStatus {
+    /// `succeeded` or `failed`
+    pub state: String,
+    pub description: String,
+    pub context: StatusContext,
+}
+
+/// Identifies the status; branch policies refer to `genre/name`
+#[derive(Debug, Serialize)]
+pub struct StatusContext {
+    pub genre: String,
+    pub name: String,
 }
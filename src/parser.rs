@@ -0,0 +1,1098 @@
+// parser.rs
+
+use crate::ast::*;
+use crate::lexer::{Lexer, StringSegment, Token, TokenKind};
+use std::fmt;
+
+/// Syntax error with the location it was detected at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        ParseError {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.span.start.line, self.span.start.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a whole Bicep file.
+///
+/// Lexing errors abort the parse; statement-level syntax errors are
+/// collected in `Program::errors` and parsing resumes at the next line.
+pub fn parse(source: &str) -> Result<Program, ParseError> {
+    let (tokens, comments) = Lexer::new(source).tokenize()?;
+    let mut parser = Parser {
+        source,
+        tokens,
+        idx: 0,
+    };
+    let (statements, errors) = parser.parse_statements();
+    Ok(Program {
+        statements,
+        comments,
+        errors,
+    })
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    idx: usize,
+}
+
+type PResult<T> = Result<T, ParseError>;
+
+impl<'a> Parser<'a> {
+    // ------------------------------------------------------------
+    // Token helpers
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.idx.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_kind(&self) -> &TokenKind {
+        &self.peek().kind
+    }
+
+    fn peek_nth_kind(&self, n: usize) -> &TokenKind {
+        &self.tokens[(self.idx + n).min(self.tokens.len() - 1)].kind
+    }
+
+    fn at(&self, kind: &TokenKind) -> bool {
+        self.peek_kind() == kind
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek_kind(), TokenKind::Ident(s) if s == keyword)
+    }
+
+    fn bump(&mut self) -> Token {
+        let tok = self.peek().clone();
+        if tok.kind != TokenKind::Eof {
+            self.idx += 1;
+        }
+        tok
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.at(kind) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, what: &str) -> PResult<Token> {
+        if self.at(kind) {
+            Ok(self.bump())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn unexpected(&self, what: &str) -> ParseError {
+        let tok = self.peek();
+        let found = match &tok.kind {
+            TokenKind::Newline => "end of line".to_string(),
+            TokenKind::Eof => "end of file".to_string(),
+            _ => format!("'{}'", tok.span.slice(self.source)),
+        };
+        ParseError::new(format!("expected {}, found {}", what, found), tok.span)
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.at(&TokenKind::Newline) {
+            self.bump();
+        }
+    }
+
+    fn prev_end(&self) -> Position {
+        if self.idx == 0 {
+            return self.tokens[0].span.start;
+        }
+        self.tokens[self.idx - 1].span.end
+    }
+
+    fn expect_ident(&mut self, what: &str) -> PResult<Ident> {
+        match self.peek_kind().clone() {
+            TokenKind::Ident(name) => {
+                let tok = self.bump();
+                Ok(Ident {
+                    name,
+                    span: tok.span,
+                })
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    /// Plain (non-interpolated) string literal such as a resource type
+    fn expect_plain_string(&mut self, what: &str) -> PResult<(String, Span)> {
+        match self.peek_kind().clone() {
+            TokenKind::String(segments) => {
+                let tok = self.bump();
+                let mut out = String::new();
+                for seg in segments {
+                    match seg {
+                        StringSegment::Text(t) => out.push_str(&t),
+                        StringSegment::Interpolation { .. } => {
+                            return Err(ParseError::new(
+                                format!("{} cannot contain interpolation", what),
+                                tok.span,
+                            ))
+                        }
+                    }
+                }
+                Ok((out, tok.span))
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    // ------------------------------------------------------------
+    // Statements
+
+    fn parse_statements(&mut self) -> (Vec<Statement>, Vec<ParseError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.at(&TokenKind::Eof) {
+                break;
+            }
+            match self.parse_statement() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    errors.push(e);
+                    self.recover();
+                }
+            }
+        }
+        (statements, errors)
+    }
+
+    /// Skip to the next newline that is not nested inside brackets
+    fn recover(&mut self) {
+        let mut depth = 0i32;
+        loop {
+            match self.peek_kind() {
+                TokenKind::Eof => return,
+                TokenKind::Newline if depth <= 0 => {
+                    self.bump();
+                    return;
+                }
+                TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => depth += 1,
+                TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn end_of_statement(&mut self) -> PResult<()> {
+        match self.peek_kind() {
+            TokenKind::Newline | TokenKind::Eof => Ok(()),
+            _ => Err(self.unexpected("end of line")),
+        }
+    }
+
+    fn parse_decorators(&mut self) -> PResult<Vec<Decorator>> {
+        let mut decorators = Vec::new();
+        while self.at(&TokenKind::At) {
+            let start = self.bump().span.start;
+            let first = self.expect_ident("decorator name")?;
+            let (namespace, name) = if self.eat(&TokenKind::Dot) {
+                let second = self.expect_ident("decorator name")?;
+                (Some(first.name), second.name)
+            } else {
+                (None, first.name)
+            };
+            let args = if self.at(&TokenKind::LParen) {
+                self.bump();
+                self.parse_call_args()?
+            } else {
+                Vec::new()
+            };
+            decorators.push(Decorator {
+                name,
+                namespace,
+                args,
+                span: Span::new(start, self.prev_end()),
+            });
+            self.skip_newlines();
+        }
+        Ok(decorators)
+    }
+
+    fn parse_statement(&mut self) -> PResult<Statement> {
+        let decorators = self.parse_decorators()?;
+        let start = self.peek().span.start;
+        let keyword = match self.peek_kind() {
+            TokenKind::Ident(k) => k.clone(),
+            _ => return Err(self.unexpected("a declaration")),
+        };
+
+        let stmt = match keyword.as_str() {
+            "param" => {
+                self.bump();
+                let name = self.expect_ident("parameter name")?;
+                let type_expr = self.parse_type()?;
+                let default = if self.eat(&TokenKind::Assign) {
+                    Some(self.parse_expr()?)
+                } else {
+                    None
+                };
+                Statement::Param(ParamDecl {
+                    decorators,
+                    name,
+                    type_expr,
+                    default,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "var" => {
+                self.bump();
+                let name = self.expect_ident("variable name")?;
+                let type_expr = if self.at(&TokenKind::Assign) {
+                    None
+                } else {
+                    Some(self.parse_type()?)
+                };
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_expr()?;
+                Statement::Var(VarDecl {
+                    decorators,
+                    name,
+                    type_expr,
+                    value,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "resource" => Statement::Resource(self.parse_resource(decorators)?),
+            "module" => {
+                self.bump();
+                let name = self.expect_ident("module name")?;
+                let (path, path_span) = self.expect_plain_string("module path")?;
+                self.expect(&TokenKind::Assign, "'='")?;
+                let body = self.parse_expr()?;
+                Statement::Module(ModuleDecl {
+                    decorators,
+                    name,
+                    path,
+                    path_span,
+                    body,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "output" => {
+                self.bump();
+                let name = self.expect_ident("output name")?;
+                let type_expr = self.parse_type()?;
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_expr()?;
+                Statement::Output(OutputDecl {
+                    decorators,
+                    name,
+                    type_expr,
+                    value,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "targetScope" => {
+                self.bump();
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_expr()?;
+                Statement::TargetScope(TargetScopeDecl {
+                    value,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "metadata" => {
+                self.bump();
+                let name = self.expect_ident("metadata name")?;
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_expr()?;
+                Statement::Metadata(MetadataDecl {
+                    name,
+                    value,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "type" => {
+                self.bump();
+                let name = self.expect_ident("type name")?;
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_type()?;
+                Statement::Type(TypeDecl {
+                    decorators,
+                    name,
+                    value,
+                    span: Span::new(start, self.prev_end()),
+                })
+            }
+            "import" | "using" | "extension" | "provider" | "func" => {
+                self.bump();
+                self.skip_balanced_line();
+                let span = Span::new(start, self.prev_end());
+                Statement::Other(OtherDecl {
+                    keyword,
+                    text: span.slice(self.source).to_string(),
+                    span,
+                })
+            }
+            _ => return Err(self.unexpected("a declaration")),
+        };
+
+        self.end_of_statement()?;
+        Ok(stmt)
+    }
+
+    /// Consume tokens up to the end of the line, allowing multi-line brackets
+    fn skip_balanced_line(&mut self) {
+        let mut depth = 0i32;
+        loop {
+            match self.peek_kind() {
+                TokenKind::Eof => return,
+                TokenKind::Newline if depth <= 0 => return,
+                TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => depth += 1,
+                TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn parse_resource(&mut self, decorators: Vec<Decorator>) -> PResult<ResourceDecl> {
+        let start = self.bump().span.start; // `resource`
+        let name = self.expect_ident("resource name")?;
+        let (type_string, type_span) = self.expect_plain_string("resource type")?;
+        let existing = if self.at_keyword("existing") {
+            self.bump();
+            true
+        } else {
+            false
+        };
+        self.expect(&TokenKind::Assign, "'='")?;
+        let body = self.parse_expr()?;
+        Ok(ResourceDecl {
+            decorators,
+            name,
+            type_string,
+            type_span,
+            existing,
+            body,
+            span: Span::new(start, self.prev_end()),
+        })
+    }
+
+    // ------------------------------------------------------------
+    // Types
+
+    fn parse_type(&mut self) -> PResult<TypeExpr> {
+        let first = self.parse_type_postfix()?;
+        if !self.at(&TokenKind::Pipe) {
+            return Ok(first);
+        }
+        let start = first.span().start;
+        let mut members = vec![first];
+        while self.eat(&TokenKind::Pipe) {
+            self.skip_newlines();
+            members.push(self.parse_type_postfix()?);
+        }
+        Ok(TypeExpr::Union(members, Span::new(start, self.prev_end())))
+    }
+
+    fn parse_type_postfix(&mut self) -> PResult<TypeExpr> {
+        let mut ty = self.parse_type_primary()?;
+        loop {
+            let start = ty.span().start;
+            if self.at(&TokenKind::LBracket) && self.peek_nth_kind(1) == &TokenKind::RBracket {
+                self.bump();
+                self.bump();
+                ty = TypeExpr::Array(Box::new(ty), Span::new(start, self.prev_end()));
+            } else if self.at(&TokenKind::Question) {
+                self.bump();
+                ty = TypeExpr::Nullable(Box::new(ty), Span::new(start, self.prev_end()));
+            } else {
+                return Ok(ty);
+            }
+        }
+    }
+
+    fn parse_type_primary(&mut self) -> PResult<TypeExpr> {
+        let start = self.peek().span.start;
+        match self.peek_kind().clone() {
+            TokenKind::Ident(name) if name == "resource" => {
+                self.bump();
+                let (type_string, _) = self.expect_plain_string("resource type")?;
+                Ok(TypeExpr::Resource(
+                    type_string,
+                    Span::new(start, self.prev_end()),
+                ))
+            }
+            TokenKind::Ident(_) => {
+                let mut name = self.expect_ident("type name")?.name;
+                while self.eat(&TokenKind::Dot) {
+                    name.push('.');
+                    name.push_str(&self.expect_ident("type name")?.name);
+                }
+                Ok(TypeExpr::Named(name, Span::new(start, self.prev_end())))
+            }
+            TokenKind::String(_) | TokenKind::Int(_) | TokenKind::Minus => {
+                let expr = self.parse_unary()?;
+                Ok(TypeExpr::Literal(Box::new(expr)))
+            }
+            TokenKind::LBrace => {
+                self.bump();
+                let mut props = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.eat(&TokenKind::RBrace) {
+                        break;
+                    }
+                    self.parse_decorators()?;
+                    let key = self.parse_property_key()?;
+                    self.expect(&TokenKind::Colon, "':'")?;
+                    let ty = self.parse_type()?;
+                    props.push((key, ty));
+                    self.eat(&TokenKind::Comma);
+                }
+                Ok(TypeExpr::Object(props, Span::new(start, self.prev_end())))
+            }
+            TokenKind::LBracket => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.eat(&TokenKind::RBracket) {
+                        break;
+                    }
+                    self.parse_decorators()?;
+                    items.push(self.parse_type()?);
+                    self.eat(&TokenKind::Comma);
+                }
+                Ok(TypeExpr::Tuple(items, Span::new(start, self.prev_end())))
+            }
+            TokenKind::LParen => {
+                self.bump();
+                let ty = self.parse_type()?;
+                self.expect(&TokenKind::RParen, "')'")?;
+                Ok(ty)
+            }
+            _ => Err(self.unexpected("a type")),
+        }
+    }
+
+    fn parse_property_key(&mut self) -> PResult<Ident> {
+        match self.peek_kind() {
+            TokenKind::Ident(_) => self.expect_ident("property name"),
+            TokenKind::String(_) => {
+                let (name, span) = self.expect_plain_string("property name")?;
+                Ok(Ident { name, span })
+            }
+            TokenKind::Star => {
+                let span = self.bump().span;
+                Ok(Ident {
+                    name: "*".to_string(),
+                    span,
+                })
+            }
+            _ => Err(self.unexpected("property name")),
+        }
+    }
+
+    // ------------------------------------------------------------
+    // Expressions
+
+    fn parse_expr(&mut self) -> PResult<Expr> {
+        let condition = self.parse_binary(0)?;
+        if !self.at(&TokenKind::Question) {
+            return Ok(condition);
+        }
+        self.bump();
+        self.skip_newlines();
+        let then = self.parse_expr()?;
+        self.skip_newlines();
+        self.expect(&TokenKind::Colon, "':'")?;
+        self.skip_newlines();
+        let otherwise = self.parse_expr()?;
+        let span = condition.span.to(otherwise.span);
+        Ok(Expr::new(
+            ExprKind::Ternary {
+                condition: Box::new(condition),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+            },
+            span,
+        ))
+    }
+
+    fn binary_op(kind: &TokenKind) -> Option<(BinaryOp, u8)> {
+        let op = match kind {
+            TokenKind::DoubleQuestion => (BinaryOp::Coalesce, 1),
+            TokenKind::Or => (BinaryOp::Or, 2),
+            TokenKind::And => (BinaryOp::And, 3),
+            TokenKind::Equals => (BinaryOp::Equals, 4),
+            TokenKind::NotEquals => (BinaryOp::NotEquals, 4),
+            TokenKind::EqualsInsensitive => (BinaryOp::EqualsInsensitive, 4),
+            TokenKind::NotEqualsInsensitive => (BinaryOp::NotEqualsInsensitive, 4),
+            TokenKind::Less => (BinaryOp::Less, 5),
+            TokenKind::LessOrEqual => (BinaryOp::LessOrEqual, 5),
+            TokenKind::Greater => (BinaryOp::Greater, 5),
+            TokenKind::GreaterOrEqual => (BinaryOp::GreaterOrEqual, 5),
+            TokenKind::Plus => (BinaryOp::Add, 6),
+            TokenKind::Minus => (BinaryOp::Subtract, 6),
+            TokenKind::Star => (BinaryOp::Multiply, 7),
+            TokenKind::Slash => (BinaryOp::Divide, 7),
+            TokenKind::Percent => (BinaryOp::Modulo, 7),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Precedence climbing over left-associative binary operators
+    fn parse_binary(&mut self, min_prec: u8) -> PResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some((op, prec)) = Self::binary_op(self.peek_kind()) {
+            if prec < min_prec {
+                break;
+            }
+            self.bump();
+            self.skip_newlines();
+            let rhs = self.parse_binary(prec + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr::new(
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span,
+            );
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> PResult<Expr> {
+        let op = match self.peek_kind() {
+            TokenKind::Not => UnaryOp::Not,
+            TokenKind::Minus => UnaryOp::Minus,
+            _ => return self.parse_postfix(),
+        };
+        let start = self.bump().span.start;
+        let operand = self.parse_unary()?;
+        let span = Span::new(start, operand.span.end);
+        Ok(Expr::new(
+            ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+            span,
+        ))
+    }
+
+    fn parse_postfix(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            let start = expr.span.start;
+            match self.peek_kind() {
+                TokenKind::Dot | TokenKind::DotQuestion | TokenKind::DoubleColon => {
+                    let tok = self.bump();
+                    let property = self.expect_ident("property name")?;
+                    expr = Expr::new(
+                        ExprKind::Member {
+                            object: Box::new(expr),
+                            safe: tok.kind == TokenKind::DotQuestion,
+                            nested: tok.kind == TokenKind::DoubleColon,
+                            property,
+                        },
+                        Span::new(start, self.prev_end()),
+                    );
+                }
+                TokenKind::LBracket | TokenKind::BracketQuestion => {
+                    let safe = self.bump().kind == TokenKind::BracketQuestion;
+                    self.skip_newlines();
+                    let index = self.parse_expr()?;
+                    self.skip_newlines();
+                    self.expect(&TokenKind::RBracket, "']'")?;
+                    expr = Expr::new(
+                        ExprKind::Index {
+                            object: Box::new(expr),
+                            index: Box::new(index),
+                            safe,
+                        },
+                        Span::new(start, self.prev_end()),
+                    );
+                }
+                TokenKind::LParen => {
+                    self.bump();
+                    let args = self.parse_call_args()?;
+                    expr = Expr::new(
+                        ExprKind::Call {
+                            callee: Box::new(expr),
+                            args,
+                        },
+                        Span::new(start, self.prev_end()),
+                    );
+                }
+                // Non-null assertion `foo!`; `!=` is lexed separately
+                TokenKind::Not => {
+                    self.bump();
+                    expr.span = Span::new(start, self.prev_end());
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// Arguments after an opening parenthesis, consuming the closing one
+    fn parse_call_args(&mut self) -> PResult<Vec<Expr>> {
+        let mut args = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.eat(&TokenKind::RParen) {
+                return Ok(args);
+            }
+            args.push(self.parse_expr()?);
+            self.skip_newlines();
+            if !self.eat(&TokenKind::Comma) {
+                self.skip_newlines();
+                self.expect(&TokenKind::RParen, "')'")?;
+                return Ok(args);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> PResult<Expr> {
+        let tok = self.peek().clone();
+        let start = tok.span.start;
+        match tok.kind {
+            TokenKind::Int(n) => {
+                self.bump();
+                Ok(Expr::new(ExprKind::Int(n), tok.span))
+            }
+            TokenKind::String(segments) => {
+                self.bump();
+                let parts = self.convert_segments(segments)?;
+                Ok(Expr::new(ExprKind::String(parts), tok.span))
+            }
+            TokenKind::MultilineString(s) => {
+                self.bump();
+                Ok(Expr::new(ExprKind::MultilineString(s), tok.span))
+            }
+            TokenKind::Ident(name) => {
+                if self.peek_nth_kind(1) == &TokenKind::Arrow {
+                    let param = self.expect_ident("lambda parameter")?;
+                    return self.parse_lambda_body(vec![param], start);
+                }
+                match name.as_str() {
+                    "true" | "false" => {
+                        self.bump();
+                        Ok(Expr::new(ExprKind::Bool(name == "true"), tok.span))
+                    }
+                    "null" => {
+                        self.bump();
+                        Ok(Expr::new(ExprKind::Null, tok.span))
+                    }
+                    "if" if self.peek_nth_kind(1) == &TokenKind::LParen => {
+                        self.bump();
+                        self.bump();
+                        self.skip_newlines();
+                        let condition = self.parse_expr()?;
+                        self.skip_newlines();
+                        self.expect(&TokenKind::RParen, "')'")?;
+                        let body = self.parse_expr()?;
+                        let span = Span::new(start, body.span.end);
+                        Ok(Expr::new(
+                            ExprKind::If {
+                                condition: Box::new(condition),
+                                body: Box::new(body),
+                            },
+                            span,
+                        ))
+                    }
+                    _ => {
+                        self.bump();
+                        Ok(Expr::new(ExprKind::Ident(name), tok.span))
+                    }
+                }
+            }
+            TokenKind::LParen => {
+                if let Some(params) = self.try_lambda_params() {
+                    return self.parse_lambda_body(params, start);
+                }
+                self.bump();
+                self.skip_newlines();
+                let mut inner = self.parse_expr()?;
+                self.skip_newlines();
+                self.expect(&TokenKind::RParen, "')'")?;
+                inner.span = Span::new(start, self.prev_end());
+                Ok(inner)
+            }
+            TokenKind::LBrace => self.parse_object(),
+            TokenKind::LBracket => {
+                if matches!(self.peek_nth_kind(1), TokenKind::Ident(k) if k == "for") {
+                    self.parse_for()
+                } else {
+                    self.parse_array()
+                }
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    /// Lookahead for `(a, b) =>`; restores the position when it is not a lambda
+    fn try_lambda_params(&mut self) -> Option<Vec<Ident>> {
+        let saved = self.idx;
+        self.bump();
+        let mut params = Vec::new();
+        loop {
+            match self.peek_kind().clone() {
+                TokenKind::Ident(name) => {
+                    let span = self.bump().span;
+                    params.push(Ident { name, span });
+                    if self.eat(&TokenKind::Comma) {
+                        continue;
+                    }
+                    if self.eat(&TokenKind::RParen) {
+                        break;
+                    }
+                }
+                TokenKind::RParen if params.is_empty() => {
+                    self.bump();
+                    break;
+                }
+                _ => {}
+            }
+            self.idx = saved;
+            return None;
+        }
+        if self.at(&TokenKind::Arrow) {
+            Some(params)
+        } else {
+            self.idx = saved;
+            None
+        }
+    }
+
+    fn parse_lambda_body(&mut self, params: Vec<Ident>, start: Position) -> PResult<Expr> {
+        self.expect(&TokenKind::Arrow, "'=>'")?;
+        self.skip_newlines();
+        let body = self.parse_expr()?;
+        let span = Span::new(start, body.span.end);
+        Ok(Expr::new(
+            ExprKind::Lambda {
+                params,
+                body: Box::new(body),
+            },
+            span,
+        ))
+    }
+
+    fn convert_segments(&self, segments: Vec<StringSegment>) -> PResult<Vec<StringPart>> {
+        let mut parts = Vec::new();
+        for seg in segments {
+            match seg {
+                StringSegment::Text(t) => parts.push(StringPart::Text(t)),
+                StringSegment::Interpolation { start, end } => {
+                    let (tokens, _) = Lexer::with_range(self.source, start, end).tokenize()?;
+                    let mut inner = Parser {
+                        source: self.source,
+                        tokens,
+                        idx: 0,
+                    };
+                    inner.skip_newlines();
+                    let expr = inner.parse_expr()?;
+                    inner.skip_newlines();
+                    if !inner.at(&TokenKind::Eof) {
+                        return Err(inner.unexpected("'}'"));
+                    }
+                    parts.push(StringPart::Interpolation(expr));
+                }
+            }
+        }
+        Ok(parts)
+    }
+
+    fn parse_object(&mut self) -> PResult<Expr> {
+        let start = self.bump().span.start; // `{`
+        let mut obj = ObjectExpr::default();
+        loop {
+            self.skip_newlines();
+            if self.eat(&TokenKind::RBrace) {
+                break;
+            }
+            let decorators = self.parse_decorators()?;
+            if self.at_keyword("resource")
+                && matches!(self.peek_nth_kind(1), TokenKind::Ident(_))
+            {
+                obj.resources.push(self.parse_resource(decorators)?);
+            } else if self.at(&TokenKind::Ellipsis) {
+                let key_span = self.bump().span;
+                let value = self.parse_expr()?;
+                obj.properties.push(ObjectProperty {
+                    decorators,
+                    key: PropertyKey::Spread,
+                    key_span,
+                    span: key_span.to(value.span),
+                    value,
+                });
+            } else {
+                let (key, key_span) = self.parse_object_key()?;
+                self.expect(&TokenKind::Colon, "':'")?;
+                self.skip_newlines();
+                let value = self.parse_expr()?;
+                obj.properties.push(ObjectProperty {
+                    decorators,
+                    key,
+                    key_span,
+                    span: key_span.to(value.span),
+                    value,
+                });
+            }
+            if !self.eat(&TokenKind::Comma) {
+                match self.peek_kind() {
+                    TokenKind::Newline | TokenKind::RBrace => {}
+                    _ => return Err(self.unexpected("new line or '}'")),
+                }
+            }
+        }
+        Ok(Expr::new(
+            ExprKind::Object(obj),
+            Span::new(start, self.prev_end()),
+        ))
+    }
+
+    /// Key of an object literal property; unlike type properties it may be interpolated
+    fn parse_object_key(&mut self) -> PResult<(PropertyKey, Span)> {
+        if let TokenKind::String(segments) = self.peek_kind() {
+            if segments
+                .iter()
+                .any(|s| matches!(s, StringSegment::Interpolation { .. }))
+            {
+                let key = self.parse_primary()?;
+                let span = key.span;
+                return Ok((PropertyKey::Interpolated(key), span));
+            }
+        }
+        let key = self.parse_property_key()?;
+        Ok((PropertyKey::Name(key.name), key.span))
+    }
+
+    fn parse_array(&mut self) -> PResult<Expr> {
+        let start = self.bump().span.start; // `[`
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.eat(&TokenKind::RBracket) {
+                break;
+            }
+            if self.at(&TokenKind::Ellipsis) {
+                let spread_start = self.bump().span.start;
+                let value = self.parse_expr()?;
+                let span = Span::new(spread_start, value.span.end);
+                items.push(Expr::new(ExprKind::Spread(Box::new(value)), span));
+            } else {
+                items.push(self.parse_expr()?);
+            }
+            if !self.eat(&TokenKind::Comma) {
+                match self.peek_kind() {
+                    TokenKind::Newline | TokenKind::RBracket => {}
+                    _ => return Err(self.unexpected("new line or ']'")),
+                }
+            }
+        }
+        Ok(Expr::new(
+            ExprKind::Array(items),
+            Span::new(start, self.prev_end()),
+        ))
+    }
+
+    fn parse_for(&mut self) -> PResult<Expr> {
+        let start = self.bump().span.start; // `[`
+        self.bump(); // `for`
+        let (item, index) = if self.eat(&TokenKind::LParen) {
+            let item = self.expect_ident("loop variable")?;
+            self.expect(&TokenKind::Comma, "','")?;
+            let index = self.expect_ident("loop index")?;
+            self.expect(&TokenKind::RParen, "')'")?;
+            (item, Some(index))
+        } else {
+            (self.expect_ident("loop variable")?, None)
+        };
+        if !self.at_keyword("in") {
+            return Err(self.unexpected("'in'"));
+        }
+        self.bump();
+        let iterable = self.parse_expr()?;
+        self.expect(&TokenKind::Colon, "':'")?;
+        self.skip_newlines();
+        let body = self.parse_expr()?;
+        self.skip_newlines();
+        self.expect(&TokenKind::RBracket, "']'")?;
+        Ok(Expr::new(
+            ExprKind::For {
+                item,
+                index,
+                iterable: Box::new(iterable),
+                body: Box::new(body),
+            },
+            Span::new(start, self.prev_end()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(src: &str) -> Program {
+        let program = parse(src).unwrap();
+        assert!(program.errors.is_empty(), "unexpected errors: {:?}", program.errors);
+        program
+    }
+
+    fn only_statement(src: &str) -> Statement {
+        let mut program = parse_ok(src);
+        assert_eq!(program.statements.len(), 1);
+        program.statements.remove(0)
+    }
+
+    #[test]
+    fn parses_declarations() {
+        let program = parse_ok(
+            "targetScope = 'subscription'\n\
+             param location string = resourceGroup().location\n\
+             var names = ['a', 'b']\n\
+             resource stg 'Microsoft.Storage/storageAccounts@2022-09-01' existing = {\n  name: 'x'\n}\n\
+             module app './app.bicep' = {\n  name: 'app'\n}\n\
+             output id string = stg.id\n\
+             type sku = 'Standard_LRS' | 'Premium_LRS'\n\
+             import * as types from 'types.bicep'\n",
+        );
+        let symbols: Vec<_> = program.symbols().iter().map(|(kind, id)| (*kind, id.name.as_str())).collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("Parameter", "location"),
+                ("Variable", "names"),
+                ("Resource", "stg"),
+                ("Module", "app"),
+                ("Output", "id"),
+            ]
+        );
+        let stg = program.resources().next().unwrap();
+        assert_eq!(stg.resource_type(), "Microsoft.Storage/storageAccounts");
+        assert!(stg.existing);
+        assert_eq!(program.modules().next().unwrap().path, "./app.bicep");
+        assert!(matches!(program.statements[0], Statement::TargetScope(_)));
+        assert!(matches!(&program.statements[6], Statement::Type(t) if matches!(t.value, TypeExpr::Union(..))));
+        assert!(matches!(&program.statements[7], Statement::Other(o) if o.keyword == "import"));
+    }
+
+    #[test]
+    fn parses_decorators() {
+        let stmt = only_statement("@description('Region')\n@sys.minLength(2)\nparam region string\n");
+        let decorators = stmt.decorators();
+        assert_eq!(decorators.len(), 2);
+        let description = find_decorator(decorators, "description").unwrap();
+        assert_eq!(description.args[0].as_plain_string().as_deref(), Some("Region"));
+        let min = find_decorator(decorators, "minLength").unwrap();
+        assert_eq!(min.namespace.as_deref(), Some("sys"));
+        assert_eq!(min.args[0].kind, ExprKind::Int(2));
+    }
+
+    #[test]
+    fn parses_loops_and_conditions() {
+        let program = parse_ok(
+            "resource a 'T@1' = [for (name, i) in names: {\n  name: name\n}]\n\
+             resource b 'T@1' = if (deploy) {\n  name: 'b'\n}\n",
+        );
+        let mut resources = program.resources();
+        match &resources.next().unwrap().body.kind {
+            ExprKind::For { item, index, .. } => {
+                assert_eq!(item.name, "name");
+                assert_eq!(index.as_ref().map(|i| i.name.as_str()), Some("i"));
+            }
+            other => panic!("expected a loop, got {:?}", other),
+        }
+        let b = resources.next().unwrap();
+        assert!(matches!(&b.body.kind, ExprKind::If { condition, .. } if condition.kind == ExprKind::Ident("deploy".to_string())));
+        assert!(b.body_object().unwrap().get("name").is_some());
+    }
+
+    #[test]
+    fn parses_interpolation_and_keys() {
+        let stmt = only_statement("var v = {\n  '${prefix}-owner': 'x-${name}'\n  ...tags\n  'plain key': [\n    ...items\n  ]\n}\n");
+        let value = match stmt {
+            Statement::Var(v) => v.value,
+            other => panic!("expected a var, got {:?}", other),
+        };
+        let obj = value.innermost_object().unwrap();
+        assert!(matches!(&obj.properties[0].key, PropertyKey::Interpolated(_)));
+        assert_eq!(obj.properties[1].key, PropertyKey::Spread);
+        assert!(matches!(obj.get("plain key").unwrap().value.kind, ExprKind::Array(ref items) if matches!(items[0].kind, ExprKind::Spread(_))));
+
+        let mut names = Vec::new();
+        value.walk(&mut |e| {
+            if let ExprKind::Ident(n) = &e.kind {
+                names.push(n.clone());
+            }
+        });
+        assert_eq!(names, vec!["prefix", "name", "tags", "items"]);
+    }
+
+    #[test]
+    fn parses_multiline_string() {
+        let stmt = only_statement("var script = '''\necho ${notInterpolated}\n'''\n");
+        match stmt {
+            Statement::Var(v) => {
+                assert_eq!(v.value.as_plain_string().as_deref(), Some("echo ${notInterpolated}\n"))
+            }
+            other => panic!("expected a var, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn records_spans() {
+        let src = "param a string\n\nvar longName = a\n";
+        let program = parse_ok(src);
+        let var = program.vars().next().unwrap();
+        assert_eq!(var.name.span.start.line, 3);
+        assert_eq!(var.name.span.start.column, 5);
+        assert_eq!(var.name.span.slice(src), "longName");
+        assert_eq!(var.span.slice(src), "var longName = a");
+    }
+
+    #[test]
+    fn recovers_after_syntax_error() {
+        let program = parse("param a string\nvar broken = { x: 1 y }\nvar ok = a\n").unwrap();
+        assert_eq!(program.errors.len(), 1);
+        assert_eq!(program.errors[0].span.start.line, 2);
+        let names: Vec<_> = program.statements.iter().filter_map(|s| s.name()).map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "ok"]);
+    }
+
+    #[test]
+    fn lexer_errors_abort() {
+        assert!(parse("var s = 'unterminated\n").is_err());
+    }
+}
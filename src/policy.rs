@@ -0,0 +1,104 @@
+// policy.rs
+//
+// Decides the process exit code from the structured findings, and maps
+// errors onto distinct exit codes so CI can tell a failing review apart
+// from a broken run.
+
+use crate::models::ValidationResult;
+use std::error::Error;
+use std::fmt;
+
+/// Run completed and the findings are within policy
+pub const EXIT_OK: i32 = 0;
+/// Run completed but the findings exceed the configured policy
+pub const EXIT_FINDINGS: i32 = 1;
+/// Invalid arguments, configuration or input files
+pub const EXIT_USAGE: i32 = 2;
+/// LLM, search or Azure DevOps call failed, or returned something unusable
+pub const EXIT_RUNTIME: i32 = 3;
+
+/// Error caused by how the tool was invoked rather than by a remote call
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+/// Box a usage error with the given message
+pub fn usage(message: impl Into<String>) -> Box<dyn Error> {
+    Box::new(UsageError(message.into()))
+}
+
+/// Exit code for an error that aborted the run
+pub fn exit_code_for(err: &(dyn Error + 'static)) -> i32 {
+    if err.downcast_ref::<UsageError>().is_some() {
+        EXIT_USAGE
+    } else {
+        EXIT_RUNTIME
+    }
+}
+
+/// When a completed run should fail the build
+#[derive(Debug, Clone, Copy)]
+pub struct FailPolicy {
+    /// Fail when any finding has at least this severity; 0 disables the check
+    pub fail_on_severity: u8,
+    /// Fail when more findings than this are reported
+    pub max_findings: Option<usize>,
+}
+
+impl FailPolicy {
+    pub fn new(fail_on_severity: u8, max_findings: Option<usize>) -> Result<Self, Box<dyn Error>> {
+        if fail_on_severity > 5 {
+            return Err(usage("--fail-on-severity must be 0..=5 (0 disables it)"));
+        }
+        Ok(FailPolicy {
+            fail_on_severity,
+            max_findings,
+        })
+    }
+
+    /// Reasons the findings violate the policy; empty when they pass
+    pub fn violations(&self, findings: &[&ValidationResult]) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.fail_on_severity > 0 {
+            let over = findings
+                .iter()
+                .filter(|f| f.severity >= self.fail_on_severity)
+                .count();
+            if over > 0 {
+                out.push(format!(
+                    "{} finding(s) with severity >= {}",
+                    over, self.fail_on_severity
+                ));
+            }
+        }
+        if let Some(max) = self.max_findings {
+            if findings.len() > max {
+                out.push(format!(
+                    "{} finding(s) reported, more than the allowed {}",
+                    findings.len(),
+                    max
+                ));
+            }
+        }
+        out
+    }
+
+    /// Print violations to stderr and return the exit code
+    pub fn evaluate(&self, findings: &[&ValidationResult]) -> i32 {
+        let violations = self.violations(findings);
+        if violations.is_empty() {
+            return EXIT_OK;
+        }
+        for v in &violations {
+            eprintln!("Policy failed: {}", v);
+        }
+        EXIT_FINDINGS
+    }
+}
@@ -0,0 +1,410 @@
+// report.rs
+//
+// Renderers for the local analysis output formats.
+
+use crate::catalog::RuleCatalog;
+use crate::models::{FinalReport, OutputFormat, ReportMetadata, ValidationResult};
+use crate::sarif;
+use prettytable::{Cell, Row, Table};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+const TABLE_WRAP_WIDTH: usize = 60;
+
+/// Everything a renderer may need besides the findings
+pub struct RenderContext<'a> {
+    pub min_severity: u8,
+    pub catalog: &'a RuleCatalog,
+    pub categories: &'a [String],
+    pub metadata: &'a ReportMetadata,
+    /// Findings dropped by inline suppression comments
+    pub suppressed: &'a [ValidationResult],
+}
+
+/// Render filtered findings in the requested format
+pub fn render(
+    format: OutputFormat,
+    findings: &[&ValidationResult],
+    ctx: &RenderContext,
+) -> Result<String, Box<dyn Error>> {
+    match format {
+        OutputFormat::Markdown => build_markdown(findings, ctx),
+        OutputFormat::Table => Ok(build_table(findings, ctx.metadata.files.len() > 1).to_string()),
+        OutputFormat::Json => build_json(findings, ctx.metadata),
+        OutputFormat::Junit => Ok(build_junit(findings, ctx)),
+        OutputFormat::Sarif => sarif::build_sarif(findings, ctx.suppressed, ctx.catalog),
+    }
+}
+
+pub fn severity_label(severity: u8) -> &'static str {
+    match severity {
+        5 => "5 (Critical) ⚠️",
+        4 => "4 (Serious)",
+        3 => "3 (Important)",
+        2 => "2 (Minor)",
+        _ => "1 (Suggestion)",
+    }
+}
+
+/// File a finding belongs to, or "-" when unknown
+fn file_of(finding: &ValidationResult) -> &str {
+    finding
+        .location
+        .as_ref()
+        .and_then(|l| l.file_path.as_deref())
+        .unwrap_or("-")
+}
+
+/// Findings grouped by file, in order of first appearance
+fn group_by_file<'a>(findings: &[&'a ValidationResult]) -> Vec<(&'a str, Vec<&'a ValidationResult>)> {
+    let mut groups: Vec<(&str, Vec<&ValidationResult>)> = Vec::new();
+    for f in findings {
+        let file = file_of(f);
+        match groups.iter_mut().find(|(name, _)| *name == file) {
+            Some((_, items)) => items.push(f),
+            None => groups.push((file, vec![*f])),
+        }
+    }
+    groups
+}
+
+/// Findings as a wrapped plain-text table, optionally with a File column
+fn build_table(findings: &[&ValidationResult], file_column: bool) -> Table {
+    let mut table = Table::new();
+    let mut header = row![b => "Category", "Location", "Finding", "Severity", "Impact"];
+    if file_column {
+        header.insert_cell(0, cell!(b -> "File"));
+    }
+    table.add_row(header);
+
+    for f in findings {
+        let wrapped_find = textwrap::fill(&f.finding, TABLE_WRAP_WIDTH);
+        let wrapped_imp = textwrap::fill(&f.impact, TABLE_WRAP_WIDTH);
+
+        let location = f
+            .location
+            .as_ref()
+            .map(|l| l.line_label())
+            .unwrap_or_else(|| "-".to_string());
+
+        let mut row = Row::new(vec![
+            Cell::new(&f.category),
+            Cell::new(&location),
+            Cell::new(&wrapped_find),
+            Cell::new(severity_label(f.severity)),
+            Cell::new(&wrapped_imp),
+        ]);
+        if file_column {
+            row.insert_cell(0, Cell::new(file_of(f)));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+/// Build final markdown report; several files get one section each
+fn build_markdown(findings: &[&ValidationResult], ctx: &RenderContext) -> Result<String, Box<dyn Error>> {
+    let min_severity = ctx.min_severity;
+    let file_count = ctx.metadata.files.len();
+    let mut out = String::new();
+    out.push_str("# Bicep Code Review Results\n\n");
+    if file_count > 1 {
+        out.push_str(&format!("Reviewed {} Bicep files.\n\n", file_count));
+    }
+
+    let mut suppressed = String::new();
+    if !ctx.suppressed.is_empty() {
+        suppressed.push_str(&format!(" {} suppressed by inline comments.", ctx.suppressed.len()));
+    }
+    if ctx.metadata.baselined > 0 {
+        suppressed.push_str(&format!(" {} known from the baseline.", ctx.metadata.baselined));
+    }
+    if findings.is_empty() {
+        out.push_str(&format!(
+            "No issues found with severity {} or higher.{}\n",
+            min_severity, suppressed
+        ));
+        return Ok(out);
+    }
+
+    out.push_str(&format!(
+        "Found {} issues with severity {} or higher.{}\n\n",
+        findings.len(),
+        min_severity,
+        suppressed
+    ));
+
+    if file_count > 1 {
+        for (file, items) in group_by_file(findings) {
+            out.push_str(&format!("## `{}` ({} issues)\n\n", file, items.len()));
+            push_code_table(&mut out, &items);
+            out.push('\n');
+        }
+    } else {
+        push_code_table(&mut out, findings);
+    }
+
+    if findings.iter().any(|f| f.severity == 5) {
+        out.push_str("\n⚠️ **CRITICAL ISSUES FOUND**\n");
+    }
+
+    Ok(out)
+}
+
+/// Indented table inside a code fence
+fn push_code_table(out: &mut String, findings: &[&ValidationResult]) {
+    let table_str = build_table(findings, false)
+        .to_string()
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    out.push_str("```\n");
+    out.push_str(&table_str);
+    out.push_str("\n```\n");
+}
+
+/// Serialized FinalReport of the filtered findings plus run metadata
+fn build_json(
+    findings: &[&ValidationResult],
+    metadata: &ReportMetadata,
+) -> Result<String, Box<dyn Error>> {
+    let report = FinalReport {
+        findings: findings.iter().map(|f| (*f).clone()).collect(),
+        metadata: Some(metadata.clone()),
+    };
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+/// JUnit XML with one test suite per category and one failing test case per finding
+fn build_junit(findings: &[&ValidationResult], ctx: &RenderContext) -> String {
+    let mut suites: BTreeMap<&str, Vec<&ValidationResult>> = BTreeMap::new();
+    for cat in ctx.categories {
+        suites.entry(cat.as_str()).or_default();
+    }
+    for f in findings {
+        suites.entry(f.category.as_str()).or_default().push(f);
+    }
+
+    let time = ctx.metadata.timings.total_ms as f64 / 1000.0;
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        crate::sarif::TOOL_NAME,
+        suites.values().map(|v| v.len().max(1)).sum::<usize>(),
+        findings.len(),
+        time
+    ));
+
+    for (category, items) in &suites {
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            xml_escape(category),
+            items.len().max(1),
+            items.len()
+        ));
+        if items.is_empty() {
+            out.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{} review\"/>\n",
+                xml_escape(&ctx.metadata.files.join(",")),
+                xml_escape(category)
+            ));
+        }
+        for f in items {
+            let classname = f
+                .location
+                .as_ref()
+                .and_then(|l| l.file_path.clone())
+                .unwrap_or_else(|| ctx.metadata.files.join(","));
+            let name = match (&f.rule_id, &f.location) {
+                (Some(id), Some(loc)) => format!("{} ({})", id, loc.line_label()),
+                (Some(id), None) => id.clone(),
+                (None, Some(loc)) => format!("{} ({})", f.finding, loc.line_label()),
+                (None, None) => f.finding.clone(),
+            };
+
+            let mut body = format!("{}\nImpact: {}", f.finding, f.impact);
+            if let Some(loc) = &f.location {
+                let file = loc.file_path.as_deref().unwrap_or("");
+                if loc.is_file_level() {
+                    body.push_str(&format!("\nLocation: {}", file));
+                } else {
+                    body.push_str(&format!(
+                        "\nLocation: {}:{}:{}",
+                        file, loc.start_line, loc.start_column
+                    ));
+                }
+                if let Some(snippet) = &loc.snippet {
+                    body.push_str(&format!("\n{}", snippet));
+                }
+            }
+
+            out.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>\n",
+                xml_escape(&classname),
+                xml_escape(&name),
+                xml_escape(&f.finding),
+                xml_escape(severity_label(f.severity)),
+                xml_escape(&body)
+            ));
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Location, Timings};
+
+    fn findings() -> Vec<ValidationResult> {
+        vec![
+            ValidationResult {
+                category: "Security".to_string(),
+                finding: "Parameter 'key' <&\"'> leaks".to_string(),
+                severity: 4,
+                impact: "Secrets & keys".to_string(),
+                rule_id: Some("security/secure-params".to_string()),
+                location: Some(Location {
+                    file_path: Some("main.bicep".to_string()),
+                    start_line: 3,
+                    start_column: 7,
+                    end_line: 3,
+                    end_column: 10,
+                    snippet: Some("param key string".to_string()),
+                }),
+            },
+            ValidationResult {
+                category: "Outputs".to_string(),
+                finding: "No outputs".to_string(),
+                severity: 2,
+                impact: "Callers cannot chain".to_string(),
+                rule_id: None,
+                location: Some(Location::file_level("app.bicep")),
+            },
+        ]
+    }
+
+    fn metadata(files: &[&str]) -> ReportMetadata {
+        ReportMetadata {
+            tool: sarif::TOOL_NAME.to_string(),
+            tool_version: "0.0.0".to_string(),
+            provider: None,
+            model: None,
+            generated_at: "2024-01-01T00:00:00Z".to_string(),
+            minimum_severity: 1,
+            files: files.iter().map(|f| f.to_string()).collect(),
+            suppressed: 0,
+            baselined: 0,
+            timings: Timings {
+                total_ms: 1500,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn escapes_xml_special_characters() {
+        assert_eq!(xml_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+        assert_eq!(xml_escape("plain ünïcode"), "plain ünïcode");
+    }
+
+    #[test]
+    fn junit_has_a_suite_per_category_and_a_case_per_finding() {
+        let findings = findings();
+        let refs: Vec<&ValidationResult> = findings.iter().collect();
+        let catalog = RuleCatalog::default();
+        let categories = vec!["Outputs".to_string(), "Parameters".to_string(), "Security".to_string()];
+        let metadata = metadata(&["main.bicep", "app.bicep"]);
+        let ctx = RenderContext {
+            min_severity: 1,
+            catalog: &catalog,
+            categories: &categories,
+            metadata: &metadata,
+            suppressed: &[],
+        };
+
+        let expected = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<testsuites name=\"{}\" tests=\"3\" failures=\"2\" time=\"1.500\">
+  <testsuite name=\"Outputs\" tests=\"1\" failures=\"1\">
+    <testcase classname=\"app.bicep\" name=\"No outputs (file)\">
+      <failure message=\"No outputs\" type=\"2 (Minor)\">No outputs
+Impact: Callers cannot chain
+Location: app.bicep</failure>
+    </testcase>
+  </testsuite>
+  <testsuite name=\"Parameters\" tests=\"1\" failures=\"0\">
+    <testcase classname=\"main.bicep,app.bicep\" name=\"Parameters review\"/>
+  </testsuite>
+  <testsuite name=\"Security\" tests=\"1\" failures=\"1\">
+    <testcase classname=\"main.bicep\" name=\"security/secure-params (L3)\">
+      <failure message=\"Parameter &apos;key&apos; &lt;&amp;&quot;&apos;&gt; leaks\" type=\"4 (Serious)\">Parameter &apos;key&apos; &lt;&amp;&quot;&apos;&gt; leaks
+Impact: Secrets &amp; keys
+Location: main.bicep:3:7
+param key string</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+",
+            sarif::TOOL_NAME
+        );
+        assert_eq!(render(OutputFormat::Junit, &refs, &ctx).unwrap(), expected);
+    }
+
+    #[test]
+    fn table_adds_a_file_column_for_several_files() {
+        let findings = findings();
+        let refs: Vec<&ValidationResult> = findings.iter().collect();
+
+        let table = build_table(&refs, true).to_string();
+        let lines: Vec<&str> = table.lines().filter(|l| l.starts_with('|')).collect();
+        assert_eq!(lines.len(), 3);
+        let cells = |line: &str| -> Vec<String> {
+            line.trim_matches('|').split('|').map(|c| c.trim().to_string()).collect()
+        };
+        assert_eq!(cells(lines[0]), vec!["File", "Category", "Location", "Finding", "Severity", "Impact"]);
+        assert_eq!(
+            cells(lines[1]),
+            vec!["main.bicep", "Security", "L3", "Parameter 'key' <&\"'> leaks", "4 (Serious)", "Secrets & keys"]
+        );
+        assert_eq!(
+            cells(lines[2]),
+            vec!["app.bicep", "Outputs", "file", "No outputs", "2 (Minor)", "Callers cannot chain"]
+        );
+
+        let single = build_table(&refs, false).to_string();
+        assert!(single.lines().nth(1).unwrap().starts_with("| Category "));
+    }
+
+    #[test]
+    fn groups_findings_by_file_in_order_of_appearance() {
+        let mut findings = findings();
+        findings.push(ValidationResult {
+            location: None,
+            ..findings[0].clone()
+        });
+        findings.push(findings[0].clone());
+        let refs: Vec<&ValidationResult> = findings.iter().collect();
+        let groups: Vec<(&str, usize)> = group_by_file(&refs).into_iter().map(|(file, items)| (file, items.len())).collect();
+        assert_eq!(groups, vec![("main.bicep", 2), ("app.bicep", 1), ("-", 1)]);
+    }
+}
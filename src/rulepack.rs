@@ -0,0 +1,572 @@
+// rulepack.rs
+//
+// Structured best-practices packs in YAML, TOML or JSON. Each rule has an
+// id, category, default severity, description, rationale and good/bad
+// examples, and may declare a deterministic matcher that runs next to the
+// built-in rules. A pack also defines which categories the LLM reviews.
+
+use crate::ast::{ObjectExpr, Program, PropertyKey, ResourceDecl};
+use crate::catalog::{CategorySection, CodeExample, PracticeRule, RuleCatalog};
+use crate::models::{Location, ValidationResult};
+use crate::policy::usage;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// File extensions read as rule packs; anything else is markdown
+pub const PACK_EXTENSIONS: [&str; 4] = ["yaml", "yml", "toml", "json"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackFile {
+    #[serde(default)]
+    name: Option<String>,
+    rules: Vec<PackRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackRule {
+    id: String,
+    category: String,
+    severity: u8,
+    description: String,
+    #[serde(default)]
+    rationale: Option<String>,
+    #[serde(default)]
+    links: Vec<String>,
+    #[serde(default)]
+    examples: PackExamples,
+    #[serde(default)]
+    matcher: Option<MatcherSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackExamples {
+    #[serde(default)]
+    good: Option<String>,
+    #[serde(default)]
+    bad: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MatcherSpec {
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    resource_type: Option<String>,
+    #[serde(default)]
+    missing_property: Option<String>,
+}
+
+/// Deterministic check attached to a pack rule
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Regex tested against every source line
+    Pattern(Regex),
+    /// Resources of a type whose body does not set a dotted property path
+    MissingProperty {
+        resource_type: String,
+        path: Vec<String>,
+    },
+}
+
+/// Whether a best-practices file should be read as a rule pack
+pub fn is_pack_path(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| PACK_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Parse a rule pack; any problem with the file is a usage error
+pub fn parse_pack(path: &str, text: &str) -> Result<RuleCatalog, Box<dyn Error>> {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let parsed: Result<PackFile, String> = match ext.as_str() {
+        "toml" => toml::from_str(text).map_err(|e| e.to_string()),
+        "json" => serde_json::from_str(text).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+    };
+    let pack = parsed.map_err(|e| usage(format!("Invalid rule pack {}: {}", path, e)))?;
+    build_catalog(pack).map_err(|e| usage(format!("Invalid rule pack {}: {}", path, e)))
+}
+
+fn build_catalog(pack: PackFile) -> Result<RuleCatalog, String> {
+    if pack.rules.is_empty() {
+        return Err("no rules defined".to_string());
+    }
+
+    let mut catalog = RuleCatalog::default();
+    let mut seen = HashSet::new();
+    for rule in pack.rules {
+        if rule.id.trim().is_empty() {
+            return Err("rule without an id".to_string());
+        }
+        if !seen.insert(rule.id.clone()) {
+            return Err(format!("duplicate rule id '{}'", rule.id));
+        }
+        if rule.category.trim().is_empty() {
+            return Err(format!("rule '{}' has no category", rule.id));
+        }
+        if !(1..=5).contains(&rule.severity) {
+            return Err(format!("rule '{}' severity must be 1..=5", rule.id));
+        }
+        let matcher = match &rule.matcher {
+            Some(spec) => Some(compile_matcher(spec).map_err(|e| format!("rule '{}': {}", rule.id, e))?),
+            None => None,
+        };
+
+        let mut examples = Vec::new();
+        for (label, code) in [("Good", &rule.examples.good), ("Bad", &rule.examples.bad)] {
+            if let Some(code) = code {
+                examples.push(CodeExample {
+                    language: "bicep".to_string(),
+                    code: code.trim_end().to_string(),
+                    label: Some(label.to_string()),
+                });
+            }
+        }
+
+        let category = rule.category.trim().to_string();
+        let practice = PracticeRule {
+            id: rule.id,
+            text: rule.description.trim().to_string(),
+            links: rule.links,
+            examples,
+            severity: Some(rule.severity),
+            rationale: rule.rationale.map(|r| r.trim().to_string()),
+            matcher,
+        };
+        match catalog.sections.iter_mut().find(|s| s.heading == category) {
+            Some(section) => section.rules.push(practice),
+            None => {
+                catalog.categories.push(category.clone());
+                catalog.sections.push(CategorySection {
+                    heading: category,
+                    rules: vec![practice],
+                    links: Vec::new(),
+                    examples: Vec::new(),
+                });
+            }
+        }
+    }
+    catalog.document = render_markdown(pack.name.as_deref(), &catalog);
+    Ok(catalog)
+}
+
+fn compile_matcher(spec: &MatcherSpec) -> Result<Matcher, String> {
+    match (&spec.pattern, &spec.resource_type, &spec.missing_property) {
+        (Some(pattern), None, None) => Regex::new(pattern)
+            .map(Matcher::Pattern)
+            .map_err(|e| format!("invalid matcher pattern: {}", e)),
+        (None, Some(resource_type), Some(property)) => Ok(Matcher::MissingProperty {
+            resource_type: resource_type.clone(),
+            path: property.split('.').map(|p| p.to_string()).collect(),
+        }),
+        _ => Err("matcher needs either `pattern`, or `resource_type` with `missing_property`".to_string()),
+    }
+}
+
+/// Markdown rendering of a pack, used as the document for LLM prompts
+fn render_markdown(name: Option<&str>, catalog: &RuleCatalog) -> String {
+    let mut out = format!("# {}\n", name.unwrap_or("Bicep Best Practices"));
+    for section in &catalog.sections {
+        out.push_str(&format!("\n## {}\n\n", section.heading));
+        for rule in &section.rules {
+            out.push_str(&format!("- {}\n", rule.text));
+            if let Some(rationale) = &rule.rationale {
+                out.push_str(&format!("  - Rationale: {}\n", rationale));
+            }
+            for ex in &rule.examples {
+                out.push_str(&format!(
+                    "\n  {}:\n```{}\n{}\n```\n",
+                    ex.label.as_deref().unwrap_or("Example"),
+                    ex.language,
+                    ex.code
+                ));
+            }
+        }
+    }
+    out
+}
+
+// ------------------------------------------------------------
+// Matchers
+
+/// Run the deterministic matchers of every pack rule against one file
+pub fn run_matchers(
+    catalog: &RuleCatalog,
+    content: &str,
+    program: Option<&Program>,
+) -> Vec<ValidationResult> {
+    let mut out = Vec::new();
+    for section in &catalog.sections {
+        for rule in &section.rules {
+            let hits = match &rule.matcher {
+                Some(Matcher::Pattern(re)) => match_pattern(re, content),
+                Some(Matcher::MissingProperty {
+                    resource_type,
+                    path,
+                }) => match program {
+                    Some(p) => match_missing_property(p, resource_type, path),
+                    None => Vec::new(),
+                },
+                None => continue,
+            };
+            for (subject, location) in hits {
+                out.push(ValidationResult {
+                    category: section.heading.clone(),
+                    finding: format!("{}: {}", subject, rule.text),
+                    severity: rule.severity.unwrap_or(crate::models::DEFAULT_MIN_SEVERITY),
+                    impact: rule
+                        .rationale
+                        .clone()
+                        .unwrap_or_else(|| format!("Violates rule {}.", rule.id)),
+                    rule_id: Some(rule.id.clone()),
+                    location: Some(location),
+                });
+            }
+        }
+    }
+    out
+}
+
+fn match_pattern(re: &Regex, content: &str) -> Vec<(String, Location)> {
+    let mut hits = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if let Some(m) = re.find(line) {
+            let start = line[..m.start()].chars().count() + 1;
+            hits.push((
+                format!("`{}`", m.as_str().trim()),
+                Location {
+                    start_line: i + 1,
+                    start_column: start,
+                    end_line: i + 1,
+                    end_column: start + m.as_str().chars().count(),
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+    hits
+}
+
+fn match_missing_property(
+    program: &Program,
+    resource_type: &str,
+    path: &[String],
+) -> Vec<(String, Location)> {
+    fn from_resource(
+        res: &ResourceDecl,
+        parent_type: Option<&str>,
+        wanted: (&str, &[String]),
+        hits: &mut Vec<(String, Location)>,
+    ) {
+        // Nested children name only their own segment, e.g. `blobServices`
+        let full_type = match parent_type {
+            Some(parent) => format!("{}/{}", parent, res.resource_type()),
+            None => res.resource_type().to_string(),
+        };
+        let (resource_type, path) = wanted;
+        if !res.existing
+            && full_type.eq_ignore_ascii_case(resource_type)
+            && !has_property(res.body_object(), path)
+        {
+            hits.push((
+                format!("Resource `{}` does not set `{}`", res.name.name, path.join(".")),
+                Location {
+                    start_line: res.name.span.start.line,
+                    start_column: res.name.span.start.column,
+                    end_line: res.name.span.end.line,
+                    end_column: res.name.span.end.column,
+                    ..Default::default()
+                },
+            ));
+        }
+        if let Some(obj) = res.body_object() {
+            for child in &obj.resources {
+                from_resource(child, Some(&full_type), wanted, hits);
+            }
+        }
+    }
+
+    let mut hits = Vec::new();
+    for res in program.resources() {
+        from_resource(res, None, (resource_type, path), &mut hits);
+    }
+    hits
+}
+
+/// Whether an object sets a dotted property path. Values that are not object
+/// literals (variables, function calls) cannot be inspected and count as set.
+fn has_property(obj: Option<&ObjectExpr>, path: &[String]) -> bool {
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    let obj = match obj {
+        Some(obj) => obj,
+        None => return false,
+    };
+    match obj.get(first) {
+        Some(_) if rest.is_empty() => true,
+        Some(prop) => match prop.value.innermost_object() {
+            Some(inner) => has_property(Some(inner), rest),
+            None => true,
+        },
+        // `...spread` or `'${key}'` may supply the property
+        None => obj
+            .properties
+            .iter()
+            .any(|p| matches!(p.key, PropertyKey::Spread | PropertyKey::Interpolated(_))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::policy::exit_code_for;
+
+    const YAML: &str = "\
+name: Team pack
+rules:
+  - id: tls
+    category: Security
+    severity: 4
+    description: Storage accounts set a minimum TLS version
+    rationale: Old TLS versions are insecure
+    examples:
+      good: \"properties: { minimumTlsVersion: 'TLS1_2' }\"
+    matcher:
+      resource_type: Microsoft.Storage/storageAccounts
+      missing_property: properties.minimumTlsVersion
+  - id: http
+    category: Security
+    severity: 3
+    description: Do not use plain http URLs
+    matcher:
+      pattern: 'http://'
+  - id: tags
+    category: Cost & Tagging
+    severity: 2
+    description: Tag every resource
+";
+
+    fn pack(path: &str, text: &str) -> RuleCatalog {
+        parse_pack(path, text).unwrap()
+    }
+
+    fn pack_error(path: &str, text: &str) -> String {
+        let err = parse_pack(path, text).unwrap_err();
+        assert_eq!(exit_code_for(err.as_ref()), crate::policy::EXIT_USAGE);
+        err.to_string()
+    }
+
+    fn one_rule(fields: &str) -> String {
+        format!("rules:\n  - id: r\n    category: C\n    description: d\n{}", fields)
+    }
+
+    fn findings(pack_text: &str, src: &str) -> Vec<(String, usize)> {
+        let catalog = pack("pack.yaml", pack_text);
+        let program = parse(src).unwrap();
+        run_matchers(&catalog, src, Some(&program))
+            .into_iter()
+            .map(|f| (f.finding, f.location.unwrap().start_line))
+            .collect()
+    }
+
+    const TLS: &str = "\
+rules:
+  - id: tls
+    category: Security
+    severity: 4
+    description: Set a minimum TLS version
+    matcher:
+      resource_type: Microsoft.Storage/storageAccounts
+      missing_property: properties.minimumTlsVersion
+";
+
+    #[test]
+    fn reads_yaml_into_categories_and_document() {
+        let catalog = pack("rules.yml", YAML);
+        assert_eq!(catalog.categories(), vec!["Security", "Cost & Tagging"]);
+        let security = catalog.rules_for("Security");
+        assert_eq!(security.len(), 2);
+        assert_eq!(security[0].id, "tls");
+        assert_eq!(security[0].severity, Some(4));
+        assert_eq!(security[0].rationale.as_deref(), Some("Old TLS versions are insecure"));
+        assert_eq!(security[0].examples[0].label.as_deref(), Some("Good"));
+        assert!(matches!(security[1].matcher, Some(Matcher::Pattern(_))));
+        assert!(catalog.document.starts_with("# Team pack\n"));
+        assert!(catalog.document.contains("\n## Cost & Tagging\n\n- Tag every resource\n"));
+    }
+
+    #[test]
+    fn reads_toml_and_json() {
+        let toml = "[[rules]]\nid = \"a\"\ncategory = \"Outputs\"\nseverity = 2\ndescription = \"Describe outputs\"\n";
+        let json = r#"{"rules": [{"id": "a", "category": "Outputs", "severity": 2, "description": "Describe outputs"}]}"#;
+        for (path, text) in [("pack.TOML", toml), ("pack.json", json)] {
+            let catalog = pack(path, text);
+            assert_eq!(catalog.categories(), vec!["Outputs"]);
+            assert!(catalog.document.starts_with("# Bicep Best Practices\n"));
+        }
+    }
+
+    #[test]
+    fn recognizes_pack_extensions() {
+        assert!(is_pack_path("rules.yaml"));
+        assert!(is_pack_path("dir/rules.JSON"));
+        assert!(!is_pack_path("bicep-best-practices.md"));
+        assert!(!is_pack_path("yaml"));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = pack_error("pack.yaml", &one_rule("    severity: 2\n    sevrity: 3\n"));
+        assert!(err.contains("unknown field `sevrity`"), "{}", err);
+        let err = pack_error("pack.yaml", &one_rule("    severity: 2\n    matcher:\n      regex: x\n"));
+        assert!(err.contains("unknown field `regex`"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let duplicate = format!("{}  - id: r\n    category: C\n    severity: 2\n    description: again\n", one_rule("    severity: 2\n"));
+        assert_eq!(pack_error("p.yaml", &duplicate), "Invalid rule pack p.yaml: duplicate rule id 'r'");
+        assert_eq!(
+            pack_error("p.yaml", "rules:\n  - id: r\n    category: ' '\n    severity: 2\n    description: d\n"),
+            "Invalid rule pack p.yaml: rule 'r' has no category"
+        );
+        for severity in ["0", "6"] {
+            assert_eq!(
+                pack_error("p.yaml", &one_rule(&format!("    severity: {}\n", severity))),
+                "Invalid rule pack p.yaml: rule 'r' severity must be 1..=5"
+            );
+        }
+        assert_eq!(pack_error("p.yaml", "rules: []\n"), "Invalid rule pack p.yaml: no rules defined");
+    }
+
+    #[test]
+    fn matcher_needs_exactly_one_kind() {
+        let both = one_rule(
+            "    severity: 2\n    matcher:\n      pattern: x\n      resource_type: A/b\n      missing_property: p\n",
+        );
+        assert_eq!(
+            pack_error("p.yaml", &both),
+            "Invalid rule pack p.yaml: rule 'r': matcher needs either `pattern`, or `resource_type` with `missing_property`"
+        );
+        let no_property = one_rule("    severity: 2\n    matcher:\n      resource_type: A/b\n");
+        assert!(pack_error("p.yaml", &no_property).contains("matcher needs either"));
+        let bad_regex = one_rule("    severity: 2\n    matcher:\n      pattern: '('\n");
+        assert!(pack_error("p.yaml", &bad_regex).contains("invalid matcher pattern"));
+    }
+
+    #[test]
+    fn pattern_columns_count_characters() {
+        let re = Regex::new("http://[a-z.]+").unwrap();
+        let hits = match_pattern(&re, "param a string\nvar ünïcode = 'http://example.com'\n");
+        assert_eq!(hits.len(), 1);
+        let (subject, location) = &hits[0];
+        assert_eq!(subject, "`http://example.com`");
+        assert_eq!(
+            (location.start_line, location.start_column, location.end_line, location.end_column),
+            (2, 16, 2, 34)
+        );
+    }
+
+    #[test]
+    fn missing_property_follows_dotted_paths_through_if_and_for() {
+        let src = "\
+resource set 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'a'
+  properties: {
+    minimumTlsVersion: 'TLS1_2'
+  }
+}
+resource unset 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'b'
+  properties: {}
+}
+resource noProps 'Microsoft.Storage/storageAccounts@2023-01-01' = if (true) {
+  name: 'c'
+}
+resource loop 'Microsoft.Storage/storageAccounts@2023-01-01' = [for i in range(0, 2): {
+  name: 'd${i}'
+  properties: {
+    minimumTlsVersion: 'TLS1_2'
+  }
+}]
+resource opaque 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'e'
+  properties: storageProperties
+}
+resource old 'Microsoft.Storage/storageAccounts@2023-01-01' existing = {
+  name: 'f'
+}
+";
+        assert_eq!(
+            findings(TLS, src),
+            vec![
+                ("Resource `unset` does not set `properties.minimumTlsVersion`: Set a minimum TLS version".to_string(), 7),
+                ("Resource `noProps` does not set `properties.minimumTlsVersion`: Set a minimum TLS version".to_string(), 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn spread_and_interpolated_keys_count_as_set() {
+        let src = "\
+var common = {
+  minimumTlsVersion: 'TLS1_2'
+}
+resource st 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'st'
+  properties: {
+    ...common
+  }
+}
+resource keyed 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'keyed'
+  properties: {
+    '${setting}': 'TLS1_2'
+  }
+}
+";
+        assert!(findings(TLS, src).is_empty());
+    }
+
+    #[test]
+    fn checks_child_resources_nested_in_their_parent() {
+        let blob = "\
+rules:
+  - id: retention
+    category: Resources
+    severity: 3
+    description: Enable soft delete
+    matcher:
+      resource_type: Microsoft.Storage/storageAccounts/blobServices
+      missing_property: properties.deleteRetentionPolicy
+";
+        let src = "\
+resource sa 'Microsoft.Storage/storageAccounts@2023-01-01' = {
+  name: 'sa'
+  resource blob 'blobServices' = {
+    name: 'default'
+  }
+}
+resource blob2 'Microsoft.Storage/storageAccounts/blobServices@2023-01-01' = {
+  parent: sa
+  name: 'default'
+}
+";
+        let lines: Vec<usize> = findings(blob, src).into_iter().map(|(_, line)| line).collect();
+        assert_eq!(lines, vec![3, 7]);
+    }
+}
@@ -0,0 +1,454 @@
+// rules.rs
+//
+// Deterministic checks for the mechanically verifiable parts of
+// bicep-best-practices.md. They run without any network access and emit
+// the same ValidationResult shape as the LLM review.
+
+use crate::ast::*;
+use crate::models::{Location, ValidationResult};
+use std::collections::HashSet;
+
+/// A single violation reported by a rule
+pub struct RuleHit {
+    pub finding: String,
+    pub impact: String,
+    pub span: Span,
+}
+
+/// Built-in rule definition
+pub struct BuiltinRule {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub category: &'static str,
+    pub severity: u8,
+    /// Needs every reference in the file, so it is skipped when statements
+    /// were dropped by syntax errors and would otherwise report false positives
+    pub needs_references: bool,
+    pub check: fn(&Program) -> Vec<RuleHit>,
+}
+
+pub const BUILTIN_RULES: &[BuiltinRule] = &[
+    BuiltinRule {
+        id: "naming/lower-camel-case",
+        description: "Use lowerCamelCase for symbolic names",
+        category: "Naming",
+        severity: 3,
+        needs_references: false,
+        check: check_lower_camel_case,
+    },
+    BuiltinRule {
+        id: "naming/no-name-in-symbolic-name",
+        description: "Avoid using 'name' in resource symbolic names",
+        category: "Naming",
+        severity: 2,
+        needs_references: false,
+        check: check_name_in_symbolic_name,
+    },
+    BuiltinRule {
+        id: "parameters/description-required",
+        description: "Describe every parameter with @description",
+        category: "Parameters",
+        severity: 3,
+        needs_references: false,
+        check: check_param_description,
+    },
+    BuiltinRule {
+        id: "parameters/declare-at-top",
+        description: "Declare parameters at the top of the file",
+        category: "Parameters",
+        severity: 2,
+        needs_references: true,
+        check: check_params_at_top,
+    },
+    BuiltinRule {
+        id: "parameters/no-unused",
+        description: "Remove unused parameters",
+        category: "Parameters",
+        severity: 3,
+        needs_references: true,
+        check: check_unused_params,
+    },
+    BuiltinRule {
+        id: "variables/no-unused",
+        description: "Remove unused variables",
+        category: "Variables",
+        severity: 2,
+        needs_references: true,
+        check: check_unused_vars,
+    },
+    BuiltinRule {
+        id: "resources/no-hardcoded-location",
+        description: "Do not hard-code resource locations",
+        category: "Resources",
+        severity: 3,
+        needs_references: false,
+        check: check_hardcoded_location,
+    },
+    BuiltinRule {
+        id: "outputs/no-secrets",
+        description: "Never expose sensitive data in outputs",
+        category: "Outputs",
+        severity: 4,
+        needs_references: false,
+        check: check_output_secrets,
+    },
+];
+
+/// Run every built-in rule against a parsed file
+pub fn run_rules(program: &Program) -> Vec<ValidationResult> {
+    let mut out = Vec::new();
+    for rule in BUILTIN_RULES {
+        if rule.needs_references && !program.errors.is_empty() {
+            continue;
+        }
+        let mut hits = (rule.check)(program);
+        hits.sort_by_key(|h| h.span.start.offset);
+        for hit in hits {
+            out.push(ValidationResult {
+                category: rule.category.to_string(),
+                finding: hit.finding,
+                severity: rule.severity,
+                impact: hit.impact,
+                rule_id: Some(rule.id.to_string()),
+                location: Some(Location {
+                    start_line: hit.span.start.line,
+                    start_column: hit.span.start.column,
+                    end_line: hit.span.end.line,
+                    end_column: hit.span.end.column,
+                    ..Default::default()
+                }),
+            });
+        }
+    }
+    out
+}
+
+// ------------------------------------------------------------
+// Helpers
+
+fn is_lower_camel_case(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Whether `name` contains the word "name" as a camelCase segment:
+/// `storageName` and `nameSuffix` do, `serviceBusNamespace` and `renamed`
+/// do not
+fn has_name_segment(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    (0..bytes.len()).any(|i| {
+        let starts_word = match &bytes[i..] {
+            [b'N', b'a', b'm', b'e', ..] => true,
+            [b'n', b'a', b'm', b'e', ..] => i == 0 || !bytes[i - 1].is_ascii_alphanumeric(),
+            _ => false,
+        };
+        starts_word && !bytes.get(i + 4).is_some_and(u8::is_ascii_lowercase)
+    })
+}
+
+/// Names that appear as identifiers anywhere in an expression
+fn referenced_names(program: &Program) -> HashSet<String> {
+    let mut names = HashSet::new();
+    program.walk_exprs(&mut |e| {
+        if let ExprKind::Ident(n) = &e.kind {
+            names.insert(n.clone());
+        }
+    });
+    names
+}
+
+// ------------------------------------------------------------
+// Rules
+
+fn check_lower_camel_case(program: &Program) -> Vec<RuleHit> {
+    program
+        .symbols()
+        .into_iter()
+        .filter(|(_, ident)| !is_lower_camel_case(&ident.name))
+        .map(|(kind, ident)| RuleHit {
+            finding: format!(
+                "{} symbolic name `{}` is not lowerCamelCase.",
+                kind, ident.name
+            ),
+            impact: "Inconsistent naming makes templates harder to read and maintain.".to_string(),
+            span: ident.span,
+        })
+        .collect()
+}
+
+fn check_name_in_symbolic_name(program: &Program) -> Vec<RuleHit> {
+    program
+        .symbols()
+        .into_iter()
+        .filter(|(kind, ident)| {
+            matches!(*kind, "Resource" | "Module") && has_name_segment(&ident.name)
+        })
+        .map(|(kind, ident)| RuleHit {
+            finding: format!(
+                "{} symbolic name `{}` contains 'name'.",
+                kind, ident.name
+            ),
+            impact: format!(
+                "The symbolic name represents the resource, not its name; `{}.name` reads confusingly.",
+                ident.name
+            ),
+            span: ident.span,
+        })
+        .collect()
+}
+
+fn check_param_description(program: &Program) -> Vec<RuleHit> {
+    program
+        .params()
+        .filter(|p| find_decorator(&p.decorators, "description").is_none())
+        .map(|p| RuleHit {
+            finding: format!("Parameter `{}` has no @description decorator.", p.name.name),
+            impact: "Users deploying the template get no guidance about the parameter's purpose."
+                .to_string(),
+            span: p.name.span,
+        })
+        .collect()
+}
+
+fn check_params_at_top(program: &Program) -> Vec<RuleHit> {
+    let mut seen_body = false;
+    let mut hits = Vec::new();
+    for stmt in &program.statements {
+        match stmt {
+            Statement::Param(p) if seen_body => hits.push(RuleHit {
+                finding: format!(
+                    "Parameter `{}` is declared after variables, resources or outputs.",
+                    p.name.name
+                ),
+                impact: "Parameters scattered through the file are easy to miss when deploying."
+                    .to_string(),
+                span: p.name.span,
+            }),
+            Statement::Param(_)
+            | Statement::TargetScope(_)
+            | Statement::Metadata(_)
+            | Statement::Type(_)
+            | Statement::Other(_) => {}
+            _ => seen_body = true,
+        }
+    }
+    hits
+}
+
+fn check_unused_params(program: &Program) -> Vec<RuleHit> {
+    let used = referenced_names(program);
+    program
+        .params()
+        .filter(|p| !used.contains(&p.name.name))
+        .map(|p| RuleHit {
+            finding: format!("Parameter `{}` is declared but never used.", p.name.name),
+            impact: "Unused parameters add noise and force callers to supply meaningless values."
+                .to_string(),
+            span: p.name.span,
+        })
+        .collect()
+}
+
+fn check_unused_vars(program: &Program) -> Vec<RuleHit> {
+    let used = referenced_names(program);
+    program
+        .vars()
+        .filter(|v| !used.contains(&v.name.name))
+        .map(|v| RuleHit {
+            finding: format!("Variable `{}` is declared but never used.", v.name.name),
+            impact: "Dead variables make the template harder to follow.".to_string(),
+            span: v.name.span,
+        })
+        .collect()
+}
+
+fn is_hardcoded_location(expr: &Expr) -> bool {
+    match expr.as_plain_string() {
+        Some(s) => !s.eq_ignore_ascii_case("global"),
+        None => false,
+    }
+}
+
+fn check_hardcoded_location(program: &Program) -> Vec<RuleHit> {
+    fn from_resource(res: &ResourceDecl, hits: &mut Vec<RuleHit>) {
+        if let Some(obj) = res.body_object() {
+            if let Some(prop) = obj.get("location") {
+                if is_hardcoded_location(&prop.value) {
+                    hits.push(RuleHit {
+                        finding: format!(
+                            "Resource `{}` uses a hard-coded location '{}'.",
+                            res.name.name,
+                            prop.value.as_plain_string().unwrap_or_default()
+                        ),
+                        impact: "The template cannot be deployed to other regions without editing it."
+                            .to_string(),
+                        span: prop.span,
+                    });
+                }
+            }
+            for child in &obj.resources {
+                from_resource(child, hits);
+            }
+        }
+    }
+
+    let mut hits = Vec::new();
+    for res in program.resources() {
+        from_resource(res, &mut hits);
+    }
+    for module in program.modules() {
+        let params = module
+            .body_object()
+            .and_then(|o| o.get("params"))
+            .and_then(|p| p.value.innermost_object());
+        if let Some(prop) = params.and_then(|p| p.get("location")) {
+            if is_hardcoded_location(&prop.value) {
+                hits.push(RuleHit {
+                    finding: format!(
+                        "Module `{}` passes a hard-coded location.",
+                        module.name.name
+                    ),
+                    impact: "The template cannot be deployed to other regions without editing it."
+                        .to_string(),
+                    span: prop.span,
+                });
+            }
+        }
+    }
+    for param in program.params() {
+        if !param.name.name.to_lowercase().contains("location") {
+            continue;
+        }
+        if let Some(default) = &param.default {
+            if is_hardcoded_location(default) {
+                hits.push(RuleHit {
+                    finding: format!(
+                        "Parameter `{}` defaults to a hard-coded location.",
+                        param.name.name
+                    ),
+                    impact: "Default to resourceGroup().location so deployments follow the target resource group."
+                        .to_string(),
+                    span: default.span,
+                });
+            }
+        }
+    }
+    hits
+}
+
+fn check_output_secrets(program: &Program) -> Vec<RuleHit> {
+    const SENSITIVE: [&str; 5] = ["password", "secret", "connectionstring", "accountkey", "token"];
+
+    let mut hits = Vec::new();
+    for output in program.outputs() {
+        if find_decorator(&output.decorators, "secure").is_some() {
+            continue;
+        }
+        let lower = output.name.name.to_lowercase();
+        let sensitive_name = SENSITIVE.iter().any(|s| lower.contains(s));
+
+        let mut calls_list = false;
+        output.value.walk(&mut |e| {
+            if let Some(name) = e.call_name() {
+                if name.starts_with("list") {
+                    calls_list = true;
+                }
+            }
+        });
+
+        if sensitive_name || calls_list {
+            hits.push(RuleHit {
+                finding: format!("Output `{}` may expose sensitive data.", output.name.name),
+                impact: "Deployment outputs are stored in plain text in the deployment history."
+                    .to_string(),
+                span: output.name.span,
+            });
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Findings of one rule as (start line, finding)
+    fn hits(rule_id: &str, src: &str) -> Vec<(usize, String)> {
+        let program = parse(src).unwrap();
+        run_rules(&program)
+            .into_iter()
+            .filter(|f| f.rule_id.as_deref() == Some(rule_id))
+            .map(|f| (f.location.unwrap().start_line, f.finding))
+            .collect()
+    }
+
+    fn lines(rule_id: &str, src: &str) -> Vec<usize> {
+        hits(rule_id, src).into_iter().map(|(line, _)| line).collect()
+    }
+
+    #[test]
+    fn name_segment_matches_camel_case_words_only() {
+        assert!(has_name_segment("name"));
+        assert!(has_name_segment("storageName"));
+        assert!(has_name_segment("nameSuffix"));
+        assert!(has_name_segment("storageName2"));
+        assert!(!has_name_segment("serviceBusNamespace"));
+        assert!(!has_name_segment("eventHubNamespace"));
+        assert!(!has_name_segment("renamedStorage"));
+    }
+
+    #[test]
+    fn flags_name_in_resource_symbolic_names() {
+        let src = "resource storageName 'Microsoft.Storage/storageAccounts@2023-01-01' = {\n  name: 'st'\n}\n\
+                   resource serviceBusNamespace 'Microsoft.ServiceBus/namespaces@2022-10-01-preview' = {\n  name: 'sb'\n}\n\
+                   resource eventHubNamespace 'Microsoft.EventHub/namespaces@2024-01-01' = {\n  name: 'eh'\n}\n";
+        assert_eq!(lines("naming/no-name-in-symbolic-name", src), vec![1]);
+    }
+
+    #[test]
+    fn reports_unused_params_and_vars() {
+        let src = "param used string\nparam unused string\nvar prefix = used\nvar dead = 'x'\noutput p string = prefix\n";
+        assert_eq!(
+            hits("parameters/no-unused", src),
+            vec![(2, "Parameter `unused` is declared but never used.".to_string())]
+        );
+        assert_eq!(
+            hits("variables/no-unused", src),
+            vec![(4, "Variable `dead` is declared but never used.".to_string())]
+        );
+    }
+
+    #[test]
+    fn reference_rules_skip_files_with_syntax_errors() {
+        let src = "param unused string\nvar = \n";
+        let program = parse(src).unwrap();
+        assert!(!program.errors.is_empty());
+        assert!(lines("parameters/no-unused", src).is_empty());
+    }
+
+    #[test]
+    fn flags_hard_coded_locations() {
+        let src = "param location string = 'westeurope'\n\
+                   param region string = resourceGroup().location\n\
+                   resource st 'Microsoft.Storage/storageAccounts@2023-01-01' = {\n  name: 'st'\n  location: 'westeurope'\n}\n\
+                   resource dns 'Microsoft.Network/dnsZones@2018-05-01' = {\n  name: 'contoso.com'\n  location: 'global'\n}\n\
+                   resource app 'Microsoft.Web/sites@2022-09-01' = {\n  name: 'app'\n  location: region\n}\n\
+                   module mod './mod.bicep' = {\n  name: 'mod'\n  params: {\n    location: 'eastus'\n  }\n}\n";
+        assert_eq!(lines("resources/no-hardcoded-location", src), vec![1, 5, 18]);
+    }
+
+    #[test]
+    fn flags_outputs_with_secrets() {
+        let src = "resource st 'Microsoft.Storage/storageAccounts@2023-01-01' existing = {\n  name: 'st'\n}\n\
+                   output adminPassword string = 'x'\n\
+                   output keys object = st.listKeys()\n\
+                   @secure()\noutput connectionString string = 'y'\n\
+                   output endpoint string = st.properties.primaryEndpoints.blob\n";
+        assert_eq!(lines("outputs/no-secrets", src), vec![4, 5]);
+    }
+}
@@ -0,0 +1,305 @@
+// sarif.rs
+//
+// SARIF 2.1.0 rendering for code-scanning dashboards.
+
+use crate::catalog::{slug, PracticeRule, RuleCatalog};
+use crate::models::{Location, ValidationResult};
+use crate::rules::BUILTIN_RULES;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+pub const TOOL_NAME: &str = "bicep-reviewer";
+const TOOL_URI: &str = "https://github.com/aymenfurter/bicep-reviewer";
+
+/// Map severity 1-5 onto SARIF result levels
+pub fn sarif_level(severity: u8) -> &'static str {
+    match severity {
+        4..=5 => "error",
+        3 => "warning",
+        _ => "note",
+    }
+}
+
+/// Rule id for a finding; LLM findings are grouped by category
+pub fn rule_id(finding: &ValidationResult) -> String {
+    match &finding.rule_id {
+        Some(id) => id.clone(),
+        None => format!("llm/{}", slug(&finding.category)),
+    }
+}
+
+/// Build a SARIF log for the filtered findings; suppressed findings are
+/// included with an `inSource` suppression so dashboards show them as dismissed
+pub fn build_sarif(
+    findings: &[&ValidationResult],
+    suppressed: &[ValidationResult],
+    catalog: &RuleCatalog,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let all: Vec<&ValidationResult> = findings
+        .iter()
+        .copied()
+        .chain(suppressed.iter())
+        .collect();
+
+    // Rule descriptors keyed by id, in a stable order
+    let mut rules: BTreeMap<String, Value> = BTreeMap::new();
+    for f in &all {
+        let id = rule_id(f);
+        if rules.contains_key(&id) {
+            continue;
+        }
+        rules.insert(id.clone(), rule_descriptor(&id, f, catalog));
+    }
+    let rule_index: BTreeMap<&String, usize> =
+        rules.keys().enumerate().map(|(i, id)| (id, i)).collect();
+
+    let results: Vec<Value> = all
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let id = rule_id(f);
+            let mut result = json!({
+                "ruleId": id,
+                "ruleIndex": rule_index[&id],
+                "level": sarif_level(f.severity),
+                "message": { "text": format!("{} {}", f.finding, f.impact) },
+                "properties": {
+                    "category": f.category,
+                    "severity": f.severity,
+                },
+            });
+            if i >= findings.len() {
+                result["suppressions"] = json!([{ "kind": "inSource" }]);
+            }
+            if let Some(loc) = &f.location {
+                result["locations"] = json!([{ "physicalLocation": physical_location(loc) }]);
+            }
+            result
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "informationUri": TOOL_URI,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.into_values().collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }]
+    });
+
+    Ok(serde_json::to_string_pretty(&log)?)
+}
+
+fn physical_location(loc: &Location) -> Value {
+    let mut physical = json!({
+        "artifactLocation": {
+            "uri": loc.file_path.clone().unwrap_or_default().replace('\\', "/"),
+        },
+    });
+    if loc.is_file_level() {
+        return physical;
+    }
+    let mut region = json!({
+        "startLine": loc.start_line,
+        "endLine": loc.end_line.max(loc.start_line),
+    });
+    if loc.start_column > 0 {
+        region["startColumn"] = json!(loc.start_column);
+    }
+    if loc.end_column > 0 {
+        region["endColumn"] = json!(loc.end_column);
+    }
+    if let Some(snippet) = &loc.snippet {
+        region["snippet"] = json!({ "text": snippet });
+    }
+    physical["region"] = region;
+    physical
+}
+
+fn rule_descriptor(id: &str, finding: &ValidationResult, catalog: &RuleCatalog) -> Value {
+    // Rule-pack rules describe themselves
+    if let Some(rule) = catalog.rule(id) {
+        return pack_rule_descriptor(rule, finding);
+    }
+
+    let short = BUILTIN_RULES
+        .iter()
+        .find(|r| r.id == id)
+        .map(|r| r.description.to_string())
+        .unwrap_or_else(|| format!("{} best practices", finding.category));
+
+    let mut descriptor = json!({
+        "id": id,
+        "name": slug(id),
+        "shortDescription": { "text": short },
+        "defaultConfiguration": { "level": sarif_level(finding.severity) },
+        "properties": { "category": finding.category },
+    });
+
+    let practices = catalog.rules_for(&finding.category);
+    if !practices.is_empty() {
+        let text: Vec<&str> = practices.iter().map(|r| r.text.as_str()).collect();
+        let markdown: Vec<String> = practices
+            .iter()
+            .map(|r| {
+                let mut item = format!("- {}", r.text);
+                for ex in &r.examples {
+                    item.push_str(&format!("\n```{}\n{}\n```", ex.language, ex.code));
+                }
+                item
+            })
+            .collect();
+        descriptor["help"] = json!({
+            "text": text.join("\n"),
+            "markdown": markdown.join("\n"),
+        });
+    }
+    if let Some(uri) = catalog.first_link(&finding.category) {
+        descriptor["helpUri"] = json!(uri);
+    }
+    descriptor
+}
+
+fn pack_rule_descriptor(rule: &PracticeRule, finding: &ValidationResult) -> Value {
+    let severity = rule.severity.unwrap_or(finding.severity);
+    let mut text = rule.text.clone();
+    let mut markdown = rule.text.clone();
+    if let Some(rationale) = &rule.rationale {
+        text.push_str(&format!("\n{}", rationale));
+        markdown.push_str(&format!("\n\n{}", rationale));
+    }
+    for ex in &rule.examples {
+        markdown.push_str(&format!(
+            "\n\n{}:\n```{}\n{}\n```",
+            ex.label.as_deref().unwrap_or("Example"),
+            ex.language,
+            ex.code
+        ));
+    }
+
+    let mut descriptor = json!({
+        "id": rule.id,
+        "name": slug(&rule.id),
+        "shortDescription": { "text": rule.text },
+        "help": { "text": text, "markdown": markdown },
+        "defaultConfiguration": { "level": sarif_level(severity) },
+        "properties": { "category": finding.category, "severity": severity },
+    });
+    if let Some(uri) = rule.links.first() {
+        descriptor["helpUri"] = json!(uri);
+    }
+    descriptor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(category: &str, rule_id: Option<&str>, location: Option<Location>) -> ValidationResult {
+        ValidationResult {
+            category: category.to_string(),
+            finding: format!("{} finding", category),
+            severity: 3,
+            impact: "Impact.".to_string(),
+            rule_id: rule_id.map(|id| id.to_string()),
+            location,
+        }
+    }
+
+    fn line(line: usize, start_column: usize, end_column: usize) -> Location {
+        Location {
+            file_path: Some("infra\\main.bicep".to_string()),
+            start_line: line,
+            start_column,
+            end_line: line,
+            end_column,
+            snippet: Some("param a string".to_string()),
+        }
+    }
+
+    fn sarif(findings: &[ValidationResult], suppressed: &[ValidationResult]) -> Value {
+        let catalog =
+            RuleCatalog::load("bicep-best-practices.md", include_str!("../bicep-best-practices.md")).unwrap();
+        let refs: Vec<&ValidationResult> = findings.iter().collect();
+        serde_json::from_str(&build_sarif(&refs, suppressed, &catalog).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rule_index_points_at_the_rule_of_each_result() {
+        let findings = vec![
+            finding("Variables", Some("variables/no-unused"), Some(line(2, 5, 9))),
+            finding("Parameters", None, Some(line(1, 7, 8))),
+            finding("Variables", Some("variables/no-unused"), Some(line(4, 5, 9))),
+        ];
+        let suppressed = vec![finding("Naming", Some("naming/lower-camel-case"), Some(line(6, 0, 0)))];
+        let log = sarif(&findings, &suppressed);
+        let run = &log["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        let ids: Vec<&str> = rules.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["llm/parameters", "naming/lower-camel-case", "variables/no-unused"]);
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 4);
+        for result in results {
+            let index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], result["ruleId"]);
+        }
+        assert_eq!(
+            rules[0]["helpUri"],
+            "https://learn.microsoft.com/en-us/azure/azure-resource-manager/bicep/parameters"
+        );
+        assert!(rules[0]["help"]["text"].as_str().is_some_and(|t| !t.is_empty()));
+    }
+
+    #[test]
+    fn suppressed_findings_carry_an_in_source_suppression() {
+        let findings = vec![finding("Parameters", None, None)];
+        let suppressed = vec![finding("Parameters", Some("parameters/no-unused"), Some(line(3, 7, 8)))];
+        let log = sarif(&findings, &suppressed);
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert!(results[0].get("suppressions").is_none());
+        assert_eq!(results[1]["suppressions"], json!([{ "kind": "inSource" }]));
+        // Findings without a location have no locations at all
+        assert!(results[0].get("locations").is_none());
+    }
+
+    #[test]
+    fn file_level_findings_omit_the_region() {
+        let whole_file = Location::file_level("infra/main.bicep");
+        assert_eq!(
+            physical_location(&whole_file),
+            json!({ "artifactLocation": { "uri": "infra/main.bicep" } })
+        );
+
+        assert_eq!(
+            physical_location(&line(3, 7, 8)),
+            json!({
+                "artifactLocation": { "uri": "infra/main.bicep" },
+                "region": {
+                    "startLine": 3,
+                    "endLine": 3,
+                    "startColumn": 7,
+                    "endColumn": 8,
+                    "snippet": { "text": "param a string" },
+                },
+            })
+        );
+        let without_columns = physical_location(&line(3, 0, 0));
+        assert!(without_columns["region"].get("startColumn").is_none());
+        assert!(without_columns["region"].get("endColumn").is_none());
+    }
+
+    #[test]
+    fn maps_severity_to_levels() {
+        let levels: Vec<&str> = (1..=5).map(sarif_level).collect();
+        assert_eq!(levels, vec!["note", "note", "warning", "error", "error"]);
+    }
+}
@@ -0,0 +1,232 @@
+// suppress.rs
+//
+// Inline suppression comments for accepted deviations:
+//
+//   // bicep-reviewer-disable-next-line outputs/no-secrets -- reviewed, output is @secure in v2
+//   // bicep-reviewer-disable Naming
+//
+// `disable-next-line` applies to the next line of code, `disable` to the
+// whole file. Each takes rule ids or category names; none means everything.
+// Text after `--` is a free-form justification.
+
+use crate::ast::{Comment, CommentKind, Program};
+use crate::models::ValidationResult;
+use std::collections::HashMap;
+
+const DIRECTIVE: &str = "bicep-reviewer-disable";
+const NEXT_LINE: &str = "-next-line";
+
+/// Rules or categories named by one comment; empty means all findings
+#[derive(Debug, Clone, Default)]
+struct Selector {
+    targets: Vec<String>,
+}
+
+impl Selector {
+    fn parse(text: &str) -> Self {
+        let text = text.split("--").next().unwrap_or_default();
+        Selector {
+            targets: text
+                .split([',', ' ', '\t'])
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+        }
+    }
+
+    fn matches(&self, finding: &ValidationResult) -> bool {
+        self.targets.is_empty()
+            || self.targets.iter().any(|t| {
+                finding.rule_id.as_deref() == Some(t.as_str())
+                    || finding.category.eq_ignore_ascii_case(t)
+            })
+    }
+}
+
+/// Suppression comments of one file
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    file: Vec<Selector>,
+    /// 1-based target line and its selector
+    lines: Vec<(usize, Selector)>,
+}
+
+impl Suppressions {
+    /// Collect directives from the `//` comments the parser found, so
+    /// `//` inside multi-line strings is left alone. Without a parse, lines
+    /// starting with `//` are taken as comments.
+    pub fn new(content: &str, program: Option<&Program>) -> Self {
+        match program {
+            Some(program) => Self::from_comments(content, &program.comments),
+            None => Self::parse(content),
+        }
+    }
+
+    /// Directives from parsed comments that start a line
+    fn from_comments(content: &str, comments: &[Comment]) -> Self {
+        let own_line: HashMap<usize, &str> = comments
+            .iter()
+            .filter(|c| c.kind == CommentKind::Line)
+            .filter(|c| {
+                let before = &content[..c.span.start.offset];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                before[line_start..].trim().is_empty()
+            })
+            .map(|c| (c.span.start.line, c.text.as_str()))
+            .collect();
+        Self::collect(content, &own_line)
+    }
+
+    /// Directives from `//` comments that start a line, by scanning the raw
+    /// text
+    fn parse(content: &str) -> Self {
+        let own_line: HashMap<usize, &str> = content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| line.trim().strip_prefix("//").map(|c| (i + 1, c)))
+            .collect();
+        Self::collect(content, &own_line)
+    }
+
+    /// Read directives from the comments on their own line, keyed by 1-based
+    /// line, and point `disable-next-line` at the next line of code
+    fn collect(content: &str, own_line: &HashMap<usize, &str>) -> Self {
+        let mut out = Suppressions::default();
+        let mut pending: Vec<Selector> = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            if let Some(comment) = own_line.get(&(i + 1)) {
+                let comment = comment.trim();
+                if let Some(rest) = comment.strip_prefix(DIRECTIVE) {
+                    match rest.strip_prefix(NEXT_LINE) {
+                        Some(args) if args.is_empty() || args.starts_with(char::is_whitespace) => {
+                            pending.push(Selector::parse(args))
+                        }
+                        None if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                            out.file.push(Selector::parse(rest))
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            // Blank lines between the directive and the code are skipped
+            if line.trim().is_empty() {
+                continue;
+            }
+            for selector in pending.drain(..) {
+                out.lines.push((i + 1, selector));
+            }
+        }
+        out
+    }
+
+    fn is_empty(&self) -> bool {
+        self.file.is_empty() && self.lines.is_empty()
+    }
+
+    /// Whether a finding with a resolved location is suppressed
+    pub fn suppresses(&self, finding: &ValidationResult) -> bool {
+        if self.file.iter().any(|s| s.matches(finding)) {
+            return true;
+        }
+        let line = match &finding.location {
+            Some(loc) if !loc.is_file_level() => loc.start_line,
+            _ => return false,
+        };
+        self.lines
+            .iter()
+            .any(|(target, s)| *target == line && s.matches(finding))
+    }
+}
+
+/// Split a file's findings into reported and suppressed ones
+pub fn partition(
+    findings: Vec<ValidationResult>,
+    content: &str,
+    program: Option<&Program>,
+) -> (Vec<ValidationResult>, Vec<ValidationResult>) {
+    let suppressions = Suppressions::new(content, program);
+    if suppressions.is_empty() {
+        return (findings, Vec::new());
+    }
+    findings
+        .into_iter()
+        .partition(|f| !suppressions.suppresses(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Location;
+
+    fn finding(category: &str, rule_id: Option<&str>, line: usize) -> ValidationResult {
+        ValidationResult {
+            category: category.to_string(),
+            finding: "f".to_string(),
+            severity: 3,
+            impact: "i".to_string(),
+            rule_id: rule_id.map(str::to_string),
+            location: Some(Location {
+                start_line: line,
+                end_line: line,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn next_line_targets_following_code() {
+        let content = "// bicep-reviewer-disable-next-line outputs/no-secrets -- reviewed\n\n// note\noutput key string = k\noutput other string = k\n";
+        let s = Suppressions::parse(content);
+        assert!(s.suppresses(&finding("Outputs", Some("outputs/no-secrets"), 4)));
+        assert!(!s.suppresses(&finding("Outputs", Some("outputs/no-secrets"), 5)));
+        assert!(!s.suppresses(&finding("Outputs", Some("outputs/other"), 4)));
+    }
+
+    #[test]
+    fn file_directive_matches_categories_case_insensitively() {
+        let s = Suppressions::parse("param a string\n  // bicep-reviewer-disable naming, Parameters\n");
+        assert!(s.suppresses(&finding("Naming", None, 1)));
+        assert!(s.suppresses(&finding("Parameters", Some("parameters/no-unused"), 1)));
+        assert!(!s.suppresses(&finding("Outputs", None, 1)));
+    }
+
+    #[test]
+    fn bare_directive_suppresses_everything() {
+        let s = Suppressions::parse("// bicep-reviewer-disable-next-line\nvar a = 1\n");
+        assert!(s.suppresses(&finding("Variables", None, 2)));
+        assert!(!s.suppresses(&finding("Variables", None, 0)));
+    }
+
+    #[test]
+    fn justification_is_not_a_target() {
+        let s = Suppressions::parse("// bicep-reviewer-disable -- Naming is fine here\n");
+        assert!(s.suppresses(&finding("Outputs", None, 3)));
+    }
+
+    #[test]
+    fn ignores_similar_comments() {
+        let content = "// bicep-reviewer-disabled Naming\nvar a = 1 // bicep-reviewer-disable\n/* bicep-reviewer-disable */\n";
+        let s = Suppressions::parse(content);
+        assert!(s.is_empty());
+        let (kept, suppressed) = partition(vec![finding("Naming", None, 2)], content, None);
+        assert_eq!((kept.len(), suppressed.len()), (1, 0));
+    }
+
+    #[test]
+    fn uses_parsed_comments_when_available() {
+        let content = "var script = '''\n// bicep-reviewer-disable\n'''\n\
+                       // bicep-reviewer-disable-next-line Naming\n\
+                       // other note\n\
+                       var Bad = script // bicep-reviewer-disable\n";
+        let program = crate::parser::parse(content).unwrap();
+        let s = Suppressions::new(content, Some(&program));
+        assert!(s.file.is_empty());
+        assert!(s.suppresses(&finding("Naming", None, 6)));
+        assert!(!s.suppresses(&finding("Variables", None, 6)));
+
+        // The raw scan cannot tell the string apart from a comment
+        assert!(!Suppressions::parse(content).file.is_empty());
+    }
+}
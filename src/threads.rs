@@ -0,0 +1,416 @@
+// threads.rs
+//
+// Keeps the PR threads the tool owns in step with the latest review. Every
+// comment the tool posts ends with a hidden marker holding a key (for
+// findings, the baseline fingerprint), so a re-run recognises its threads:
+// unchanged findings are left alone, changed ones are edited in place, a
+// finding that comes back reopens its thread, and threads whose finding is
+// gone are marked fixed. Threads a reviewer closed as won't fix, by design
+// or closed are never touched again. The review summary is a single
+// PR-level thread that is edited in place on every run.
+
+use crate::models::{ExistingThread, Thread, ThreadComment, ThreadContext, ThreadStatus};
+use std::collections::{HashMap, HashSet};
+
+const MARKER_START: &str = "<!-- bicep-reviewer:";
+const MARKER_END: &str = " -->";
+const FINDING_PREFIX: &str = "finding:";
+/// Marker key of the review summary thread
+pub const SUMMARY_KEY: &str = "summary";
+
+/// Hidden marker for `key`
+pub fn marker(key: &str) -> String {
+    format!("{}{}{}", MARKER_START, key, MARKER_END)
+}
+
+/// Comment text with the hidden marker for `key` appended
+pub fn with_marker(content: &str, key: &str) -> String {
+    format!("{}\n\n{}", content.trim_end(), marker(key))
+}
+
+/// Key of the marker in a comment posted by the tool
+pub fn marker_key(content: &str) -> Option<&str> {
+    let start = content.rfind(MARKER_START)? + MARKER_START.len();
+    let end = content[start..].find(MARKER_END)? + start;
+    Some(content[start..end].trim())
+}
+
+/// Keys of all markers in a text listing several findings, e.g. a review body
+pub fn marker_keys(content: &str) -> Vec<&str> {
+    let mut keys = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find(MARKER_START) {
+        rest = &rest[start + MARKER_START.len()..];
+        match rest.find(MARKER_END) {
+            Some(end) => {
+                keys.push(rest[..end].trim());
+                rest = &rest[end..];
+            }
+            None => break,
+        }
+    }
+    keys
+}
+
+/// Whether a marker key belongs to a finding rather than the summary
+pub fn is_finding_key(key: &str) -> bool {
+    key.starts_with(FINDING_PREFIX)
+}
+
+/// Marker key for a finding; repeated fingerprints get a `#n` suffix
+pub fn finding_key(fingerprint: &str, seen: &mut HashMap<String, usize>) -> String {
+    let n = seen.entry(fingerprint.to_string()).or_insert(0);
+    *n += 1;
+    if *n == 1 {
+        format!("{}{}", FINDING_PREFIX, fingerprint)
+    } else {
+        format!("{}{}#{}", FINDING_PREFIX, fingerprint, n)
+    }
+}
+
+/// A thread the latest review wants on the PR
+#[derive(Debug)]
+pub struct DesiredThread {
+    pub key: String,
+    /// File and lines the thread is anchored to; `None` for a PR-level thread
+    pub thread_context: Option<ThreadContext>,
+    /// Comment text including the marker
+    pub content: String,
+    pub status: ThreadStatus,
+}
+
+impl DesiredThread {
+    /// Request body that creates the thread
+    pub fn to_thread(&self) -> Thread {
+        Thread {
+            comments: vec![ThreadComment {
+                content: self.content.clone(),
+                comment_type: 1,
+            }],
+            status: self.status,
+            thread_context: self.thread_context.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThreadAction {
+    /// Post the desired thread at this index
+    Create(usize),
+    /// Replace the tool's comment with the desired thread's content
+    UpdateComment {
+        thread_id: i32,
+        comment_id: i32,
+        desired: usize,
+    },
+    SetStatus {
+        thread_id: i32,
+        status: ThreadStatus,
+    },
+    /// Thread is up to date or was settled by a reviewer
+    Keep(i32),
+}
+
+/// What a sync did, for the run log
+#[derive(Debug, Default)]
+pub struct SyncCounts {
+    pub created: usize,
+    pub updated: usize,
+    pub reopened: usize,
+    pub resolved: usize,
+    pub unchanged: usize,
+}
+
+impl SyncCounts {
+    pub fn record(&mut self, action: &ThreadAction) {
+        match action {
+            ThreadAction::Create(_) => self.created += 1,
+            ThreadAction::UpdateComment { .. } => self.updated += 1,
+            ThreadAction::SetStatus {
+                status: ThreadStatus::Fixed,
+                ..
+            } => self.resolved += 1,
+            ThreadAction::SetStatus { .. } => self.reopened += 1,
+            ThreadAction::Keep(_) => self.unchanged += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for SyncCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} new, {} updated, {} reopened, {} resolved, {} unchanged",
+            self.created, self.updated, self.reopened, self.resolved, self.unchanged
+        )
+    }
+}
+
+/// Thread and comment the tool owns, by marker key
+fn owned_threads(existing: &[ExistingThread]) -> Vec<(&str, &ExistingThread, i32, &str)> {
+    let mut out = Vec::new();
+    for thread in existing.iter().filter(|t| !t.is_deleted) {
+        let first = thread.comments.iter().find(|c| !c.is_deleted);
+        if let Some((comment, content)) = first.and_then(|c| c.content.as_deref().map(|t| (c, t))) {
+            if let Some(key) = marker_key(content) {
+                out.push((key, thread, comment.id, content));
+            }
+        }
+    }
+    out
+}
+
+/// Actions that bring the PR's finding threads in line with `desired`
+pub fn plan(existing: &[ExistingThread], desired: &[DesiredThread]) -> Vec<ThreadAction> {
+    let owned = owned_threads(existing);
+    let mut by_key = HashMap::new();
+    for (key, thread, comment_id, content) in &owned {
+        by_key.entry(*key).or_insert((*thread, *comment_id, *content));
+    }
+
+    let mut actions = Vec::new();
+    for (i, d) in desired.iter().enumerate() {
+        let (thread, comment_id, content) = match by_key.get(d.key.as_str()) {
+            Some(found) => *found,
+            None => {
+                actions.push(ThreadAction::Create(i));
+                continue;
+            }
+        };
+        if matches!(
+            thread.status,
+            ThreadStatus::WontFix | ThreadStatus::Closed | ThreadStatus::ByDesign
+        ) {
+            actions.push(ThreadAction::Keep(thread.id));
+            continue;
+        }
+        let changed = content != d.content;
+        if changed {
+            actions.push(ThreadAction::UpdateComment {
+                thread_id: thread.id,
+                comment_id,
+                desired: i,
+            });
+        }
+        if thread.status == ThreadStatus::Fixed {
+            actions.push(ThreadAction::SetStatus {
+                thread_id: thread.id,
+                status: d.status,
+            });
+        } else if !changed {
+            actions.push(ThreadAction::Keep(thread.id));
+        }
+    }
+
+    // Open finding threads that the latest review no longer reports
+    let wanted: HashSet<&str> = desired.iter().map(|d| d.key.as_str()).collect();
+    for (key, thread, _, _) in owned {
+        if is_finding_key(key)
+            && !wanted.contains(key)
+            && matches!(
+                thread.status,
+                ThreadStatus::Active | ThreadStatus::Pending | ThreadStatus::Unknown
+            )
+        {
+            actions.push(ThreadAction::SetStatus {
+                thread_id: thread.id,
+                status: ThreadStatus::Fixed,
+            });
+        }
+    }
+    actions
+}
+
+/// Actions that keep a single thread, such as the summary, at the desired
+/// content and status
+pub fn plan_single(existing: &[ExistingThread], desired: &DesiredThread) -> Vec<ThreadAction> {
+    let found = owned_threads(existing)
+        .into_iter()
+        .find(|(key, ..)| *key == desired.key);
+    let (_, thread, comment_id, content) = match found {
+        Some(found) => found,
+        None => return vec![ThreadAction::Create(0)],
+    };
+    let mut actions = Vec::new();
+    if content != desired.content {
+        actions.push(ThreadAction::UpdateComment {
+            thread_id: thread.id,
+            comment_id,
+            desired: 0,
+        });
+    }
+    if thread.status != desired.status {
+        actions.push(ThreadAction::SetStatus {
+            thread_id: thread.id,
+            status: desired.status,
+        });
+    }
+    if actions.is_empty() {
+        actions.push(ThreadAction::Keep(thread.id));
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ExistingComment, Location};
+
+    fn existing(id: i32, status: ThreadStatus, content: &str) -> ExistingThread {
+        ExistingThread {
+            id,
+            status,
+            is_deleted: false,
+            comments: vec![
+                ExistingComment {
+                    id: id * 10,
+                    content: Some(content.to_string()),
+                    is_deleted: false,
+                },
+                ExistingComment {
+                    id: id * 10 + 1,
+                    content: Some("Reply from a reviewer".to_string()),
+                    is_deleted: false,
+                },
+            ],
+        }
+    }
+
+    fn desired(key: &str, text: &str) -> DesiredThread {
+        DesiredThread {
+            key: key.to_string(),
+            thread_context: Some(ThreadContext::for_location("/main.bicep", None, "")),
+            content: with_marker(text, key),
+            status: ThreadStatus::Active,
+        }
+    }
+
+    #[test]
+    fn reads_markers_back() {
+        let text = with_marker("Finding", "finding:abc");
+        assert_eq!(marker_key(&text), Some("finding:abc"));
+        assert_eq!(marker_key("No marker"), None);
+        let list = format!("- a {}\n- b {}", marker("finding:a"), marker("finding:b#2"));
+        assert_eq!(marker_keys(&list), vec!["finding:a", "finding:b#2"]);
+    }
+
+    #[test]
+    fn numbers_repeated_fingerprints() {
+        let mut seen = HashMap::new();
+        assert_eq!(finding_key("fp", &mut seen), "finding:fp");
+        assert_eq!(finding_key("fp", &mut seen), "finding:fp#2");
+        assert_eq!(finding_key("other", &mut seen), "finding:other");
+    }
+
+    #[test]
+    fn creates_updates_and_keeps() {
+        let a = desired("finding:a", "A");
+        let b = desired("finding:b", "B changed");
+        let c = desired("finding:c", "C");
+        let threads = vec![
+            existing(1, ThreadStatus::Active, &a.content),
+            existing(2, ThreadStatus::Active, &with_marker("B", "finding:b")),
+        ];
+        assert_eq!(
+            plan(&threads, &[a, b, c]),
+            vec![
+                ThreadAction::Keep(1),
+                ThreadAction::UpdateComment {
+                    thread_id: 2,
+                    comment_id: 20,
+                    desired: 1,
+                },
+                ThreadAction::Create(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_threads_whose_finding_is_gone() {
+        let threads = vec![
+            existing(1, ThreadStatus::Active, &with_marker("Gone", "finding:gone")),
+            existing(2, ThreadStatus::Active, &with_marker("Summary", SUMMARY_KEY)),
+            existing(3, ThreadStatus::Active, "A reviewer's own thread"),
+        ];
+        assert_eq!(
+            plan(&threads, &[]),
+            vec![ThreadAction::SetStatus {
+                thread_id: 1,
+                status: ThreadStatus::Fixed,
+            }]
+        );
+    }
+
+    #[test]
+    fn reopens_fixed_thread_when_finding_returns() {
+        let a = desired("finding:a", "A");
+        let threads = vec![existing(1, ThreadStatus::Fixed, &a.content)];
+        assert_eq!(
+            plan(&threads, &[a]),
+            vec![ThreadAction::SetStatus {
+                thread_id: 1,
+                status: ThreadStatus::Active,
+            }]
+        );
+    }
+
+    #[test]
+    fn leaves_threads_closed_by_reviewer_alone() {
+        let a = desired("finding:a", "A changed");
+        let threads = vec![
+            existing(1, ThreadStatus::WontFix, &with_marker("A", "finding:a")),
+            existing(2, ThreadStatus::Closed, &with_marker("Gone", "finding:gone")),
+        ];
+        assert_eq!(plan(&threads, &[a]), vec![ThreadAction::Keep(1)]);
+    }
+
+    #[test]
+    fn single_thread_follows_content_and_status() {
+        let mut summary = desired(SUMMARY_KEY, "2 findings");
+        assert_eq!(plan_single(&[], &summary), vec![ThreadAction::Create(0)]);
+
+        let threads = vec![existing(5, ThreadStatus::Active, &summary.content)];
+        assert_eq!(plan_single(&threads, &summary), vec![ThreadAction::Keep(5)]);
+
+        summary.content = with_marker("No findings", SUMMARY_KEY);
+        summary.status = ThreadStatus::Fixed;
+        assert_eq!(
+            plan_single(&threads, &summary),
+            vec![
+                ThreadAction::UpdateComment {
+                    thread_id: 5,
+                    comment_id: 50,
+                    desired: 0,
+                },
+                ThreadAction::SetStatus {
+                    thread_id: 5,
+                    status: ThreadStatus::Fixed,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn thread_context_without_columns_spans_whole_lines() {
+        let content = "param a string\nvar b = {\n  c: a\n}   \n";
+        let lines = Location {
+            start_line: 2,
+            end_line: 4,
+            ..Default::default()
+        };
+        let context = serde_json::to_value(ThreadContext::for_location("/main.bicep", Some(&lines), content)).unwrap();
+        assert_eq!(context["rightFileStart"], serde_json::json!({ "line": 2, "offset": 1 }));
+        assert_eq!(context["rightFileEnd"], serde_json::json!({ "line": 4, "offset": 2 }));
+
+        let columns = Location {
+            start_column: 7,
+            end_column: 8,
+            ..lines.clone()
+        };
+        let context = serde_json::to_value(ThreadContext::for_location("/main.bicep", Some(&columns), content)).unwrap();
+        assert_eq!(context["rightFileEnd"], serde_json::json!({ "line": 4, "offset": 8 }));
+
+        let file = serde_json::to_value(ThreadContext::for_location("/main.bicep", None, content)).unwrap();
+        assert!(file.get("rightFileStart").is_none());
+    }
+}
@@ -0,0 +1,96 @@
+// azure_devops.rs
+//
+// Runs the `azure` subcommand against a local stub of an Azure DevOps
+// Server collection and checks the URLs it calls and the threads and
+// status it posts.
+
+mod common;
+
+use common::{azure_devops_routes, request_log, route, start_stub, AdoPullRequest};
+use serde_json::{json, Value};
+use std::process::Command;
+
+const REPO: &str = "/tfs/DefaultCollection/proj/_apis/git/repositories";
+
+#[test]
+fn reviews_pull_request_on_a_collection_url_with_api_version() {
+    let (url, recorded) = start_stub(azure_devops_routes(AdoPullRequest {
+        collection: "/tfs/DefaultCollection",
+        api_version: "6.0",
+        path: "/infra/main.bicep",
+        head: "@description('Kept')\nparam kept string\noutput k string = kept\nparam added string\n".to_string(),
+        base: Some("@description('Kept')\nparam kept string\noutput k string = kept\n".to_string()),
+        threads: json!([]),
+    }));
+    let output = Command::new(env!("CARGO_BIN_EXE_bicep_llm_validator"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_clear()
+        .args([
+            "azure",
+            "--organization",
+            &format!("{}/tfs/DefaultCollection/", url),
+            "--project",
+            "proj",
+            "--repository",
+            "repo",
+            "--pull-request-id",
+            "5",
+            "--pat",
+            "pat",
+            "--api-version",
+            "6.0",
+            "--best-practices-file",
+            "bicep-best-practices.md",
+            "--rules-only",
+        ])
+        .output()
+        .expect("run bicep_llm_validator");
+    let recorded = recorded.lock().unwrap();
+    let log = format!(
+        "stderr: {}\nrequests: {:?}",
+        String::from_utf8_lossy(&output.stderr),
+        request_log(&recorded)
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", log);
+
+    // Every call goes to the collection with the requested API version
+    assert!(recorded.iter().all(|(_, p, _)| p.starts_with(REPO) && p.contains("api-version=6.0")), "{}", log);
+    let calls: Vec<_> = recorded
+        .iter()
+        .map(|(m, p, _)| format!("{} {}", m, route(p).trim_start_matches(REPO)))
+        .collect();
+    let pr = "/repo-guid/pullRequests/5";
+    for expected in [
+        "GET /repo".to_string(),
+        format!("GET {}", pr),
+        format!("GET {}/iterations", pr),
+        format!("GET {}/iterations/2/changes", pr),
+        "GET /repo-guid/items".to_string(),
+        "GET /repo-guid/blobs/base-blob".to_string(),
+        format!("GET {}/threads", pr),
+        format!("POST {}/threads", pr),
+        format!("POST {}/statuses", pr),
+    ] {
+        assert!(calls.contains(&expected), "missing {}\n{}", expected, log);
+    }
+
+    // Only the added parameter is in the diff: no description, never used
+    let threads: Vec<&Value> = recorded
+        .iter()
+        .filter(|(m, p, _)| m == "POST" && route(p) == format!("{}{}/threads", REPO, pr))
+        .map(|(_, _, body)| body)
+        .collect();
+    let anchored: Vec<_> = threads
+        .iter()
+        .filter_map(|t| t.get("threadContext"))
+        .map(|c| (c["filePath"].as_str().unwrap(), c["rightFileStart"]["line"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(anchored, vec![("/infra/main.bicep", 4), ("/infra/main.bicep", 4)], "{}", log);
+
+    let status = recorded
+        .iter()
+        .find(|(m, p, _)| m == "POST" && route(p).ends_with("/statuses"))
+        .map(|(_, _, body)| body)
+        .unwrap();
+    assert_eq!(status["state"], "succeeded");
+}
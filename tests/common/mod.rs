@@ -0,0 +1,173 @@
+// common/mod.rs
+//
+// Local stub of a hosting service's REST API for the subcommand tests. Each
+// connection carries one request, which is recorded and answered by the
+// test's route function.
+
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Request the stub received: method, path with query, JSON body
+pub type Recorded = (String, String, Value);
+
+/// Answers a request (method, path with query, JSON body) with a status and body
+pub type Routes = Arc<dyn Fn(&str, &str, &Value) -> (u16, String) + Send + Sync>;
+
+/// Answer one request per connection and record it
+fn handle(stream: TcpStream, routes: &Routes, recorded: &Mutex<Vec<Recorded>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let (status, response) = routes(&method, &path, &body);
+    recorded.lock().unwrap().push((method, path, body));
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    )
+    .unwrap();
+}
+
+/// Stub server on a free port; returns its URL and the recorded requests
+pub fn start_stub(routes: Routes) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let shared = recorded.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let shared = shared.clone();
+            let routes = routes.clone();
+            thread::spawn(move || handle(stream, &routes, &shared));
+        }
+    });
+    (url, recorded)
+}
+
+/// Bodies of the requests sent to `route` with `method`
+pub fn posted<'a>(recorded: &'a [Recorded], method: &str, route: &str) -> Vec<&'a Value> {
+    recorded
+        .iter()
+        .filter(|(m, p, _)| m == method && p == route)
+        .map(|(_, _, body)| body)
+        .collect()
+}
+
+/// Requests as `METHOD path` lines, for assertion messages
+pub fn request_log(recorded: &[Recorded]) -> Vec<String> {
+    recorded.iter().map(|(m, p, _)| format!("{} {}", m, p)).collect()
+}
+
+/// Unified diff adding every line of `content`
+pub fn added_patch(content: &str) -> String {
+    let lines: Vec<_> = content.lines().collect();
+    let mut patch = format!("@@ -0,0 +1,{} @@\n", lines.len());
+    for line in lines {
+        patch.push_str(&format!("+{}\n", line));
+    }
+    patch
+}
+
+/// Pull request the Azure DevOps stub serves
+pub struct AdoPullRequest {
+    /// Collection path the API lives under, e.g. `/tfs/DefaultCollection`
+    pub collection: &'static str,
+    pub api_version: &'static str,
+    /// Repository path of the changed file, e.g. `/infra/main.bicep`
+    pub path: &'static str,
+    /// Version on the source branch
+    pub head: String,
+    /// Version on the target branch; `None` for an added file
+    pub base: Option<String>,
+    /// Existing PR threads, as returned by the threads API
+    pub threads: Value,
+}
+
+/// Routes of the Azure DevOps Git API for project `proj`, repository `repo`
+/// (id `repo-guid`) and pull request 5 from branch `feature`. Requests
+/// without the expected `api-version` are not found.
+pub fn azure_devops_routes(pr: AdoPullRequest) -> Routes {
+    Arc::new(move |method: &str, path: &str, _body: &Value| {
+        let (route, query) = path.split_once('?').unwrap_or((path, ""));
+        let version = format!("api-version={}", pr.api_version);
+        let repos = format!("{}/proj/_apis/git/repositories/", pr.collection);
+        let not_found = (404, serde_json::json!({ "message": "not found" }).to_string());
+        let rest = match route.strip_prefix(&repos) {
+            Some(rest) if query.split('&').any(|q| q == version) => rest,
+            _ => return not_found,
+        };
+        let ok = |v: Value| (200, v.to_string());
+        let change_type = if pr.base.is_some() { "edit" } else { "add" };
+        match (method, rest) {
+            ("GET", "repo") => ok(serde_json::json!({ "id": "repo-guid" })),
+            ("GET", "repo-guid/pullRequests/5") => ok(serde_json::json!({ "sourceRefName": "refs/heads/feature" })),
+            ("GET", "repo-guid/pullRequests/5/iterations") => ok(serde_json::json!({ "value": [{ "id": 1 }, { "id": 2 }] })),
+            ("GET", "repo-guid/pullRequests/5/iterations/2/changes") => ok(serde_json::json!({
+                "changeEntries": [
+                    {
+                        "changeTrackingId": 1,
+                        "changeId": 1,
+                        "changeType": change_type,
+                        "item": { "objectId": "head-blob", "originalObjectId": "base-blob", "path": pr.path }
+                    },
+                    { "changeTrackingId": 2, "changeId": 2, "changeType": "edit", "item": { "path": "/README.md" } }
+                ]
+            })),
+            ("GET", "repo-guid/items")
+                if query.contains(&format!("path={}", urlencode(pr.path)))
+                    && query.contains("versionDescriptor.version=feature") =>
+            {
+                (200, pr.head.clone())
+            }
+            ("GET", "repo-guid/blobs/base-blob") if query.contains("$format=text") => match &pr.base {
+                Some(base) => (200, base.clone()),
+                None => not_found,
+            },
+            ("GET", "repo-guid/pullRequests/5/threads") => ok(serde_json::json!({ "value": pr.threads })),
+            ("POST", "repo-guid/pullRequests/5/threads") | ("POST", "repo-guid/pullRequests/5/statuses") => {
+                (201, "{}".to_string())
+            }
+            ("PATCH", p) if p.starts_with("repo-guid/pullRequests/5/threads/") => ok(serde_json::json!({})),
+            _ => not_found,
+        }
+    })
+}
+
+/// Percent-encoding of the path characters the tests use
+fn urlencode(text: &str) -> String {
+    text.replace('/', "%2F")
+}
+
+/// Path of an Azure DevOps stub request without its query
+pub fn route(path: &str) -> &str {
+    path.split('?').next().unwrap_or(path)
+}
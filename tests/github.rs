@@ -0,0 +1,109 @@
+// github.rs
+//
+// Runs the `github` subcommand against a local stub of the GitHub REST API
+// and checks the review and check run it posts.
+
+mod common;
+
+use common::{added_patch, posted, request_log, start_stub};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::process::Command;
+use std::sync::Arc;
+
+const PARAMS: usize = 60;
+
+/// Template with PARAMS undocumented, unused parameters: two findings each
+fn main_bicep() -> String {
+    (1..=PARAMS).map(|i| format!("param p{} string\n", i)).collect()
+}
+
+fn respond(method: &str, path: &str, _body: &Value) -> (u16, String) {
+    let main = main_bicep();
+    let route = path.split('?').next().unwrap_or("");
+    let first_page = !path.contains("page=") || path.contains("&page=1");
+    let json = |v: Value| v.to_string();
+    match (method, route) {
+        ("GET", "/repos/o/r/pulls/7") => (
+            200,
+            json(json!({ "head": { "sha": "h1" }, "base": { "sha": "b1" } })),
+        ),
+        ("GET", "/repos/o/r/pulls/7/files") if first_page => (
+            200,
+            json(json!([
+                { "filename": "infra/main.bicep", "status": "added", "patch": added_patch(&main) },
+                { "filename": "README.md", "status": "modified", "patch": "@@ -1 +1 @@\n-a\n+b\n" }
+            ])),
+        ),
+        ("GET", "/repos/o/r/pulls/7/files")
+        | ("GET", "/repos/o/r/pulls/7/comments")
+        | ("GET", "/repos/o/r/pulls/7/reviews") => (200, "[]".to_string()),
+        ("GET", "/repos/o/r/contents/infra/main.bicep") if path.ends_with("ref=h1") => (200, main),
+        ("POST", "/repos/o/r/pulls/7/reviews") => (200, json(json!({ "id": 1 }))),
+        ("POST", "/repos/o/r/check-runs") => (201, json(json!({ "id": 99 }))),
+        ("PATCH", "/repos/o/r/check-runs/99") => (200, json(json!({ "id": 99 }))),
+        _ => (404, json(json!({ "message": "Not Found" }))),
+    }
+}
+
+#[test]
+fn posts_review_and_batched_check_run() {
+    let (url, recorded) = start_stub(Arc::new(respond));
+    let output = Command::new(env!("CARGO_BIN_EXE_bicep_llm_validator"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_clear()
+        .env("GITHUB_REPOSITORY", "o/r")
+        .env("GITHUB_TOKEN", "t")
+        .env("GITHUB_API_URL", &url)
+        .args([
+            "github",
+            "--pull-request",
+            "7",
+            "--best-practices-file",
+            "bicep-best-practices.md",
+            "--rules-only",
+            "--fail-on-severity",
+            "3",
+        ])
+        .output()
+        .expect("run bicep_llm_validator");
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "stderr: {}\nrequests: {:?}",
+        String::from_utf8_lossy(&output.stderr),
+        request_log(&recorded)
+    );
+
+    // One review with a line comment per finding
+    let reviews = posted(&recorded, "POST", "/repos/o/r/pulls/7/reviews");
+    assert_eq!(reviews.len(), 1);
+    let review = reviews[0];
+    assert_eq!(review["commit_id"], "h1");
+    assert_eq!(review["event"], "COMMENT");
+    let comments = review["comments"].as_array().unwrap();
+    assert_eq!(comments.len(), 2 * PARAMS);
+    assert!(comments.iter().all(|c| c["path"] == "infra/main.bicep" && c["side"] == "RIGHT"));
+    assert!(comments.iter().all(|c| c["body"].as_str().unwrap().contains("<!-- bicep-reviewer:finding:")));
+    let lines: HashSet<_> = comments.iter().map(|c| c["line"].as_u64().unwrap()).collect();
+    assert_eq!(lines, (1..=PARAMS as u64).collect());
+
+    // The check run is created with the first 50 annotations and updated with the rest
+    let runs = posted(&recorded, "POST", "/repos/o/r/check-runs");
+    assert_eq!(runs.len(), 1);
+    let run = runs[0];
+    assert_eq!(run["head_sha"], "h1");
+    assert_eq!(run["status"], "completed");
+    assert_eq!(run["conclusion"], "failure");
+    assert_eq!(run["output"]["annotations"].as_array().unwrap().len(), 50);
+    assert_eq!(run["output"]["annotations"][0]["annotation_level"], "failure");
+
+    let updates = posted(&recorded, "PATCH", "/repos/o/r/check-runs/99");
+    let batches: Vec<_> = updates
+        .iter()
+        .map(|u| u["output"]["annotations"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(batches, vec![50, 2 * PARAMS - 100]);
+    assert!(updates.iter().all(|u| u["output"]["title"] == run["output"]["title"]));
+}
@@ -0,0 +1,156 @@
+// gitlab.rs
+//
+// Runs the `gitlab` subcommand against a local stub of the GitLab REST API
+// and checks the discussions, summary note and commit status it posts.
+
+mod common;
+
+use common::{added_patch, posted, request_log, start_stub, Recorded};
+use serde_json::{json, Value};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+const MR: &str = "/api/v4/projects/g%2Fp/merge_requests/3";
+const MAIN: &str = "param p1 string\nparam p2 string\n";
+
+/// Stub answering with `discussions` as the merge request's discussions
+fn run(discussions: Value) -> (Option<i32>, String, Vec<Recorded>) {
+    let discussions = Mutex::new(discussions);
+    let (url, recorded) = start_stub(Arc::new(move |method: &str, path: &str, _body: &Value| {
+        let route = path.split('?').next().unwrap_or("");
+        let first_page = path.contains("page=1");
+        let mr_path = route.strip_prefix(MR);
+        let ok = |v: Value| (200, v.to_string());
+        match (method, mr_path) {
+            ("GET", Some("")) => ok(json!({
+                "diff_refs": { "base_sha": "b1", "start_sha": "b1", "head_sha": "h1" }
+            })),
+            ("GET", Some("/diffs")) if first_page => ok(json!([
+                { "old_path": "infra/main.bicep", "new_path": "infra/main.bicep", "new_file": true, "diff": added_patch(MAIN) },
+                { "old_path": "README.md", "new_path": "README.md", "diff": "@@ -1 +1 @@\n-a\n+b\n" }
+            ])),
+            ("GET", Some("/discussions")) if first_page => ok(discussions.lock().unwrap().clone()),
+            ("GET", Some(_)) => ok(json!([])),
+            ("POST", Some("/discussions")) | ("POST", Some("/notes")) => (201, "{}".to_string()),
+            ("PUT", Some(p)) if p.starts_with("/discussions/") || p.starts_with("/notes/") => ok(json!({})),
+            ("GET", None) if route == "/api/v4/projects/g%2Fp/repository/files/infra%2Fmain.bicep/raw" => {
+                (200, MAIN.to_string())
+            }
+            ("POST", None) if route == "/api/v4/projects/g%2Fp/statuses/h1" => (201, "{}".to_string()),
+            _ => (404, json!({ "message": "404 Not Found" }).to_string()),
+        }
+    }));
+    let output = Command::new(env!("CARGO_BIN_EXE_bicep_llm_validator"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_clear()
+        .args([
+            "gitlab",
+            "--project",
+            "g/p",
+            "--merge-request-iid",
+            "3",
+            "--token",
+            "t",
+            "--api-url",
+            &format!("{}/api/v4", url),
+            "--best-practices-file",
+            "bicep-best-practices.md",
+            "--rules-only",
+            "--fail-on-severity",
+            "3",
+        ])
+        .output()
+        .expect("run bicep_llm_validator");
+    let recorded = recorded.lock().unwrap().clone();
+    (
+        output.status.code(),
+        format!("stderr: {}\nrequests: {:?}", String::from_utf8_lossy(&output.stderr), request_log(&recorded)),
+        recorded,
+    )
+}
+
+/// Discussion holding one note by the tool (user 1)
+fn discussion(id: &str, body: &str, resolved_by: Option<u64>) -> Value {
+    json!({
+        "id": id,
+        "notes": [{
+            "id": 1,
+            "body": body,
+            "resolvable": true,
+            "resolved": resolved_by.is_some(),
+            "author": { "id": 1 },
+            "resolved_by": resolved_by.map(|id| json!({ "id": id })),
+        }]
+    })
+}
+
+fn puts(recorded: &[Recorded]) -> Vec<String> {
+    let mut out: Vec<_> = recorded
+        .iter()
+        .filter(|(m, p, _)| m == "PUT" && p.contains("/discussions/"))
+        .map(|(_, p, _)| p.trim_start_matches(MR).to_string())
+        .collect();
+    out.sort();
+    out
+}
+
+#[test]
+fn posts_discussions_summary_and_status() {
+    let (code, log, recorded) = run(json!([]));
+    assert_eq!(code, Some(1), "{}", log);
+
+    // Two undocumented, unused parameters: two findings on each added line
+    let created = posted(&recorded, "POST", &format!("{}/discussions", MR));
+    assert_eq!(created.len(), 4, "{}", log);
+    for d in &created {
+        let position = &d["position"];
+        assert_eq!(position["head_sha"], "h1");
+        assert_eq!(position["new_path"], "infra/main.bicep");
+        assert!(position.get("old_line").is_none());
+        assert!(d["body"].as_str().unwrap().contains("<!-- bicep-reviewer:finding:"));
+    }
+    let mut lines: Vec<_> = created.iter().map(|d| d["position"]["new_line"].as_u64().unwrap()).collect();
+    lines.sort();
+    assert_eq!(lines, vec![1, 1, 2, 2]);
+
+    let notes = posted(&recorded, "POST", &format!("{}/notes", MR));
+    assert_eq!(notes.len(), 1);
+    assert!(notes[0]["body"].as_str().unwrap().contains("<!-- bicep-reviewer:summary -->"));
+
+    let statuses = posted(&recorded, "POST", "/api/v4/projects/g%2Fp/statuses/h1");
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0]["state"], "failed");
+}
+
+#[test]
+fn reopens_returning_findings_and_resolves_gone_ones() {
+    let (_, log, first) = run(json!([]));
+    let bodies: Vec<String> = posted(&first, "POST", &format!("{}/discussions", MR))
+        .iter()
+        .map(|d| d["body"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(bodies.len(), 4, "{}", log);
+
+    let gone = "Fixed\n\n<!-- bicep-reviewer:finding:gone -->";
+    let (code, log, recorded) = run(json!([
+        // Resolved by the tool when the finding was gone: reopened
+        discussion("d0", &bodies[0], Some(1)),
+        // Resolved by a reviewer: left alone
+        discussion("d1", &bodies[1], Some(2)),
+        // Still open: left alone
+        discussion("d2", &bodies[2], None),
+        // Finding no longer reported: resolved
+        discussion("d3", gone, None),
+    ]));
+    assert_eq!(code, Some(1), "{}", log);
+
+    assert_eq!(
+        puts(&recorded),
+        vec!["/discussions/d0?resolved=false", "/discussions/d3?resolved=true"],
+        "{}",
+        log
+    );
+    let created = posted(&recorded, "POST", &format!("{}/discussions", MR));
+    assert_eq!(created.len(), 1, "{}", log);
+    assert_eq!(created[0]["body"].as_str().unwrap(), bodies[3]);
+}
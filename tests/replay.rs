@@ -0,0 +1,175 @@
+// replay.rs
+//
+// Runs the binary on example.bicep with the LLM and search responses
+// recorded in tests/fixtures/replay, so the whole review pipeline is
+// exercised offline. After a prompt change the fixtures have to be
+// re-recorded with `--record tests/fixtures/replay`. The `azure` run reviews
+// the same file in a pull request served by a stub Azure DevOps server.
+
+mod common;
+
+use common::{azure_devops_routes, request_log, route, start_stub, AdoPullRequest};
+use serde_json::{json, Value};
+use std::process::{Command, Output};
+
+const FIXTURES: &str = "tests/fixtures/replay";
+
+fn replay(fixtures: &str, extra_args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bicep_llm_validator"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_clear()
+        .args([
+            "--bicep-file",
+            "example.bicep",
+            "--best-practices-file",
+            "bicep-best-practices.md",
+            "--replay",
+            fixtures,
+            "--no-cache",
+            "--format",
+            "json",
+        ])
+        .args(extra_args)
+        .output()
+        .expect("run bicep_llm_validator")
+}
+
+fn findings(output: &Output) -> Vec<Value> {
+    let report: Value = serde_json::from_slice(&output.stdout).expect("JSON report on stdout");
+    report["findings"].as_array().expect("findings array").clone()
+}
+
+#[test]
+fn replay_reports_llm_and_rule_findings() {
+    let output = replay(FIXTURES, &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let summary: Vec<_> = findings(&output)
+        .iter()
+        .map(|f| {
+            (
+                f["category"].as_str().unwrap().to_string(),
+                f["severity"].as_u64().unwrap(),
+                f["location"]["start_line"].as_u64().unwrap(),
+                f["rule_id"].as_str().map(str::to_string),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("Resources".to_string(), 4, 6, None),
+            ("Naming".to_string(), 3, 3, None),
+            ("Parameters".to_string(), 3, 1, None),
+            (
+                "Parameters".to_string(),
+                3,
+                1,
+                Some("parameters/description-required".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn replay_fails_policy_on_high_severity() {
+    let output = replay(FIXTURES, &["--fail-on-severity", "4"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(findings(&output).len(), 4);
+}
+
+#[test]
+fn replay_without_fixture_is_runtime_error() {
+    // The parent directory holds no recorded responses
+    let output = replay("tests/fixtures", &[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No recorded"));
+}
+
+#[test]
+fn missing_baseline_fails_before_any_llm_call() {
+    // Without recorded responses a review would fail with a runtime error
+    let output = replay("tests/fixtures", &["--baseline", "tests/fixtures/missing-baseline.json"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("No recorded"));
+}
+
+#[test]
+fn replay_azure_posts_threads_and_status() {
+    let (url, recorded) = start_stub(azure_devops_routes(AdoPullRequest {
+        collection: "/DefaultCollection",
+        api_version: "7.1",
+        path: "/example.bicep",
+        head: std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/example.bicep")).unwrap(),
+        base: None,
+        threads: json!([]),
+    }));
+    let output = Command::new(env!("CARGO_BIN_EXE_bicep_llm_validator"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_clear()
+        .args([
+            "azure",
+            "--organization",
+            &format!("{}/DefaultCollection", url),
+            "--project",
+            "proj",
+            "--repository",
+            "repo",
+            "--pull-request-id",
+            "5",
+            "--pat",
+            "pat",
+            "--best-practices-file",
+            "bicep-best-practices.md",
+            "--replay",
+            FIXTURES,
+            "--no-cache",
+            "--full-review",
+            "--fail-on-severity",
+            "4",
+        ])
+        .output()
+        .expect("run bicep_llm_validator");
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "stderr: {}\nrequests: {:?}",
+        String::from_utf8_lossy(&output.stderr),
+        request_log(&recorded)
+    );
+
+    let threads_route = "/DefaultCollection/proj/_apis/git/repositories/repo-guid/pullRequests/5/threads";
+    let threads: Vec<_> = recorded
+        .iter()
+        .filter(|(m, p, _)| m == "POST" && route(p) == threads_route)
+        .map(|(_, _, body)| body)
+        .collect();
+    // The four replayed findings on their lines, then the summary
+    let anchored: Vec<_> = threads
+        .iter()
+        .filter_map(|t| t["threadContext"].as_object())
+        .map(|c| (c["filePath"].as_str().unwrap().to_string(), c["rightFileStart"]["line"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(
+        anchored,
+        vec![
+            ("/example.bicep".to_string(), 6),
+            ("/example.bicep".to_string(), 3),
+            ("/example.bicep".to_string(), 1),
+            ("/example.bicep".to_string(), 1),
+        ]
+    );
+    let summary = threads.last().unwrap();
+    assert!(summary.get("threadContext").is_none_or(Value::is_null));
+    assert!(summary["comments"][0]["content"].as_str().unwrap().contains("<!-- bicep-reviewer:summary -->"));
+
+    let statuses: Vec<_> = recorded
+        .iter()
+        .filter(|(m, p, _)| m == "POST" && route(p).ends_with("/pullRequests/5/statuses"))
+        .map(|(_, _, body)| body)
+        .collect();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0]["state"], "failed");
+    assert_eq!(statuses[0]["context"], json!({ "genre": "bicep-reviewer", "name": "review" }));
+}